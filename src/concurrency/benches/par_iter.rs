@@ -0,0 +1,87 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use concurrency::par_iter::{default_threads, ParallelSlice};
+
+const RUNS: u32 = 5;
+
+// same recursion as an exercise would write it, deliberately slow
+fn fibonacci(n: u64) -> u64 {
+    if n < 2 {
+        n
+    } else {
+        fibonacci(n - 1) + fibonacci(n - 2)
+    }
+}
+
+fn is_prime(n: u64) -> bool {
+    n >= 2
+        && (2..)
+            .take_while(|d| d * d <= n)
+            .all(|d| !n.is_multiple_of(d))
+}
+
+/// Average time of `RUNS` runs of `f`.
+fn time<R>(mut f: impl FnMut() -> R) -> Duration {
+    black_box(f()); // warm up
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        black_box(f());
+    }
+    start.elapsed() / RUNS
+}
+
+fn compare(name: &str, sequential: Duration, parallel: Duration) {
+    println!(
+        "{:<28} sequential {:>10.2?}   parallel {:>10.2?}   speedup {:.2}x",
+        name,
+        sequential,
+        parallel,
+        sequential.as_secs_f64() / parallel.as_secs_f64()
+    );
+}
+
+fn main() {
+    println!(
+        "Benchmarking with {} threads, average of {} runs\n",
+        default_threads(),
+        RUNS
+    );
+
+    // uneven work: later elements are much more expensive, which is where stealing helps
+    let positions: Vec<u64> = (0..32).map(|i| i % 28).collect();
+    compare(
+        "map fibonacci",
+        time(|| positions.iter().map(|&n| fibonacci(n)).collect::<Vec<_>>()),
+        time(|| positions.par_map(|&n| fibonacci(n))),
+    );
+
+    let numbers: Vec<u64> = (0..200_000).collect();
+    compare(
+        "filter primes",
+        time(|| numbers.iter().filter(|&&n| is_prime(n)).count()),
+        time(|| numbers.par_filter(|&n| is_prime(n)).len()),
+    );
+
+    // cheap work per element, the overhead of spawning threads shows here
+    let values: Vec<u64> = (0..2_000_000).collect();
+    compare(
+        "reduce sum",
+        time(|| values.iter().copied().reduce(|a, b| a + b)),
+        time(|| values.par_reduce(|a, b| a + b)),
+    );
+
+    compare(
+        "for_each fibonacci(20)",
+        time(|| {
+            numbers[..2_000].iter().for_each(|_| {
+                black_box(fibonacci(black_box(20)));
+            })
+        }),
+        time(|| {
+            numbers[..2_000].par_for_each(|_| {
+                black_box(fibonacci(black_box(20)));
+            })
+        }),
+    );
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::sync::{Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use concurrency::par_iter::default_threads;
+use concurrency::sharded_map::ShardedMap;
+
+const KEYS: u64 = 10_000;
+const OPS_PER_THREAD: u64 = 200_000;
+const RUNS: u32 = 3;
+
+/// The operations every contender has to support.
+trait Map: Sync {
+    fn get(&self, key: u64) -> Option<u64>;
+    fn insert(&self, key: u64, value: u64);
+}
+
+impl Map for Mutex<HashMap<u64, u64>> {
+    fn get(&self, key: u64) -> Option<u64> {
+        self.lock().unwrap().get(&key).copied()
+    }
+    fn insert(&self, key: u64, value: u64) {
+        self.lock().unwrap().insert(key, value);
+    }
+}
+
+impl Map for RwLock<HashMap<u64, u64>> {
+    fn get(&self, key: u64) -> Option<u64> {
+        self.read().unwrap().get(&key).copied()
+    }
+    fn insert(&self, key: u64, value: u64) {
+        self.write().unwrap().insert(key, value);
+    }
+}
+
+impl Map for ShardedMap<u64, u64> {
+    fn get(&self, key: u64) -> Option<u64> {
+        ShardedMap::get(self, &key)
+    }
+    fn insert(&self, key: u64, value: u64) {
+        ShardedMap::insert(self, key, value);
+    }
+}
+
+// cheap pseudo random keys, so every thread walks the keys in its own order
+fn next(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+/// Average time for `threads` threads doing a mix with `write_percent` writes.
+fn time(map: &impl Map, threads: u64, write_percent: u64) -> Duration {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        thread::scope(|s| {
+            for t in 0..threads {
+                s.spawn(move || {
+                    let mut state = t * 7919 + 1;
+                    for _ in 0..OPS_PER_THREAD {
+                        let random = next(&mut state);
+                        let key = random % KEYS;
+                        if random / KEYS % 100 < write_percent {
+                            map.insert(key, random);
+                        } else {
+                            black_box(map.get(key));
+                        }
+                    }
+                });
+            }
+        });
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    // a few threads even on small machines, otherwise there is nothing to contend
+    let threads = default_threads().max(4) as u64;
+    println!(
+        "{} threads, {} operations each on {} keys, average of {} runs\n",
+        threads, OPS_PER_THREAD, KEYS, RUNS
+    );
+    println!(
+        "{:<16} {:>14} {:>14} {:>14}",
+        "writes", "Mutex", "RwLock", "ShardedMap"
+    );
+    for write_percent in [1, 10, 50, 100] {
+        let filled = || (0..KEYS).map(|k| (k, k)).collect::<HashMap<_, _>>();
+        let mutex = Mutex::new(filled());
+        let rwlock = RwLock::new(filled());
+        let sharded: ShardedMap<u64, u64> = filled().into_iter().collect();
+        println!(
+            "{:<16} {:>14.2?} {:>14.2?} {:>14.2?}",
+            format!("{}%", write_percent),
+            time(&mutex, threads, write_percent),
+            time(&rwlock, threads, write_percent),
+            time(&sharded, threads, write_percent),
+        );
+    }
+}
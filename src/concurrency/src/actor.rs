@@ -0,0 +1,544 @@
+//! A lightweight actor abstraction on top of `mpsc` channels.
+//!
+//! An actor is a value that lives on its own thread and is only reachable
+//! through its typed mailbox: [`Addr::send`] for fire-and-forget messages
+//! and [`Addr::ask`] for request/response. The [`ActorSystem`] restarts
+//! actors that panic (according to their [`Supervision`]) and stops all of
+//! them in an orderly way on shutdown.
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+pub trait Actor: Sized + Send + 'static {
+    type Message: Send + 'static;
+
+    /// Handle one message, called on the actor's own thread.
+    fn handle(&mut self, message: Self::Message, ctx: &Context<Self>);
+
+    /// Called before the first message (and again after every restart).
+    fn started(&mut self, _ctx: &Context<Self>) {}
+
+    /// Called once the actor stops for good, but not after a panic.
+    fn stopped(&mut self) {}
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ActorError {
+    /// The actor has stopped, its mailbox is closed.
+    Stopped,
+    /// The actor dropped the reply handle without answering, e.g. by panicking.
+    NoReply,
+    /// `ask_timeout` gave up waiting for the reply.
+    Timeout,
+}
+
+impl fmt::Display for ActorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ActorError::Stopped => write!(f, "actor has stopped"),
+            ActorError::NoReply => write!(f, "actor did not reply"),
+            ActorError::Timeout => write!(f, "timed out waiting for reply"),
+        }
+    }
+}
+
+impl std::error::Error for ActorError {}
+
+/// What the system does when an actor panics while handling a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Supervision {
+    /// Stop the actor, its mailbox gets closed.
+    Stop,
+    /// Create a fresh actor from the factory and continue with the next
+    /// message, at most `max_restarts` times.
+    Restart { max_restarts: usize },
+}
+
+enum Envelope<M> {
+    Message(M),
+    Stop,
+}
+
+/// Handle to answer an `ask` request, send it along inside the message.
+pub struct ReplyTo<R> {
+    sender: mpsc::Sender<R>,
+}
+
+impl<R> ReplyTo<R> {
+    /// Answer the request, the asking side may already have given up.
+    pub fn reply(self, value: R) {
+        let _ = self.sender.send(value);
+    }
+}
+
+/// Address of an actor, used to send messages to it.
+pub struct Addr<A: Actor> {
+    sender: mpsc::Sender<Envelope<A::Message>>,
+    restarts: Arc<AtomicUsize>,
+    alive: Arc<AtomicBool>,
+}
+
+impl<A: Actor> Clone for Addr<A> {
+    fn clone(&self) -> Self {
+        Addr {
+            sender: self.sender.clone(),
+            restarts: Arc::clone(&self.restarts),
+            alive: Arc::clone(&self.alive),
+        }
+    }
+}
+
+impl<A: Actor> Addr<A> {
+    /// Put a message into the mailbox without waiting for it to be handled.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ActorError::Stopped` if the actor has stopped.
+    pub fn send(&self, message: A::Message) -> Result<(), ActorError> {
+        self.sender
+            .send(Envelope::Message(message))
+            .map_err(|_| ActorError::Stopped)
+    }
+
+    /// Send a request and block until the actor replies.
+    ///
+    /// `make_message` gets the `ReplyTo` handle the actor has to answer with.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ActorError::Stopped` if the actor has stopped and
+    /// `ActorError::NoReply` if it never answered.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use concurrency::actor::{Actor, ActorSystem, Context, ReplyTo, Supervision};
+    /// struct Counter(u32);
+    /// enum Msg {
+    ///     Add(u32),
+    ///     Get(ReplyTo<u32>),
+    /// }
+    /// impl Actor for Counter {
+    ///     type Message = Msg;
+    ///     fn handle(&mut self, message: Msg, _ctx: &Context<Self>) {
+    ///         match message {
+    ///             Msg::Add(n) => self.0 += n,
+    ///             Msg::Get(reply) => reply.reply(self.0),
+    ///         }
+    ///     }
+    /// }
+    /// let mut system = ActorSystem::new();
+    /// let counter = system.spawn(|| Counter(0), Supervision::Stop);
+    /// counter.send(Msg::Add(2)).unwrap();
+    /// assert_eq!(counter.ask(Msg::Get), Ok(2));
+    /// system.shutdown();
+    /// ```
+    pub fn ask<R>(
+        &self,
+        make_message: impl FnOnce(ReplyTo<R>) -> A::Message,
+    ) -> Result<R, ActorError> {
+        let (sender, receiver) = mpsc::channel();
+        self.send(make_message(ReplyTo { sender }))?;
+        receiver.recv().map_err(|_| ActorError::NoReply)
+    }
+
+    /// Like `ask`, but gives up after `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// Additionally returns `ActorError::Timeout` if there was no reply in time.
+    pub fn ask_timeout<R>(
+        &self,
+        make_message: impl FnOnce(ReplyTo<R>) -> A::Message,
+        timeout: Duration,
+    ) -> Result<R, ActorError> {
+        let (sender, receiver) = mpsc::channel();
+        self.send(make_message(ReplyTo { sender }))?;
+        receiver.recv_timeout(timeout).map_err(|e| match e {
+            RecvTimeoutError::Timeout => ActorError::Timeout,
+            RecvTimeoutError::Disconnected => ActorError::NoReply,
+        })
+    }
+
+    /// Ask the actor to stop after the messages already in its mailbox.
+    pub fn stop(&self) {
+        let _ = self.sender.send(Envelope::Stop);
+    }
+
+    /// How often the actor was restarted after a panic.
+    pub fn restarts(&self) -> usize {
+        self.restarts.load(Ordering::SeqCst)
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
+    }
+}
+
+/// Passed to every handler, gives the actor access to its own address.
+pub struct Context<A: Actor> {
+    addr: Addr<A>,
+    stopping: AtomicBool,
+}
+
+impl<A: Actor> Context<A> {
+    pub fn addr(&self) -> Addr<A> {
+        self.addr.clone()
+    }
+
+    /// Stop the actor right after the current message.
+    pub fn stop(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Owns the actor threads and shuts them down in order.
+#[derive(Default)]
+pub struct ActorSystem {
+    // stops one actor, type erased so actors of any type fit in one list
+    stoppers: Vec<Box<dyn Fn() + Send>>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl ActorSystem {
+    pub fn new() -> ActorSystem {
+        ActorSystem {
+            stoppers: Vec::new(),
+            handles: Vec::new(),
+        }
+    }
+
+    /// Start an actor on its own thread.
+    ///
+    /// The factory is used to create the actor and to recreate it after a
+    /// panic if `supervision` allows restarts.
+    pub fn spawn<A, F>(&mut self, factory: F, supervision: Supervision) -> Addr<A>
+    where
+        A: Actor,
+        F: Fn() -> A + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let addr = Addr {
+            sender,
+            restarts: Arc::new(AtomicUsize::new(0)),
+            alive: Arc::new(AtomicBool::new(true)),
+        };
+        let ctx = Context {
+            addr: addr.clone(),
+            stopping: AtomicBool::new(false),
+        };
+        let handle = thread::spawn(move || run(factory, supervision, receiver, ctx));
+
+        let stopper = addr.clone();
+        self.stoppers.push(Box::new(move || stopper.stop()));
+        self.handles.push(handle);
+        addr
+    }
+
+    /// Stop all actors, newest first, and wait for their threads.
+    ///
+    /// Every actor finishes the messages that are already in its mailbox.
+    pub fn shutdown(mut self) {
+        self.stop_all();
+    }
+
+    fn stop_all(&mut self) {
+        // stop in reverse spawn order: later actors usually depend on earlier ones
+        while let Some(stop) = self.stoppers.pop() {
+            stop();
+            if let Some(handle) = self.handles.pop() {
+                // only a panic in `stopped` gets here, the actor is gone either way
+                let _ = handle.join();
+            }
+        }
+    }
+}
+
+impl Drop for ActorSystem {
+    fn drop(&mut self) {
+        self.stop_all();
+    }
+}
+
+/// The actor's thread: create the actor and handle messages until stopped.
+fn run<A, F>(
+    factory: F,
+    supervision: Supervision,
+    receiver: mpsc::Receiver<Envelope<A::Message>>,
+    ctx: Context<A>,
+) where
+    A: Actor,
+    F: Fn() -> A,
+{
+    let Some(mut actor) = start(&factory, supervision, &ctx) else {
+        ctx.addr.alive.store(false, Ordering::SeqCst);
+        return; // dropping the receiver closes the mailbox
+    };
+    loop {
+        let message = match receiver.recv() {
+            Ok(Envelope::Message(message)) => message,
+            // `ctx` keeps a sender alive, so only a stop request ends the loop
+            Ok(Envelope::Stop) | Err(_) => break,
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| actor.handle(message, &ctx)));
+        if result.is_err() {
+            // the old state may be broken, so start over with a fresh actor
+            let fresh = if restart(supervision, &ctx) {
+                start(&factory, supervision, &ctx)
+            } else {
+                None
+            };
+            match fresh {
+                Some(fresh) => actor = fresh,
+                None => {
+                    ctx.addr.alive.store(false, Ordering::SeqCst);
+                    return;
+                }
+            }
+        }
+        if ctx.stopping.load(Ordering::SeqCst) {
+            break;
+        }
+    }
+    ctx.addr.alive.store(false, Ordering::SeqCst);
+    actor.stopped();
+}
+
+/// Create the actor and call `started`. A panic in either counts like a
+/// panic in `handle`: retried as long as `supervision` allows, `None` after.
+fn start<A, F>(factory: &F, supervision: Supervision, ctx: &Context<A>) -> Option<A>
+where
+    A: Actor,
+    F: Fn() -> A,
+{
+    loop {
+        let started = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut actor = factory();
+            actor.started(ctx);
+            actor
+        }));
+        match started {
+            Ok(actor) => return Some(actor),
+            Err(_) if restart(supervision, ctx) => continue,
+            Err(_) => return None,
+        }
+    }
+}
+
+/// Count a restart if `supervision` allows another one.
+fn restart<A: Actor>(supervision: Supervision, ctx: &Context<A>) -> bool {
+    match supervision {
+        Supervision::Restart { max_restarts } if ctx.addr.restarts() < max_restarts => {
+            ctx.addr.restarts.fetch_add(1, Ordering::SeqCst);
+            true
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct Counter {
+        count: i32,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    enum CounterMsg {
+        Add(i32),
+        Get(ReplyTo<i32>),
+        Crash,
+        StopMe,
+        Silent(ReplyTo<i32>),
+    }
+
+    impl Actor for Counter {
+        type Message = CounterMsg;
+
+        fn handle(&mut self, message: CounterMsg, ctx: &Context<Self>) {
+            match message {
+                CounterMsg::Add(n) => self.count += n,
+                CounterMsg::Get(reply) => reply.reply(self.count),
+                CounterMsg::Crash => panic!("counter crashed on purpose"),
+                CounterMsg::StopMe => ctx.stop(),
+                CounterMsg::Silent(_reply) => {} // dropped without answering
+            }
+        }
+
+        fn started(&mut self, _ctx: &Context<Self>) {
+            self.log.lock().unwrap().push(String::from("started"));
+        }
+
+        fn stopped(&mut self) {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("stopped at {}", self.count));
+        }
+    }
+
+    fn counter(
+        system: &mut ActorSystem,
+        supervision: Supervision,
+    ) -> (Addr<Counter>, Arc<Mutex<Vec<String>>>) {
+        let log = Arc::new(Mutex::new(vec![]));
+        let factory_log = Arc::clone(&log);
+        let addr = system.spawn(
+            move || Counter {
+                count: 0,
+                log: Arc::clone(&factory_log),
+            },
+            supervision,
+        );
+        (addr, log)
+    }
+
+    #[test]
+    fn handles_messages_in_order() {
+        let mut system = ActorSystem::new();
+        let (addr, _) = counter(&mut system, Supervision::Stop);
+        for i in 1..=10 {
+            addr.send(CounterMsg::Add(i)).unwrap();
+        }
+        assert_eq!(addr.ask(CounterMsg::Get), Ok(55));
+    }
+    #[test]
+    fn restarts_with_fresh_state_after_panic() {
+        let mut system = ActorSystem::new();
+        let (addr, log) = counter(&mut system, Supervision::Restart { max_restarts: 1 });
+        addr.send(CounterMsg::Add(5)).unwrap();
+        addr.send(CounterMsg::Crash).unwrap();
+        assert_eq!(addr.ask(CounterMsg::Get), Ok(0));
+        assert_eq!(addr.restarts(), 1);
+        assert_eq!(*log.lock().unwrap(), vec!["started", "started"]);
+        // the second panic exceeds `max_restarts`
+        addr.send(CounterMsg::Crash).unwrap();
+        assert!(matches!(
+            addr.ask(CounterMsg::Get),
+            Err(ActorError::NoReply | ActorError::Stopped)
+        ));
+        assert!(!addr.is_alive());
+        assert_eq!(addr.send(CounterMsg::Add(1)), Err(ActorError::Stopped));
+    }
+    #[test]
+    fn stop_supervision_closes_mailbox() {
+        let mut system = ActorSystem::new();
+        let (addr, log) = counter(&mut system, Supervision::Stop);
+        addr.send(CounterMsg::Crash).unwrap();
+        assert!(matches!(
+            addr.ask(CounterMsg::Get),
+            Err(ActorError::NoReply | ActorError::Stopped)
+        ));
+        // `stopped` is not called after a panic
+        assert_eq!(*log.lock().unwrap(), vec!["started"]);
+    }
+    #[test]
+    fn panics_while_starting_are_supervised() {
+        struct Fragile;
+        impl Actor for Fragile {
+            type Message = ReplyTo<&'static str>;
+            fn handle(&mut self, reply: ReplyTo<&'static str>, _ctx: &Context<Self>) {
+                reply.reply("up");
+            }
+        }
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let mut system = ActorSystem::new();
+        let factory_attempts = Arc::clone(&attempts);
+        // the first two attempts panic
+        let addr = system.spawn(
+            move || {
+                if factory_attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                    panic!("not ready yet");
+                }
+                Fragile
+            },
+            Supervision::Restart { max_restarts: 2 },
+        );
+        assert_eq!(addr.ask(|reply| reply), Ok("up"));
+        assert_eq!(addr.restarts(), 2);
+
+        struct Broken;
+        impl Actor for Broken {
+            type Message = ();
+            fn handle(&mut self, _: (), _ctx: &Context<Self>) {}
+            fn started(&mut self, _ctx: &Context<Self>) {
+                panic!("broken on start");
+            }
+        }
+        let broken = system.spawn(|| Broken, Supervision::Restart { max_restarts: 1 });
+        while broken.is_alive() {
+            thread::yield_now();
+        }
+        assert_eq!(broken.restarts(), 1);
+        assert_eq!(broken.send(()), Err(ActorError::Stopped));
+        // dropping the system joins the dead thread without panicking
+        drop(system);
+    }
+    #[test]
+    fn missing_reply_is_reported() {
+        let mut system = ActorSystem::new();
+        let (addr, _) = counter(&mut system, Supervision::Stop);
+        assert_eq!(addr.ask(CounterMsg::Silent), Err(ActorError::NoReply));
+        assert_eq!(
+            addr.ask_timeout(CounterMsg::Get, Duration::from_secs(5)),
+            Ok(0)
+        );
+    }
+    #[test]
+    fn actor_can_stop_itself() {
+        let mut system = ActorSystem::new();
+        let (addr, log) = counter(&mut system, Supervision::Stop);
+        addr.send(CounterMsg::Add(3)).unwrap();
+        addr.send(CounterMsg::StopMe).unwrap();
+        // stopping happens right after the message, so this one is never handled
+        assert!(matches!(
+            addr.ask(CounterMsg::Get),
+            Err(ActorError::NoReply | ActorError::Stopped)
+        ));
+        assert_eq!(log.lock().unwrap().last().unwrap(), "stopped at 3");
+    }
+    #[test]
+    fn shutdown_drains_mailboxes_in_reverse_order() {
+        let order = Arc::new(Mutex::new(vec![]));
+        struct Named(&'static str, Arc<Mutex<Vec<&'static str>>>);
+        impl Actor for Named {
+            type Message = ();
+            fn handle(&mut self, _: (), _ctx: &Context<Self>) {
+                thread::sleep(Duration::from_millis(5));
+            }
+            fn stopped(&mut self) {
+                self.1.lock().unwrap().push(self.0);
+            }
+        }
+        let mut system = ActorSystem::new();
+        let first = {
+            let order = Arc::clone(&order);
+            system.spawn(
+                move || Named("first", Arc::clone(&order)),
+                Supervision::Stop,
+            )
+        };
+        let second = {
+            let order = Arc::clone(&order);
+            system.spawn(
+                move || Named("second", Arc::clone(&order)),
+                Supervision::Stop,
+            )
+        };
+        for _ in 0..3 {
+            first.send(()).unwrap();
+            second.send(()).unwrap();
+        }
+        system.shutdown();
+        assert_eq!(*order.lock().unwrap(), vec!["second", "first"]);
+        assert_eq!(first.send(()), Err(ActorError::Stopped));
+        assert!(!second.is_alive());
+    }
+}
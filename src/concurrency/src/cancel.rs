@@ -0,0 +1,418 @@
+//! Cooperative cancellation and scoped tasks.
+//!
+//! A [`CancellationToken`] is a flag that workers check (or wait on) to find
+//! out that they should stop. Child tokens are cancelled together with their
+//! parent, but cancelling a child leaves the parent alone, so a whole tree of
+//! workers can be shut down at once or one branch at a time.
+//!
+//! [`scope`] builds on `thread::scope`: every task gets its own child token,
+//! all tasks are joined before `scope` returns, and the first panic cancels
+//! the remaining tasks and is re-raised with its original payload.
+
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+struct State {
+    cancelled: bool,
+    // children are only referenced weakly, a dropped child just goes away
+    children: Vec<Weak<Inner>>,
+}
+
+struct Inner {
+    state: Mutex<State>,
+    cancelled: Condvar,
+}
+
+impl Inner {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // the state stays consistent even if a holder panicked
+        self.state.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+/// A clonable flag telling workers to stop; clones share the same flag.
+///
+/// # Examples
+///
+/// ```
+/// use std::thread;
+/// use std::time::Duration;
+/// use concurrency::cancel::CancellationToken;
+/// let token = CancellationToken::new();
+/// let worker = {
+///     let token = token.child_token();
+///     thread::spawn(move || {
+///         let mut rounds = 0;
+///         // `wait_timeout` doubles as a sleep that ends early on cancellation
+///         while !token.wait_timeout(Duration::from_millis(1)) {
+///             rounds += 1;
+///         }
+///         rounds
+///     })
+/// };
+/// thread::sleep(Duration::from_millis(10));
+/// token.cancel();
+/// assert!(worker.join().unwrap() > 0);
+/// ```
+#[derive(Clone)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State {
+                    cancelled: false,
+                    children: Vec::new(),
+                }),
+                cancelled: Condvar::new(),
+            }),
+        }
+    }
+
+    /// A new token that is cancelled when this one is. A child of an
+    /// already cancelled token starts out cancelled.
+    pub fn child_token(&self) -> CancellationToken {
+        let child = CancellationToken::new();
+        let mut state = self.inner.lock();
+        if state.cancelled {
+            child.inner.lock().cancelled = true;
+        } else {
+            state.children.retain(|child| child.strong_count() > 0);
+            state.children.push(Arc::downgrade(&child.inner));
+        }
+        child
+    }
+
+    /// Cancel this token and all of its descendants. Cancelling twice does
+    /// nothing.
+    pub fn cancel(&self) {
+        let mut pending = vec![Arc::clone(&self.inner)];
+        // iterative, so deep hierarchies can't overflow the stack
+        while let Some(inner) = pending.pop() {
+            let children = {
+                let mut state = inner.lock();
+                if state.cancelled {
+                    continue;
+                }
+                state.cancelled = true;
+                inner.cancelled.notify_all();
+                std::mem::take(&mut state.children)
+            };
+            // the parent lock is released here, so locks are never nested
+            pending.extend(children.iter().filter_map(Weak::upgrade));
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.lock().cancelled
+    }
+
+    /// Block until the token is cancelled.
+    pub fn wait(&self) {
+        let state = self.inner.lock();
+        let _state = self
+            .inner
+            .cancelled
+            .wait_while(state, |state| !state.cancelled)
+            .unwrap_or_else(|p| p.into_inner());
+    }
+
+    /// Block until the token is cancelled or `timeout` has passed, returns
+    /// whether it was cancelled.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let state = self.inner.lock();
+        let (state, _) = self
+            .inner
+            .cancelled
+            .wait_timeout_while(state, timeout, |state| !state.cancelled)
+            .unwrap_or_else(|p| p.into_inner());
+        state.cancelled
+    }
+
+    /// Like `wait_timeout`, but with a point in time instead of a duration.
+    pub fn wait_until(&self, deadline: Instant) -> bool {
+        self.wait_timeout(deadline.saturating_duration_since(Instant::now()))
+    }
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        CancellationToken::new()
+    }
+}
+
+impl std::fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+type Panic = Box<dyn Any + Send + 'static>;
+
+/// Spawns tasks inside of [`scope`].
+pub struct TaskScope<'scope, 'env: 'scope> {
+    scope: &'scope thread::Scope<'scope, 'env>,
+    token: CancellationToken,
+    first_panic: Arc<Mutex<Option<Panic>>>,
+}
+
+impl<'scope, 'env> TaskScope<'scope, 'env> {
+    /// Run `f` on a new thread. It gets a child token of the scope, which is
+    /// cancelled when the scope's token is or when any task panics.
+    pub fn spawn<F, T>(&self, f: F) -> TaskHandle<'scope, T>
+    where
+        F: FnOnce(&CancellationToken) -> T + Send + 'scope,
+        T: Send + 'scope,
+    {
+        let token = self.token.child_token();
+        let scope_token = self.token.clone();
+        let first_panic = Arc::clone(&self.first_panic);
+        let handle =
+            self.scope.spawn(
+                move || match panic::catch_unwind(AssertUnwindSafe(|| f(&token))) {
+                    Ok(output) => Some(output),
+                    Err(payload) => {
+                        // stop the siblings, they would keep the scope waiting otherwise
+                        scope_token.cancel();
+                        first_panic
+                            .lock()
+                            .unwrap_or_else(|p| p.into_inner())
+                            .get_or_insert(payload);
+                        None
+                    }
+                },
+            );
+        TaskHandle { handle }
+    }
+
+    /// The token shared by all tasks of this scope, cancel it to stop them.
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+/// Handle of a task spawned with [`TaskScope::spawn`].
+pub struct TaskHandle<'scope, T> {
+    handle: thread::ScopedJoinHandle<'scope, Option<T>>,
+}
+
+impl<T> TaskHandle<'_, T> {
+    /// Wait for the task, `None` if it panicked. The panic itself is
+    /// re-raised when the scope ends.
+    pub fn join(self) -> Option<T> {
+        // the task catches its own panic, so the thread itself can't fail
+        self.handle.join().ok().flatten()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+}
+
+/// Cancels the scope when the scope closure itself panics, so that the
+/// implicit join at the end of `thread::scope` can't wait forever.
+struct CancelOnPanic<'a>(&'a CancellationToken);
+
+impl Drop for CancelOnPanic<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.cancel();
+        }
+    }
+}
+
+/// Run `f` with a [`TaskScope`] and wait for all tasks spawned in it.
+///
+/// The tasks get child tokens of `parent`, so cancelling `parent` stops
+/// them. Tasks that only end on cancellation have to be cancelled by `f`
+/// (with `TaskScope::token`) before it returns, otherwise `scope` waits
+/// for them forever.
+///
+/// # Panics
+///
+/// If a task panics, the other tasks are cancelled and, once all of them
+/// are joined, the first panic is resumed with its original payload.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::mpsc;
+/// use std::time::Duration;
+/// use concurrency::cancel::{self, CancellationToken};
+/// let (tx, rx) = mpsc::channel();
+/// let produced = cancel::scope(&CancellationToken::new(), |s| {
+///     let producer = s.spawn(move |token| {
+///         let mut sent = 0;
+///         while !token.is_cancelled() {
+///             tx.send(sent).unwrap();
+///             sent += 1;
+///             token.wait_timeout(Duration::from_millis(1));
+///         }
+///         sent
+///     });
+///     // take a few values, then shut the producer down
+///     let first: Vec<i32> = rx.iter().take(3).collect();
+///     assert_eq!(first, [0, 1, 2]);
+///     s.token().cancel();
+///     producer.join().unwrap()
+/// });
+/// assert!(produced >= 3);
+/// ```
+pub fn scope<'env, F, R>(parent: &CancellationToken, f: F) -> R
+where
+    F: for<'scope> FnOnce(&TaskScope<'scope, 'env>) -> R,
+{
+    let token = parent.child_token();
+    // shared, a local can't be borrowed for the caller-chosen `'env`
+    let first_panic = Arc::new(Mutex::new(None));
+    let output = thread::scope(|scope| {
+        let _guard = CancelOnPanic(&token);
+        f(&TaskScope {
+            scope,
+            token: token.clone(),
+            first_panic: Arc::clone(&first_panic),
+        })
+    });
+    // all tasks are joined, so nobody else holds the lock anymore
+    let payload = first_panic.lock().unwrap_or_else(|p| p.into_inner()).take();
+    if let Some(payload) = payload {
+        panic::resume_unwind(payload);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn cancel_wakes_waiters_and_sticks() {
+        let token = CancellationToken::new();
+        assert!(!token.is_cancelled());
+        assert!(!token.wait_timeout(Duration::from_millis(1)));
+        let waiter = {
+            let token = token.clone();
+            thread::spawn(move || token.wait())
+        };
+        thread::sleep(Duration::from_millis(10));
+        token.cancel();
+        waiter.join().unwrap();
+        token.cancel();
+        assert!(token.is_cancelled());
+        assert!(token.wait_timeout(Duration::from_secs(10)));
+    }
+    #[test]
+    fn children_follow_their_parent_but_not_the_other_way_around() {
+        let root = CancellationToken::new();
+        let child = root.child_token();
+        let grandchild = child.child_token();
+        let sibling = root.child_token();
+
+        child.cancel();
+        assert!(grandchild.is_cancelled());
+        assert!(!root.is_cancelled());
+        assert!(!sibling.is_cancelled());
+
+        root.cancel();
+        assert!(sibling.is_cancelled());
+        assert!(root.child_token().is_cancelled());
+    }
+    #[test]
+    fn dropped_children_are_forgotten() {
+        let root = CancellationToken::new();
+        for _ in 0..100 {
+            drop(root.child_token());
+        }
+        let _kept = root.child_token();
+        assert_eq!(root.inner.lock().children.len(), 1);
+    }
+    #[test]
+    fn deep_hierarchies_cancel_without_recursion() {
+        let root = CancellationToken::new();
+        let mut tokens = vec![root.clone()];
+        for _ in 0..100_000 {
+            let child = tokens.last().unwrap().child_token();
+            tokens.push(child);
+        }
+        root.cancel();
+        assert!(tokens.last().unwrap().is_cancelled());
+    }
+    #[test]
+    fn scope_joins_all_tasks() {
+        let finished = AtomicUsize::new(0);
+        let outputs = scope(&CancellationToken::new(), |s| {
+            let handles: Vec<_> = (0..4)
+                .map(|i| {
+                    let finished = &finished;
+                    s.spawn(move |_| {
+                        thread::sleep(Duration::from_millis(5));
+                        finished.fetch_add(1, Ordering::SeqCst);
+                        i * 10
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(outputs, vec![0, 10, 20, 30]);
+        assert_eq!(finished.load(Ordering::SeqCst), 4);
+    }
+    #[test]
+    fn cancelling_the_parent_stops_the_tasks() {
+        let parent = CancellationToken::new();
+        let stopped = AtomicUsize::new(0);
+        scope(&parent, |s| {
+            for _ in 0..3 {
+                s.spawn(|token| {
+                    token.wait();
+                    stopped.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+            parent.cancel();
+        });
+        assert_eq!(stopped.load(Ordering::SeqCst), 3);
+    }
+    #[test]
+    fn first_panic_cancels_siblings_and_is_resumed() {
+        let parent = CancellationToken::new();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            scope(&parent, |s| {
+                // would run forever without the cancellation
+                let waiter = s.spawn(|token| {
+                    token.wait();
+                    "cancelled"
+                });
+                s.spawn(|_| -> () {
+                    thread::sleep(Duration::from_millis(5));
+                    panic!("worker failed");
+                });
+                assert_eq!(waiter.join(), Some("cancelled"));
+            })
+        }));
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"worker failed"));
+        // only the scope was cancelled, not the caller's token
+        assert!(!parent.is_cancelled());
+    }
+    #[test]
+    fn panicking_scope_closure_cancels_its_tasks() {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            scope(&CancellationToken::new(), |s| {
+                s.spawn(|token| token.wait());
+                panic!("scope closure failed");
+            })
+        }));
+        assert!(result.is_err());
+    }
+}
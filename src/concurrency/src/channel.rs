@@ -0,0 +1,741 @@
+//! A bounded multi-producer, multi-consumer channel.
+//!
+//! Unlike `mpsc::channel`, `send` blocks while the buffer is full, so a fast
+//! producer cannot grow memory without limit. Both ends can be cloned.
+//! Errors are the ones from `std::sync::mpsc`, and disconnection works the
+//! same way: receiving fails once all senders are gone and the buffer is
+//! drained, sending fails once all receivers are gone.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+struct State<T> {
+    queue: VecDeque<T>,
+    senders: usize,
+    receivers: usize,
+    // signals of `Select`s currently waiting on this channel
+    selectors: Vec<Arc<Signal>>,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        // the state is never left half-updated, so a poisoned lock is still usable
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<T> State<T> {
+    fn notify_selectors(&self) {
+        for signal in &self.selectors {
+            signal.notify();
+        }
+    }
+}
+
+/// Create a channel that buffers at most `capacity` messages.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+///
+/// # Examples
+///
+/// ```
+/// use std::thread;
+/// use concurrency::channel;
+/// let (tx, rx) = channel::bounded(2);
+/// thread::spawn(move || {
+///     for i in 0..10 {
+///         tx.send(i).unwrap(); // blocks while two messages are waiting
+///     }
+/// });
+/// assert_eq!(rx.iter().sum::<i32>(), 45);
+/// ```
+pub fn bounded<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "channel capacity must be greater than zero");
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(capacity),
+            senders: 1,
+            receivers: 1,
+            selectors: Vec::new(),
+        }),
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+        capacity,
+    });
+    (
+        Sender {
+            shared: Arc::clone(&shared),
+        },
+        Receiver { shared },
+    )
+}
+
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Send a message, blocking while the channel is full.
+    ///
+    /// # Errors
+    ///
+    /// Gives the message back if all receivers have been dropped.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut state = self.shared.lock();
+        loop {
+            if state.receivers == 0 {
+                return Err(SendError(value));
+            }
+            if state.queue.len() < self.shared.capacity {
+                break;
+            }
+            state = self
+                .shared
+                .not_full
+                .wait(state)
+                .unwrap_or_else(|p| p.into_inner());
+        }
+        self.push(state, value);
+        Ok(())
+    }
+
+    /// Send a message only if there is room for it right now.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TrySendError::Full` if the buffer is full and
+    /// `TrySendError::Disconnected` if all receivers have been dropped.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let state = self.shared.lock();
+        if state.receivers == 0 {
+            return Err(TrySendError::Disconnected(value));
+        }
+        if state.queue.len() == self.shared.capacity {
+            return Err(TrySendError::Full(value));
+        }
+        self.push(state, value);
+        Ok(())
+    }
+
+    /// Like `send`, but gives up after `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TrySendError::Full` if there was no room before the timeout
+    /// and `TrySendError::Disconnected` if all receivers have been dropped.
+    pub fn send_timeout(&self, value: T, timeout: Duration) -> Result<(), TrySendError<T>> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.lock();
+        loop {
+            if state.receivers == 0 {
+                return Err(TrySendError::Disconnected(value));
+            }
+            if state.queue.len() < self.shared.capacity {
+                break;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(TrySendError::Full(value));
+            }
+            state = self
+                .shared
+                .not_full
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|p| p.into_inner())
+                .0;
+        }
+        self.push(state, value);
+        Ok(())
+    }
+
+    fn push(&self, mut state: MutexGuard<'_, State<T>>, value: T) {
+        state.queue.push_back(value);
+        state.notify_selectors();
+        drop(state);
+        self.shared.not_empty.notify_one();
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        Sender {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            // wake up every blocked receiver so it can see the disconnect
+            state.notify_selectors();
+            drop(state);
+            self.shared.not_empty.notify_all();
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Receive a message, blocking until one is available.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RecvError` once the channel is empty and all senders are gone.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(value) = state.queue.pop_front() {
+                drop(state);
+                self.shared.not_full.notify_one();
+                return Ok(value);
+            }
+            if state.senders == 0 {
+                return Err(RecvError);
+            }
+            state = self
+                .shared
+                .not_empty
+                .wait(state)
+                .unwrap_or_else(|p| p.into_inner());
+        }
+    }
+
+    /// Receive a message only if one is waiting right now.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TryRecvError::Empty` if there is no message yet and
+    /// `TryRecvError::Disconnected` if there will never be one.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.shared.lock();
+        match state.queue.pop_front() {
+            Some(value) => {
+                drop(state);
+                self.shared.not_full.notify_one();
+                Ok(value)
+            }
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Like `recv`, but gives up after `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RecvTimeoutError::Timeout` if no message arrived in time and
+    /// `RecvTimeoutError::Disconnected` if there will never be one.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(value) = state.queue.pop_front() {
+                drop(state);
+                self.shared.not_full.notify_one();
+                return Ok(value);
+            }
+            if state.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self
+                .shared
+                .not_empty
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|p| p.into_inner())
+                .0;
+        }
+    }
+
+    /// Number of messages currently buffered.
+    pub fn len(&self) -> usize {
+        self.shared.lock().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity
+    }
+
+    /// Blocking iterator that ends when the channel is disconnected.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { receiver: self }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().receivers += 1;
+        Receiver {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.receivers -= 1;
+        if state.receivers == 0 {
+            // wake up every blocked sender so it can see the disconnect
+            drop(state);
+            self.shared.not_full.notify_all();
+        }
+    }
+}
+
+pub struct Iter<'a, T> {
+    receiver: &'a Receiver<T>,
+}
+
+impl<T> Iterator for Iter<'_, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.receiver.recv().ok()
+    }
+}
+
+pub struct IntoIter<T> {
+    receiver: Receiver<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.receiver.recv().ok()
+    }
+}
+
+// enables `for received in rx { ... }` just like with `mpsc::Receiver`
+impl<T> IntoIterator for Receiver<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { receiver: self }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// Wakes up a waiting `Select` when one of its channels changes.
+struct Signal {
+    fired: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Signal {
+    fn notify(&self) {
+        *self.fired.lock().unwrap_or_else(|p| p.into_inner()) = true;
+        self.condvar.notify_all();
+    }
+
+    /// Wait until notified or until the deadline passes, then reset.
+    fn wait(&self, deadline: Option<Instant>) {
+        let mut fired = self.fired.lock().unwrap_or_else(|p| p.into_inner());
+        while !*fired {
+            match deadline {
+                None => fired = self.condvar.wait(fired).unwrap_or_else(|p| p.into_inner()),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    fired = self
+                        .condvar
+                        .wait_timeout(fired, deadline - now)
+                        .unwrap_or_else(|p| p.into_inner())
+                        .0;
+                }
+            }
+        }
+        *fired = false;
+    }
+}
+
+/// Type-erased view of a receiver, so one `Select` can hold receivers of
+/// different message types.
+trait Selectable {
+    /// A message is waiting or the channel is disconnected.
+    fn is_ready(&self) -> bool;
+    fn register(&self, signal: &Arc<Signal>);
+    fn unregister(&self, signal: &Arc<Signal>);
+}
+
+impl<T> Selectable for Receiver<T> {
+    fn is_ready(&self) -> bool {
+        let state = self.shared.lock();
+        !state.queue.is_empty() || state.senders == 0
+    }
+
+    fn register(&self, signal: &Arc<Signal>) {
+        self.shared.lock().selectors.push(Arc::clone(signal));
+    }
+
+    fn unregister(&self, signal: &Arc<Signal>) {
+        self.shared
+            .lock()
+            .selectors
+            .retain(|s| !Arc::ptr_eq(s, signal));
+    }
+}
+
+/// Wait on several receivers at once.
+///
+/// `ready` only reports that a receiver *was* ready. With several consumers
+/// another thread may take the message first, so follow up with `try_recv`
+/// and wait again on `TryRecvError::Empty`. The [`select!`](crate::select)
+/// macro does exactly that.
+///
+/// # Examples
+///
+/// ```
+/// use concurrency::channel::{self, Select};
+/// let (_tx1, rx1) = channel::bounded::<i32>(1);
+/// let (tx2, rx2) = channel::bounded(1);
+/// tx2.send("hi").unwrap();
+/// let mut select = Select::new();
+/// select.recv(&rx1);
+/// let index = select.recv(&rx2);
+/// assert_eq!(select.ready(), index);
+/// assert_eq!(rx2.try_recv(), Ok("hi"));
+/// ```
+#[derive(Default)]
+pub struct Select<'a> {
+    receivers: Vec<&'a dyn Selectable>,
+}
+
+/// Where the next search for a ready receiver starts. It is shared by all
+/// `Select`s (`select!` builds a new one every time), so that one busy or
+/// disconnected receiver cannot starve the others.
+static NEXT_START: AtomicUsize = AtomicUsize::new(0);
+
+impl<'a> Select<'a> {
+    pub fn new() -> Select<'a> {
+        Select {
+            receivers: Vec::new(),
+        }
+    }
+
+    /// Add a receiver and return its index for `ready`.
+    pub fn recv<T>(&mut self, receiver: &'a Receiver<T>) -> usize {
+        self.receivers.push(receiver);
+        self.receivers.len() - 1
+    }
+
+    /// Block until one of the receivers is ready and return its index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no receiver was added.
+    pub fn ready(&self) -> usize {
+        self.wait(None)
+            .expect("waiting without deadline always finds a receiver")
+    }
+
+    /// Like `ready`, but returns `None` if nothing got ready within `timeout`.
+    pub fn ready_timeout(&self, timeout: Duration) -> Option<usize> {
+        self.wait(Some(Instant::now() + timeout))
+    }
+
+    fn wait(&self, deadline: Option<Instant>) -> Option<usize> {
+        assert!(
+            !self.receivers.is_empty(),
+            "select needs at least one receiver"
+        );
+        let signal = Arc::new(Signal {
+            fired: Mutex::new(false),
+            condvar: Condvar::new(),
+        });
+        // register before checking, so that no message slips through in between
+        for receiver in &self.receivers {
+            receiver.register(&signal);
+        }
+        let count = self.receivers.len();
+        let start = NEXT_START.fetch_add(1, Ordering::Relaxed) % count;
+        let ready = loop {
+            let mut indices = (start..count).chain(0..start);
+            if let Some(index) = indices.find(|&i| self.receivers[i].is_ready()) {
+                break Some(index);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break None;
+            }
+            signal.wait(deadline);
+        };
+        for receiver in &self.receivers {
+            receiver.unregister(&signal);
+        }
+        ready
+    }
+}
+
+/// Receive from whichever of several receivers gets a message first.
+///
+/// Every branch gets a `Result<T, RecvError>`, which is an error if that
+/// channel is disconnected. The receiver expressions are evaluated more than
+/// once, so pass plain variables.
+///
+/// # Examples
+///
+/// ```
+/// use concurrency::{channel, select};
+/// let (tx1, rx1) = channel::bounded(1);
+/// let (_tx2, rx2) = channel::bounded::<String>(1);
+/// tx1.send(5).unwrap();
+/// let doubled = select! {
+///     recv(rx1) -> msg => msg.unwrap() * 2,
+///     recv(rx2) -> msg => msg.unwrap().len(),
+/// };
+/// assert_eq!(doubled, 10);
+/// ```
+#[macro_export]
+macro_rules! select {
+    ($(recv($rx:expr) -> $res:pat => $body:expr),+ $(,)?) => {{
+        let mut select = $crate::channel::Select::new();
+        $( select.recv(&$rx); )+
+        'select: loop {
+            let ready = select.ready();
+            let mut index = 0usize;
+            $(
+                if ready == index {
+                    let result = match $rx.try_recv() {
+                        ::std::result::Result::Ok(msg) => ::std::result::Result::Ok(msg),
+                        ::std::result::Result::Err(::std::sync::mpsc::TryRecvError::Disconnected) => {
+                            ::std::result::Result::Err(::std::sync::mpsc::RecvError)
+                        }
+                        // another receiver was faster, wait again
+                        ::std::result::Result::Err(::std::sync::mpsc::TryRecvError::Empty) => {
+                            continue 'select
+                        }
+                    };
+                    #[allow(unreachable_code)]
+                    {
+                        let $res = result;
+                        break 'select $body;
+                    }
+                }
+                index += 1;
+            )+
+            let _ = index;
+            unreachable!("select returned an index without receiver");
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    #[should_panic(expected = "greater than zero")]
+    fn zero_capacity_panics() {
+        bounded::<i32>(0);
+    }
+    #[test]
+    fn try_send_reports_full() {
+        let (tx, rx) = bounded(2);
+        tx.try_send(1).unwrap();
+        tx.try_send(2).unwrap();
+        assert_eq!(tx.try_send(3), Err(TrySendError::Full(3)));
+        assert_eq!(rx.len(), 2);
+        assert_eq!(rx.recv(), Ok(1));
+        tx.try_send(3).unwrap();
+        assert_eq!(rx.iter().take(2).collect::<Vec<_>>(), vec![2, 3]);
+    }
+    #[test]
+    fn send_blocks_until_there_is_room() {
+        let (tx, rx) = bounded(1);
+        tx.send(1).unwrap();
+        let handle = thread::spawn(move || {
+            tx.send(2).unwrap(); // blocks until the main thread receives
+        });
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(rx.len(), 1);
+        assert_eq!(rx.recv(), Ok(1));
+        handle.join().unwrap();
+        assert_eq!(rx.recv(), Ok(2));
+    }
+    #[test]
+    fn send_timeout_gives_value_back() {
+        let (tx, _rx) = bounded(1);
+        tx.send("a").unwrap();
+        assert_eq!(
+            tx.send_timeout("b", Duration::from_millis(10)),
+            Err(TrySendError::Full("b"))
+        );
+    }
+    #[test]
+    fn recv_timeout_times_out() {
+        let (_tx, rx) = bounded::<i32>(1);
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+    }
+    #[test]
+    fn receivers_drain_after_senders_disconnect() {
+        let (tx, rx) = bounded(4);
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        drop(tx);
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(rx.recv(), Err(RecvError));
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(1)),
+            Err(RecvTimeoutError::Disconnected)
+        );
+    }
+    #[test]
+    fn blocked_sender_wakes_up_when_receivers_disconnect() {
+        let (tx, rx) = bounded(1);
+        tx.send(1).unwrap();
+        let handle = thread::spawn(move || tx.send(2));
+        thread::sleep(Duration::from_millis(20));
+        drop(rx);
+        assert_eq!(handle.join().unwrap(), Err(SendError(2)));
+    }
+    #[test]
+    fn stress_many_producers_many_consumers() {
+        const PRODUCERS: usize = 8;
+        const CONSUMERS: usize = 4;
+        const MESSAGES: usize = 2_000;
+        let (tx, rx) = bounded(16);
+        let received = Arc::new(AtomicUsize::new(0));
+        let mut handles = vec![];
+        for p in 0..PRODUCERS {
+            let tx = tx.clone();
+            handles.push(thread::spawn(move || {
+                for i in 0..MESSAGES {
+                    tx.send(p * MESSAGES + i).unwrap();
+                }
+                0
+            }));
+        }
+        drop(tx);
+        for _ in 0..CONSUMERS {
+            let rx = rx.clone();
+            let received = Arc::clone(&received);
+            handles.push(thread::spawn(move || {
+                let mut sum = 0;
+                for value in rx.iter() {
+                    // the buffer never grows beyond its capacity
+                    assert!(rx.len() <= rx.capacity());
+                    received.fetch_add(1, Ordering::SeqCst);
+                    sum += value;
+                }
+                sum
+            }));
+        }
+        drop(rx);
+        let total: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        let n = PRODUCERS * MESSAGES;
+        assert_eq!(received.load(Ordering::SeqCst), n);
+        assert_eq!(total, n * (n - 1) / 2);
+    }
+    #[test]
+    fn select_waits_for_the_first_ready_receiver() {
+        let (_tx1, rx1) = bounded::<i32>(1);
+        let (tx2, rx2) = bounded(1);
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            tx2.send("late").unwrap();
+        });
+        let mut select = Select::new();
+        select.recv(&rx1);
+        let second = select.recv(&rx2);
+        assert_eq!(select.ready(), second);
+        assert_eq!(rx2.recv(), Ok("late"));
+        handle.join().unwrap();
+    }
+    #[test]
+    fn select_times_out() {
+        let (_tx, rx) = bounded::<i32>(1);
+        let mut select = Select::new();
+        select.recv(&rx);
+        assert_eq!(select.ready_timeout(Duration::from_millis(10)), None);
+    }
+    #[test]
+    fn select_macro_reports_disconnect() {
+        let (tx1, rx1) = bounded::<i32>(1);
+        let (_tx2, rx2) = bounded::<i32>(1);
+        drop(tx1);
+        let result = crate::select! {
+            recv(rx1) -> msg => msg,
+            recv(rx2) -> _msg => Ok(-1),
+        };
+        assert_eq!(result, Err(RecvError));
+    }
+    #[test]
+    fn stress_select_over_many_channels() {
+        const CHANNELS: usize = 4;
+        const MESSAGES: usize = 500;
+        let mut receivers = vec![];
+        let mut handles = vec![];
+        for c in 0..CHANNELS {
+            let (tx, rx) = bounded(2);
+            receivers.push(rx);
+            handles.push(thread::spawn(move || {
+                for i in 0..MESSAGES {
+                    tx.send(c * MESSAGES + i).unwrap();
+                }
+            }));
+        }
+        let (rx0, rx1, rx2, rx3) = (&receivers[0], &receivers[1], &receivers[2], &receivers[3]);
+        let mut seen = vec![false; CHANNELS * MESSAGES];
+        let mut received = 0;
+        while received < CHANNELS * MESSAGES {
+            let msg = crate::select! {
+                recv(rx0) -> msg => msg,
+                recv(rx1) -> msg => msg,
+                recv(rx2) -> msg => msg,
+                recv(rx3) -> msg => msg,
+            };
+            // a finished producer leaves a disconnected channel behind,
+            // which stays ready but must not starve the others
+            if let Ok(value) = msg {
+                seen[value] = true;
+                received += 1;
+            }
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!(seen.iter().all(|&s| s));
+    }
+}
@@ -0,0 +1,333 @@
+//! A small single-threaded async runtime.
+//!
+//! [`block_on`] drives a future to completion on the current thread.
+//! Inside of it, [`spawn`] starts more tasks, [`timer::sleep`] waits
+//! without blocking the thread and [`channel`] passes messages between
+//! tasks. Tasks are only polled again after their waker put them back into
+//! the ready queue, and the thread parks while there is nothing to do.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//! use concurrency::executor::{self, channel, timer};
+//! let sum = executor::block_on(async {
+//!     let (tx, mut rx) = channel::unbounded();
+//!     for i in 1..=3 {
+//!         let tx = tx.clone();
+//!         executor::spawn(async move {
+//!             timer::sleep(Duration::from_millis(10 * i)).await;
+//!             tx.send(i).await.unwrap();
+//!         });
+//!     }
+//!     drop(tx);
+//!     let mut sum = 0;
+//!     while let Some(i) = rx.recv().await {
+//!         sum += i;
+//!     }
+//!     sum
+//! });
+//! assert_eq!(sum, 6);
+//! ```
+
+pub mod channel;
+pub mod timer;
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::Instant;
+
+use timer::TimerWheel;
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+/// Id of the future passed to `block_on`, spawned tasks count up from zero.
+const MAIN_TASK: usize = usize::MAX;
+
+/// Wakes a task by putting its id into the ready queue.
+///
+/// Wakers have to be `Send + Sync`, so this part is shared with an `Arc`
+/// even though the tasks themselves never leave the executor thread.
+struct TaskWaker {
+    id: usize,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+    thread: Thread,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.ready
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .push_back(self.id);
+        // `block_on` may be parked waiting for exactly this
+        self.thread.unpark();
+    }
+}
+
+struct Runtime {
+    tasks: RefCell<HashMap<usize, Task>>,
+    next_id: Cell<usize>,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+    timers: RefCell<TimerWheel>,
+    thread: Thread,
+}
+
+impl Runtime {
+    fn waker(&self, id: usize) -> Waker {
+        Waker::from(Arc::new(TaskWaker {
+            id,
+            ready: Arc::clone(&self.ready),
+            thread: self.thread.clone(),
+        }))
+    }
+
+    fn pop_ready(&self) -> Option<usize> {
+        self.ready
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .pop_front()
+    }
+}
+
+thread_local! {
+    // the runtime of the innermost `block_on` on this thread
+    static CURRENT: RefCell<Option<Rc<Runtime>>> = const { RefCell::new(None) };
+}
+
+fn current() -> Rc<Runtime> {
+    CURRENT.with(|current| {
+        current
+            .borrow()
+            .clone()
+            .expect("must be called from within `executor::block_on`")
+    })
+}
+
+/// Resets `CURRENT` even if a task panics.
+struct EnterGuard {
+    previous: Option<Rc<Runtime>>,
+}
+
+impl Drop for EnterGuard {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = self.previous.take());
+    }
+}
+
+/// Run `future` to completion on the current thread.
+///
+/// Tasks spawned inside that are still unfinished when `future` completes
+/// are dropped.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let runtime = Rc::new(Runtime {
+        tasks: RefCell::new(HashMap::new()),
+        next_id: Cell::new(0),
+        ready: Arc::new(Mutex::new(VecDeque::from([MAIN_TASK]))),
+        timers: RefCell::new(TimerWheel::new(Instant::now())),
+        thread: thread::current(),
+    });
+    let _guard = EnterGuard {
+        previous: CURRENT.with(|current| current.replace(Some(Rc::clone(&runtime)))),
+    };
+
+    let mut future = std::pin::pin!(future);
+    let main_waker = runtime.waker(MAIN_TASK);
+    loop {
+        while let Some(id) = runtime.pop_ready() {
+            if id == MAIN_TASK {
+                if let Poll::Ready(output) =
+                    future.as_mut().poll(&mut Context::from_waker(&main_waker))
+                {
+                    return output;
+                }
+                continue;
+            }
+            // take the task out, so it can spawn new tasks while being polled
+            let Some(mut task) = runtime.tasks.borrow_mut().remove(&id) else {
+                continue; // woken after it already finished
+            };
+            let waker = runtime.waker(id);
+            if task
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker))
+                .is_pending()
+            {
+                runtime.tasks.borrow_mut().insert(id, task);
+            }
+        }
+
+        runtime.timers.borrow_mut().advance(Instant::now());
+        let has_ready = !runtime
+            .ready
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .is_empty();
+        if has_ready {
+            continue;
+        }
+        // nothing to do: sleep until a waker unparks us or the earliest timer is due
+        let next_deadline = runtime.timers.borrow().next_deadline_in(Instant::now());
+        match next_deadline {
+            Some(timeout) => thread::park_timeout(timeout),
+            None => thread::park(),
+        }
+    }
+}
+
+struct JoinState<T> {
+    output: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// Resolves to the output of a spawned task.
+///
+/// Dropping the handle does not cancel the task.
+pub struct JoinHandle<T> {
+    state: Rc<RefCell<JoinState<T>>>,
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.borrow_mut();
+        match state.output.take() {
+            Some(output) => Poll::Ready(output),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Start a task that runs concurrently with the current one.
+///
+/// # Panics
+///
+/// Panics if called outside of `block_on`.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + 'static,
+    F::Output: 'static,
+{
+    let runtime = current();
+    let state = Rc::new(RefCell::new(JoinState {
+        output: None,
+        waker: None,
+    }));
+    let task_state = Rc::clone(&state);
+    let task = async move {
+        let output = future.await;
+        let mut state = task_state.borrow_mut();
+        state.output = Some(output);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    };
+
+    let id = runtime.next_id.get();
+    runtime.next_id.set(id + 1);
+    runtime.tasks.borrow_mut().insert(id, Box::pin(task));
+    runtime.waker(id).wake(); // poll it for the first time
+    JoinHandle { state }
+}
+
+/// Let the other ready tasks run before continuing.
+pub async fn yield_now() {
+    let mut yielded = false;
+    std::future::poll_fn(move |cx| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn block_on_returns_output() {
+        assert_eq!(block_on(async { 1 + 2 }), 3);
+    }
+    #[test]
+    fn spawned_tasks_can_be_awaited() {
+        let result = block_on(async {
+            let a = spawn(async { 20 });
+            let b = spawn(async { 22 });
+            a.await + b.await
+        });
+        assert_eq!(result, 42);
+    }
+    #[test]
+    fn tasks_interleave_at_await_points() {
+        let log = Rc::new(RefCell::new(vec![]));
+        block_on({
+            let log = Rc::clone(&log);
+            async move {
+                let handles: Vec<_> = ["a", "b"]
+                    .into_iter()
+                    .map(|name| {
+                        let log = Rc::clone(&log);
+                        spawn(async move {
+                            for i in 0..2 {
+                                log.borrow_mut().push(format!("{name}{i}"));
+                                yield_now().await;
+                            }
+                        })
+                    })
+                    .collect();
+                for handle in handles {
+                    handle.await;
+                }
+            }
+        });
+        assert_eq!(*log.borrow(), vec!["a0", "b0", "a1", "b1"]);
+    }
+    #[test]
+    fn tasks_can_spawn_tasks() {
+        let result = block_on(async { spawn(async { spawn(async { 7 }).await * 6 }).await });
+        assert_eq!(result, 42);
+    }
+    #[test]
+    fn wakers_work_from_other_threads() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let result = block_on(std::future::poll_fn(move |cx| match rx.try_recv() {
+            Ok(value) => Poll::Ready(value),
+            Err(_) => {
+                let waker = cx.waker().clone();
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(10));
+                    let _ = tx.send("from thread");
+                    waker.wake();
+                });
+                Poll::Pending
+            }
+        }));
+        assert_eq!(result, "from thread");
+    }
+    #[test]
+    #[should_panic(expected = "within `executor::block_on`")]
+    fn spawn_outside_of_runtime_panics() {
+        spawn(async {});
+    }
+}
@@ -0,0 +1,249 @@
+//! Async channels for tasks of the single-threaded executor.
+//!
+//! Works like `mpsc` (many senders, one receiver), but `recv` and, for
+//! bounded channels, `send` are futures: instead of blocking the thread
+//! they let other tasks run until a message or free space arrives.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::poll_fn;
+use std::rc::Rc;
+use std::task::{Poll, Waker};
+
+struct State<T> {
+    queue: VecDeque<T>,
+    capacity: Option<usize>,
+    senders: usize,
+    receiver_alive: bool,
+    receiver_waker: Option<Waker>,
+    // senders waiting for free space in a bounded channel
+    sender_wakers: Vec<Waker>,
+}
+
+/// Error of `send` when the receiver is gone, gives the value back.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+/// A channel without a limit, `send` always completes immediately.
+pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+    with_capacity(None)
+}
+
+/// A channel buffering at most `capacity` messages.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+pub fn bounded<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "channel capacity must be greater than zero");
+    with_capacity(Some(capacity))
+}
+
+fn with_capacity<T>(capacity: Option<usize>) -> (Sender<T>, Receiver<T>) {
+    let state = Rc::new(RefCell::new(State {
+        queue: VecDeque::new(),
+        capacity,
+        senders: 1,
+        receiver_alive: true,
+        receiver_waker: None,
+        sender_wakers: Vec::new(),
+    }));
+    (
+        Sender {
+            state: Rc::clone(&state),
+        },
+        Receiver { state },
+    )
+}
+
+pub struct Sender<T> {
+    state: Rc<RefCell<State<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Send a message, waiting for free space if the channel is bounded.
+    ///
+    /// # Errors
+    ///
+    /// Gives the message back if the receiver has been dropped.
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut value = Some(value);
+        poll_fn(|cx| {
+            let mut state = self.state.borrow_mut();
+            if !state.receiver_alive {
+                return Poll::Ready(Err(SendError(
+                    value.take().expect("polled after completion"),
+                )));
+            }
+            if state
+                .capacity
+                .is_some_and(|capacity| state.queue.len() >= capacity)
+            {
+                state.sender_wakers.push(cx.waker().clone());
+                return Poll::Pending;
+            }
+            state
+                .queue
+                .push_back(value.take().expect("polled after completion"));
+            if let Some(waker) = state.receiver_waker.take() {
+                waker.wake();
+            }
+            Poll::Ready(Ok(()))
+        })
+        .await
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.state.borrow_mut().senders += 1;
+        Sender {
+            state: Rc::clone(&self.state),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.state.borrow_mut();
+        state.senders -= 1;
+        if state.senders == 0 {
+            // let a waiting `recv` see the end of the channel
+            if let Some(waker) = state.receiver_waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    state: Rc<RefCell<State<T>>>,
+}
+
+impl<T> Receiver<T> {
+    /// Wait for the next message, `None` once all senders are gone and
+    /// the buffer is empty.
+    pub async fn recv(&mut self) -> Option<T> {
+        poll_fn(|cx| {
+            let mut state = self.state.borrow_mut();
+            if let Some(value) = state.queue.pop_front() {
+                // there is room for one more message now
+                for waker in state.sender_wakers.drain(..) {
+                    waker.wake();
+                }
+                return Poll::Ready(Some(value));
+            }
+            if state.senders == 0 {
+                return Poll::Ready(None);
+            }
+            state.receiver_waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Take a message only if one is buffered right now.
+    pub fn try_recv(&mut self) -> Option<T> {
+        let mut state = self.state.borrow_mut();
+        let value = state.queue.pop_front();
+        if value.is_some() {
+            for waker in state.sender_wakers.drain(..) {
+                waker.wake();
+            }
+        }
+        value
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.state.borrow_mut();
+        state.receiver_alive = false;
+        // blocked senders have to find out that nobody is listening anymore
+        for waker in state.sender_wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::{block_on, spawn, yield_now};
+
+    #[test]
+    fn receives_until_all_senders_are_gone() {
+        let received = block_on(async {
+            let (tx, mut rx) = unbounded();
+            for i in 0..3 {
+                let tx = tx.clone();
+                spawn(async move {
+                    tx.send(i).await.unwrap();
+                });
+            }
+            drop(tx);
+            let mut received = vec![];
+            while let Some(value) = rx.recv().await {
+                received.push(value);
+            }
+            received
+        });
+        assert_eq!(received, vec![0, 1, 2]);
+    }
+    #[test]
+    fn bounded_send_waits_for_the_receiver() {
+        let log = block_on(async {
+            let log = Rc::new(RefCell::new(vec![]));
+            let (tx, mut rx) = bounded(1);
+            let producer = {
+                let log = Rc::clone(&log);
+                spawn(async move {
+                    for i in 0..3 {
+                        tx.send(i).await.unwrap();
+                        log.borrow_mut().push(format!("sent {i}"));
+                    }
+                })
+            };
+            // give the producer the chance to fill the channel
+            yield_now().await;
+            while let Some(value) = rx.recv().await {
+                log.borrow_mut().push(format!("got {value}"));
+            }
+            producer.await;
+            Rc::try_unwrap(log).unwrap().into_inner()
+        });
+        // the producer never gets more than one message ahead
+        assert_eq!(
+            log,
+            vec!["sent 0", "got 0", "sent 1", "got 1", "sent 2", "got 2"]
+        );
+    }
+    #[test]
+    fn send_fails_without_receiver() {
+        block_on(async {
+            let (tx, rx) = bounded(1);
+            drop(rx);
+            assert_eq!(tx.send("hi").await, Err(SendError("hi")));
+        });
+    }
+    #[test]
+    fn blocked_sender_is_released_when_receiver_drops() {
+        block_on(async {
+            let (tx, rx) = bounded(1);
+            tx.send(1).await.unwrap();
+            let blocked = spawn(async move { tx.send(2).await });
+            yield_now().await;
+            drop(rx);
+            assert_eq!(blocked.await, Err(SendError(2)));
+        });
+    }
+    #[test]
+    fn try_recv_does_not_wait() {
+        block_on(async {
+            let (tx, mut rx) = unbounded();
+            assert_eq!(rx.try_recv(), None);
+            tx.send(5).await.unwrap();
+            assert_eq!(rx.try_recv(), Some(5));
+        });
+    }
+}
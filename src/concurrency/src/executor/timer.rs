@@ -0,0 +1,381 @@
+//! Async timers driven by a hashed timer wheel.
+//!
+//! The wheel has a fixed number of slots, each covering one tick. A timer
+//! goes into the slot of its deadline tick modulo the wheel size, so
+//! inserting is O(1) and every advance only looks at the slots of the
+//! ticks that passed. Timers further away than one turn simply stay in
+//! their slot until their tick comes around.
+
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::{Rc, Weak};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// Resolution of all timers.
+pub const TICK: Duration = Duration::from_millis(1);
+const SLOTS: usize = 256;
+
+/// State shared between a `Sleep` future and its wheel entry.
+#[derive(Default)]
+struct TimerShared {
+    fired: Cell<bool>,
+    waker: RefCell<Option<Waker>>,
+}
+
+struct Entry {
+    deadline_tick: u64,
+    timer: Rc<TimerShared>,
+}
+
+pub(crate) struct TimerWheel {
+    start: Instant,
+    slots: Vec<Vec<Entry>>,
+    // the next tick that has not been processed yet
+    current_tick: u64,
+    len: usize,
+}
+
+impl TimerWheel {
+    pub(crate) fn new(start: Instant) -> TimerWheel {
+        TimerWheel {
+            start,
+            slots: (0..SLOTS).map(|_| Vec::new()).collect(),
+            current_tick: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns the tick the timer was put in, to find it again in `remove`.
+    fn insert(&mut self, deadline: Instant, timer: Rc<TimerShared>) -> u64 {
+        // round up, so that a timer never fires before its deadline
+        let since_start = deadline.saturating_duration_since(self.start);
+        let deadline_tick = to_ticks(since_start.as_nanos().div_ceil(TICK.as_nanos()));
+        // a deadline in the past fires with the next advance
+        let deadline_tick = deadline_tick.max(self.current_tick);
+        self.slots[slot_of(deadline_tick)].push(Entry {
+            deadline_tick,
+            timer,
+        });
+        self.len += 1;
+        deadline_tick
+    }
+
+    /// Forget a timer that hasn't fired, e.g. because its `Sleep` was dropped.
+    fn remove(&mut self, deadline_tick: u64, timer: &Rc<TimerShared>) {
+        let slot = &mut self.slots[slot_of(deadline_tick)];
+        if let Some(i) = slot
+            .iter()
+            .position(|entry| Rc::ptr_eq(&entry.timer, timer))
+        {
+            slot.swap_remove(i);
+            self.len -= 1;
+        }
+    }
+
+    /// Fire all timers whose deadline is at or before `now`.
+    pub(crate) fn advance(&mut self, now: Instant) {
+        let now_tick =
+            to_ticks(now.saturating_duration_since(self.start).as_nanos() / TICK.as_nanos());
+        if now_tick < self.current_tick {
+            return;
+        }
+        // after a full turn every slot has been visited, no need to go on
+        let ticks = (now_tick - self.current_tick + 1).min(SLOTS as u64);
+        for tick in self.current_tick..self.current_tick + ticks {
+            let slot = &mut self.slots[slot_of(tick)];
+            let mut i = 0;
+            while i < slot.len() {
+                if slot[i].deadline_tick <= now_tick {
+                    let entry = slot.swap_remove(i);
+                    self.len -= 1;
+                    entry.timer.fired.set(true);
+                    let waker = entry.timer.waker.borrow_mut().take();
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                } else {
+                    i += 1; // due in a later turn of the wheel
+                }
+            }
+        }
+        self.current_tick = now_tick + 1;
+    }
+
+    /// Time until the earliest timer is due, `None` if no timer is waiting
+    /// (or the earliest one is too far away to be represented).
+    pub(crate) fn next_deadline_in(&self, now: Instant) -> Option<Duration> {
+        if self.len == 0 {
+            return None;
+        }
+        // the wheel isn't sorted, but parking happens far less often than ticks
+        let tick = self
+            .slots
+            .iter()
+            .flatten()
+            .map(|entry| entry.deadline_tick)
+            .min()?;
+        let since_start = TICK.as_nanos().checked_mul(u128::from(tick))?;
+        let deadline = self
+            .start
+            .checked_add(Duration::from_nanos(u64::try_from(since_start).ok()?))?;
+        Some(deadline.saturating_duration_since(now))
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+fn to_ticks(ticks: u128) -> u64 {
+    // a u64 of ticks is hundreds of millions of years
+    u64::try_from(ticks).unwrap_or(u64::MAX)
+}
+
+fn slot_of(tick: u64) -> usize {
+    (tick % SLOTS as u64) as usize
+}
+
+/// Future returned by [`sleep`].
+pub struct Sleep {
+    deadline: Instant,
+    timer: Option<Registration>,
+}
+
+/// Where a `Sleep` waits in the wheel of its runtime.
+struct Registration {
+    timer: Rc<TimerShared>,
+    deadline_tick: u64,
+    // weak, since the runtime owns the tasks that own the sleeps
+    runtime: Weak<super::Runtime>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.timer.as_ref().is_some_and(|r| r.timer.fired.get())
+            || Instant::now() >= self.deadline
+        {
+            return Poll::Ready(());
+        }
+        let timer = match &self.timer {
+            Some(registration) => Rc::clone(&registration.timer),
+            None => {
+                let timer = Rc::new(TimerShared::default());
+                let runtime = super::current();
+                let deadline_tick = runtime
+                    .timers
+                    .borrow_mut()
+                    .insert(self.deadline, Rc::clone(&timer));
+                self.timer = Some(Registration {
+                    timer: Rc::clone(&timer),
+                    deadline_tick,
+                    runtime: Rc::downgrade(&runtime),
+                });
+                timer
+            }
+        };
+        // the task may have moved to another waker since the last poll
+        *timer.waker.borrow_mut() = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    /// Take the timer out of the wheel, so a sleep that lost e.g. a
+    /// [`timeout`] doesn't keep waking the executor until its deadline.
+    fn drop(&mut self) {
+        let Some(registration) = self.timer.take() else {
+            return;
+        };
+        if registration.timer.fired.get() {
+            return;
+        }
+        if let Some(runtime) = registration.runtime.upgrade() {
+            // the wheel is only borrowed while advancing, which doesn't drop sleeps
+            if let Ok(mut timers) = runtime.timers.try_borrow_mut() {
+                timers.remove(registration.deadline_tick, &registration.timer);
+            }
+        }
+    }
+}
+
+/// Wait for `duration` without blocking the executor thread.
+///
+/// # Panics
+///
+/// Panics if polled outside of `executor::block_on`.
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(Instant::now() + duration)
+}
+
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep {
+        deadline,
+        timer: None,
+    }
+}
+
+/// Error of [`timeout`] when the future did not finish in time.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Elapsed;
+
+/// Run `future`, but give up after `duration`.
+///
+/// # Errors
+///
+/// Returns `Elapsed` if the timer fired first.
+pub async fn timeout<F: Future>(duration: Duration, future: F) -> Result<F::Output, Elapsed> {
+    let mut future = std::pin::pin!(future);
+    let mut sleep = std::pin::pin!(sleep(duration));
+    std::future::poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        sleep.as_mut().poll(cx).map(|()| Err(Elapsed))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::{block_on, spawn};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn timer_with_waker(
+        wheel: &mut TimerWheel,
+        deadline: Instant,
+        waker: &Arc<CountingWaker>,
+    ) -> Rc<TimerShared> {
+        let timer = Rc::new(TimerShared::default());
+        *timer.waker.borrow_mut() = Some(Waker::from(Arc::clone(waker)));
+        wheel.insert(deadline, Rc::clone(&timer));
+        timer
+    }
+
+    #[test]
+    fn wheel_fires_timers_once_their_tick_passed() {
+        let start = Instant::now();
+        let mut wheel = TimerWheel::new(start);
+        let waker = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let early = timer_with_waker(&mut wheel, start + TICK * 3, &waker);
+        let late = timer_with_waker(&mut wheel, start + TICK * 10, &waker);
+        assert_eq!(wheel.len(), 2);
+
+        wheel.advance(start + TICK * 2);
+        assert!(!early.fired.get());
+        wheel.advance(start + TICK * 3);
+        assert!(early.fired.get());
+        assert!(!late.fired.get());
+        assert_eq!(waker.0.load(Ordering::SeqCst), 1);
+
+        wheel.advance(start + TICK * 50);
+        assert!(late.fired.get());
+        assert_eq!(wheel.len(), 0);
+        assert_eq!(wheel.next_deadline_in(start), None);
+    }
+    #[test]
+    fn wheel_reports_the_earliest_deadline() {
+        let start = Instant::now();
+        let mut wheel = TimerWheel::new(start);
+        let waker = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let late = timer_with_waker(&mut wheel, start + TICK * (SLOTS as u32 + 2), &waker);
+        assert_eq!(
+            wheel.next_deadline_in(start),
+            Some(TICK * (SLOTS as u32 + 2))
+        );
+        let _early = timer_with_waker(&mut wheel, start + TICK * 40, &waker);
+        // not the next tick, the earliest deadline
+        assert_eq!(wheel.next_deadline_in(start + TICK * 10), Some(TICK * 30));
+        wheel.remove(SLOTS as u64 + 2, &late);
+        assert_eq!(wheel.len(), 1);
+        wheel.advance(start + TICK * 40);
+        assert_eq!(wheel.next_deadline_in(start), None);
+    }
+    #[test]
+    fn wheel_keeps_timers_due_in_a_later_turn() {
+        let start = Instant::now();
+        let mut wheel = TimerWheel::new(start);
+        let waker = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        // same slot as tick 5, but one full turn later
+        let far = timer_with_waker(&mut wheel, start + TICK * (SLOTS as u32 + 5), &waker);
+        wheel.advance(start + TICK * 5);
+        assert!(!far.fired.get());
+        wheel.advance(start + TICK * (SLOTS as u32 + 4));
+        assert!(!far.fired.get());
+        wheel.advance(start + TICK * (SLOTS as u32 + 5));
+        assert!(far.fired.get());
+    }
+    #[test]
+    fn wheel_handles_long_jumps() {
+        let start = Instant::now();
+        let mut wheel = TimerWheel::new(start);
+        let waker = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let timers: Vec<_> = (0..SLOTS as u32 * 3)
+            .step_by(7)
+            .map(|t| timer_with_waker(&mut wheel, start + TICK * t, &waker))
+            .collect();
+        wheel.advance(start + TICK * (SLOTS as u32 * 10));
+        assert!(timers.iter().all(|t| t.fired.get()));
+        assert_eq!(waker.0.load(Ordering::SeqCst), timers.len());
+    }
+    #[test]
+    fn sleeps_finish_in_deadline_order() {
+        let start = Instant::now();
+        let order = block_on(async {
+            let order = Rc::new(RefCell::new(vec![]));
+            let handles: Vec<_> = [30, 10, 20]
+                .into_iter()
+                .map(|ms| {
+                    let order = Rc::clone(&order);
+                    spawn(async move {
+                        sleep(Duration::from_millis(ms)).await;
+                        order.borrow_mut().push(ms);
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.await;
+            }
+            Rc::try_unwrap(order).unwrap().into_inner()
+        });
+        // spawned in a different order, so the sleeps must have run concurrently
+        assert_eq!(order, vec![10, 20, 30]);
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+    #[test]
+    fn timeout_gives_up() {
+        block_on(async {
+            let slow = timeout(Duration::from_millis(5), sleep(Duration::from_secs(10))).await;
+            assert_eq!(slow, Err(Elapsed));
+            let fast = timeout(Duration::from_secs(10), async { 5 }).await;
+            assert_eq!(fast, Ok(5));
+        });
+    }
+    #[test]
+    fn dropped_sleeps_leave_the_wheel() {
+        block_on(async {
+            let mut sleep = Box::pin(sleep(Duration::from_secs(60)));
+            let pending =
+                std::future::poll_fn(|cx| Poll::Ready(sleep.as_mut().poll(cx).is_pending())).await;
+            assert!(pending);
+            assert_eq!(crate::executor::current().timers.borrow().len(), 1);
+            drop(sleep);
+            assert_eq!(crate::executor::current().timers.borrow().len(), 0);
+        });
+    }
+}
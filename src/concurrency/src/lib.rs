@@ -0,0 +1,13 @@
+//! # concurrency
+//!
+//! Reusable building blocks that grew out of the examples in `main.rs`.
+
+pub mod actor;
+pub mod cancel;
+pub mod channel;
+pub mod executor;
+pub mod metrics;
+pub mod par_iter;
+pub mod pubsub;
+pub mod sharded_map;
+pub mod tracked_mutex;
@@ -70,6 +70,173 @@ fn message_passing() {
     }
 }
 
+// the same as `message_passing`, but with tasks on a single thread instead of OS threads
+fn async_message_passing() {
+    use concurrency::executor::{self, channel, timer};
+    use std::time::Duration;
+    executor::block_on(async {
+        let (tx, mut rx) = channel::unbounded();
+        let tx2 = tx.clone();
+        // `spawn` starts a task, it runs whenever the current task hits an `.await`
+        executor::spawn(async move {
+            let val = String::from("hi - this is an async oneliner");
+            tx.send(val).await.unwrap();
+        });
+        let received = rx.recv().await.unwrap();
+        println!("Got: {}", received);
+
+        executor::spawn(async move {
+            let vals = vec![
+                String::from("- async multiliner -"),
+                String::from("hi"),
+                String::from("from"),
+                String::from("the"),
+                String::from("task"),
+            ];
+            for val in vals {
+                tx2.send(val).await.unwrap();
+                // `thread::sleep` would block every task, `timer::sleep` only this one
+                timer::sleep(Duration::from_millis(200)).await;
+            }
+        });
+        // `recv` returns `None` once every sender is dropped
+        while let Some(received) = rx.recv().await {
+            println!("Got: {}", received);
+        }
+    });
+}
+
+fn bounded_message_passing() {
+    use concurrency::channel;
+    use concurrency::select;
+    use std::thread;
+    use std::time::Duration;
+    // `mpsc::channel` is unbounded: the `tx2` loop above could send as fast as it wants
+    // and the messages would pile up in memory if the receiver can't keep up
+    // a bounded channel only buffers `capacity` messages, `send` blocks while it is full
+    let (tx, rx) = channel::bounded(2);
+    let producer = thread::spawn(move || {
+        for i in 1..=5 {
+            tx.send(i).unwrap();
+            println!("sent {} (blocks once two are waiting)", i);
+        }
+    });
+    for received in &rx {
+        println!("Got: {}", received);
+        thread::sleep(Duration::from_millis(100)); // slow consumer
+    }
+    producer.join().unwrap();
+
+    // wait on several receivers at once with `select!`
+    let (numbers_tx, numbers) = channel::bounded(1);
+    let (words_tx, words) = channel::bounded(1);
+    thread::spawn(move || numbers_tx.send(42).unwrap());
+    thread::spawn(move || words_tx.send(String::from("hello")).unwrap());
+    for _ in 0..2 {
+        select! {
+            recv(numbers) -> msg => println!("Got number: {:?}", msg),
+            recv(words) -> msg => println!("Got word: {:?}", msg),
+        }
+    }
+}
+
+fn publish_subscribe() {
+    use concurrency::pubsub::{EventBus, OverflowPolicy};
+    use std::thread;
+    // one-to-many: every subscriber of a topic gets its own copy of each message
+    let bus = EventBus::new();
+    let kitchen = bus.subscribe("orders", 10, OverflowPolicy::Block);
+    let billing = bus.subscribe("orders", 10, OverflowPolicy::Block);
+    // a slow subscriber that only cares about the latest messages
+    let display = bus.subscribe("orders", 1, OverflowPolicy::DropOldest);
+    let publisher = thread::spawn(move || {
+        for order in ["soup", "salad", "pasta"] {
+            bus.publish("orders", String::from(order));
+        }
+    }); // the bus is dropped here, which ends the `iter()` loops below
+    publisher.join().unwrap();
+    for order in kitchen.iter() {
+        println!("Kitchen cooks: {}", order);
+    }
+    println!("Billing got {} orders", billing.iter().count());
+    println!(
+        "Display shows only: {:?} (dropped {})",
+        display.recv(),
+        display.dropped()
+    );
+}
+
+fn actors() {
+    use concurrency::actor::{Actor, ActorSystem, Context, ReplyTo, Supervision};
+    // instead of hand-rolling a thread that owns its state and loops over `rx`,
+    // the state lives in an actor and is only reachable through its mailbox
+    struct Greeter {
+        greeted: Vec<String>,
+    }
+    enum GreeterMsg {
+        Greet(String),
+        Count(ReplyTo<usize>), // request/response via `ask`
+    }
+    impl Actor for Greeter {
+        type Message = GreeterMsg;
+        fn handle(&mut self, message: GreeterMsg, _ctx: &Context<Self>) {
+            match message {
+                GreeterMsg::Greet(name) => {
+                    println!("Hello, {}!", name);
+                    self.greeted.push(name);
+                }
+                GreeterMsg::Count(reply) => reply.reply(self.greeted.len()),
+            }
+        }
+    }
+    let mut system = ActorSystem::new();
+    let greeter = system.spawn(
+        || Greeter { greeted: vec![] },
+        Supervision::Restart { max_restarts: 3 },
+    );
+    for name in ["Alice", "Bob"] {
+        greeter.send(GreeterMsg::Greet(String::from(name))).unwrap();
+    }
+    println!("Greeted {:?} people", greeter.ask(GreeterMsg::Count));
+    system.shutdown(); // waits until every actor has handled its mailbox
+}
+
+// unlike the threads in `threads` and `message_passing`, these can be told to stop early
+fn cancellation() {
+    use concurrency::cancel::{self, CancellationToken};
+    use std::sync::mpsc;
+    use std::time::Duration;
+    let shutdown = CancellationToken::new();
+    let (tx, rx) = mpsc::channel();
+    // all tasks are joined before `scope` returns, a panic in one of them cancels the others
+    let ticks = cancel::scope(&shutdown, |s| {
+        let handles: Vec<_> = ["left", "right"]
+            .into_iter()
+            .map(|name| {
+                let tx = tx.clone();
+                // every task gets its own child token of `shutdown`
+                s.spawn(move |token| {
+                    let mut ticks = 0;
+                    // an interruptible sleep: returns `true` as soon as the token is cancelled
+                    while !token.wait_timeout(Duration::from_millis(20)) {
+                        ticks += 1;
+                        tx.send(format!("{} tick {}", name, ticks)).unwrap();
+                    }
+                    ticks
+                })
+            })
+            .collect();
+        drop(tx);
+        for received in rx.iter().take(5) {
+            println!("Got: {}", received);
+        }
+        // would run forever otherwise
+        shutdown.cancel();
+        handles.into_iter().map(|h| h.join().unwrap()).sum::<u32>()
+    });
+    println!("workers stopped after {} ticks", ticks);
+}
+
 fn shared_state() {
     use std::sync::Mutex;
     let m = Mutex::new(5);
@@ -96,10 +263,31 @@ fn shared_state() {
         handle.join().unwrap();
     }
     println!("Result: {}", *counter.lock().unwrap());
+
+    // for a plain counter the lock is overkill: every thread waits for the one holding it.
+    // A sharded atomic counter lets the threads update their own part without waiting
+    use concurrency::metrics::Registry;
+    let registry = Registry::new();
+    let counter = registry.counter("increments_total", "Increments from the worker threads.");
+    let mut handles = vec![];
+    for _ in 0..10 {
+        let counter = counter.clone(); // clones share the value, no `Arc` needed
+        handles.push(thread::spawn(move || counter.inc()));
+    }
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    // the registry renders all its metrics in the Prometheus text format
+    print!("{}", registry.render());
 }
 
 fn main() {
     threads();
     message_passing();
+    async_message_passing();
+    bounded_message_passing();
+    publish_subscribe();
+    actors();
+    cancellation();
     shared_state();
 }
@@ -0,0 +1,486 @@
+//! Lock-free counters, gauges and histograms plus a registry that renders
+//! them in the Prometheus text format.
+//!
+//! A single atomic that every thread hammers is better than a `Mutex`, but
+//! the cache line holding it still bounces between cores. Counters and
+//! histograms therefore keep one cache-line-sized shard per group of
+//! threads; updates touch only the shard of the current thread and reads
+//! add all shards up.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::thread;
+//! use concurrency::metrics::Registry;
+//! let registry = Registry::new();
+//! let requests = registry.counter("http_requests_total", "Handled requests.");
+//! thread::scope(|s| {
+//!     for _ in 0..4 {
+//!         s.spawn(|| (0..1000).for_each(|_| requests.inc()));
+//!     }
+//! });
+//! assert_eq!(requests.get(), 4000);
+//! assert!(registry.render().contains("http_requests_total 4000\n"));
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+const SHARDS: usize = 16;
+
+/// Keeps each shard on its own cache line, so neighbours don't contend.
+#[repr(align(64))]
+#[derive(Default)]
+struct Padded<T>(T);
+
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // threads are spread round-robin, so the first `SHARDS` threads never share
+    static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed) % SHARDS;
+}
+
+fn shard_index() -> usize {
+    SHARD.with(|shard| *shard)
+}
+
+/// A value that only goes up, like the number of handled requests.
+///
+/// Clones share the same value.
+#[derive(Clone, Default)]
+pub struct Counter {
+    shards: Arc<[Padded<AtomicU64>; SHARDS]>,
+}
+
+impl Counter {
+    pub fn new() -> Counter {
+        Counter::default()
+    }
+
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, n: u64) {
+        // only the total matters, nothing is ordered by a counter
+        self.shards[shard_index()].0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// The sum of all shards. Updates running at the same time may or may
+    /// not be included.
+    pub fn get(&self) -> u64 {
+        self.shards
+            .iter()
+            .map(|shard| shard.0.load(Ordering::Relaxed))
+            .sum()
+    }
+}
+
+/// A value that goes up and down, like the number of open connections.
+///
+/// `set` has to win over every other update, so a gauge is a single atomic
+/// instead of being sharded. Clones share the same value.
+#[derive(Clone, Default)]
+pub struct Gauge {
+    value: Arc<AtomicI64>,
+}
+
+impl Gauge {
+    pub fn new() -> Gauge {
+        Gauge::default()
+    }
+
+    pub fn set(&self, value: i64) {
+        self.value.store(value, Ordering::Relaxed);
+    }
+
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn dec(&self) {
+        self.add(-1);
+    }
+
+    pub fn add(&self, n: i64) {
+        self.value.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// Upper bounds used by Prometheus client libraries, meant for durations
+/// in seconds.
+pub const DEFAULT_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+struct HistogramShard {
+    // one count per bound plus the last one for everything above
+    counts: Vec<AtomicU64>,
+    // an `f64` stored as bits, there is no atomic float
+    sum: AtomicU64,
+}
+
+/// Counts observations in buckets, like the distribution of response times.
+///
+/// Clones share the same buckets.
+#[derive(Clone)]
+pub struct Histogram {
+    bounds: Arc<[f64]>,
+    shards: Arc<[Padded<HistogramShard>]>,
+}
+
+/// The state of a histogram at one point in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramSnapshot {
+    /// `(upper bound, observations <= bound)`, the last bound is infinity,
+    /// so its count equals `count`.
+    pub buckets: Vec<(f64, u64)>,
+    pub sum: f64,
+    pub count: u64,
+}
+
+impl Histogram {
+    /// A histogram with the given bucket upper bounds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bounds` is not strictly increasing or isn't finite. The
+    /// `+Inf` bucket is always added.
+    pub fn new(bounds: &[f64]) -> Histogram {
+        assert!(
+            bounds.iter().all(|bound| bound.is_finite()),
+            "histogram bounds must be finite, the +Inf bucket is implied"
+        );
+        assert!(
+            bounds.windows(2).all(|pair| pair[0] < pair[1]),
+            "histogram bounds must be strictly increasing"
+        );
+        let shards = (0..SHARDS)
+            .map(|_| {
+                Padded(HistogramShard {
+                    counts: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+                    sum: AtomicU64::new(0f64.to_bits()),
+                })
+            })
+            .collect();
+        Histogram {
+            bounds: bounds.into(),
+            shards,
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        let bucket = self.bounds.partition_point(|bound| *bound < value);
+        let shard = &self.shards[shard_index()].0;
+        shard.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        // few threads share a shard, so this loop rarely runs twice
+        let _ = shard
+            .sum
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + value).to_bits())
+            });
+    }
+
+    /// Add up all shards. Like `Counter::get`, concurrent updates may be
+    /// partially included.
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let mut counts = vec![0; self.bounds.len() + 1];
+        let mut sum = 0.0;
+        for shard in self.shards.iter() {
+            for (total, count) in counts.iter_mut().zip(&shard.0.counts) {
+                *total += count.load(Ordering::Relaxed);
+            }
+            sum += f64::from_bits(shard.0.sum.load(Ordering::Relaxed));
+        }
+        let mut cumulative = 0;
+        let buckets = self
+            .bounds
+            .iter()
+            .copied()
+            .chain([f64::INFINITY])
+            .zip(counts)
+            .map(|(bound, count)| {
+                cumulative += count;
+                (bound, cumulative)
+            })
+            .collect();
+        HistogramSnapshot {
+            buckets,
+            sum,
+            count: cumulative,
+        }
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram::new(&DEFAULT_BUCKETS)
+    }
+}
+
+#[derive(Clone)]
+enum Metric {
+    Counter(Counter),
+    Gauge(Gauge),
+    Histogram(Histogram),
+}
+
+impl Metric {
+    fn kind(&self) -> &'static str {
+        match self {
+            Metric::Counter(_) => "counter",
+            Metric::Gauge(_) => "gauge",
+            Metric::Histogram(_) => "histogram",
+        }
+    }
+}
+
+struct Entry {
+    help: String,
+    metric: Metric,
+}
+
+/// Metrics by name. Registering a name twice returns the existing metric,
+/// so independent parts of a program can share one.
+///
+/// Clones share the same metrics.
+#[derive(Clone, Default)]
+pub struct Registry {
+    // only touched when registering and rendering, never on updates
+    metrics: Arc<Mutex<BTreeMap<String, Entry>>>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// The counter called `name`, created if it doesn't exist yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid metric name or is already used by a
+    /// different kind of metric.
+    pub fn counter(&self, name: &str, help: &str) -> Counter {
+        match self.register(name, help, || Metric::Counter(Counter::new())) {
+            Metric::Counter(counter) => counter,
+            other => already_registered(name, other.kind()),
+        }
+    }
+
+    /// The gauge called `name`, created if it doesn't exist yet.
+    ///
+    /// # Panics
+    ///
+    /// Same as `counter`.
+    pub fn gauge(&self, name: &str, help: &str) -> Gauge {
+        match self.register(name, help, || Metric::Gauge(Gauge::new())) {
+            Metric::Gauge(gauge) => gauge,
+            other => already_registered(name, other.kind()),
+        }
+    }
+
+    /// The histogram called `name`, created with `bounds` if it doesn't
+    /// exist yet (an existing one keeps its buckets).
+    ///
+    /// # Panics
+    ///
+    /// Same as `counter`, and like `Histogram::new` for invalid bounds.
+    pub fn histogram(&self, name: &str, help: &str, bounds: &[f64]) -> Histogram {
+        match self.register(name, help, || Metric::Histogram(Histogram::new(bounds))) {
+            Metric::Histogram(histogram) => histogram,
+            other => already_registered(name, other.kind()),
+        }
+    }
+
+    fn register(&self, name: &str, help: &str, create: impl FnOnce() -> Metric) -> Metric {
+        assert!(is_valid_name(name), "invalid metric name `{name}`");
+        let mut metrics = self.metrics.lock().unwrap_or_else(|p| p.into_inner());
+        let entry = metrics.entry(name.to_string()).or_insert_with(|| Entry {
+            help: help.to_string(),
+            metric: create(),
+        });
+        entry.metric.clone()
+    }
+
+    /// All metrics in the Prometheus text exposition format, sorted by name.
+    pub fn render(&self) -> String {
+        let metrics = self.metrics.lock().unwrap_or_else(|p| p.into_inner());
+        let mut out = String::new();
+        for (name, entry) in metrics.iter() {
+            // writing to a `String` can't fail
+            let _ = writeln!(out, "# HELP {name} {}", escape_help(&entry.help));
+            let _ = writeln!(out, "# TYPE {name} {}", entry.metric.kind());
+            match &entry.metric {
+                Metric::Counter(counter) => {
+                    let _ = writeln!(out, "{name} {}", counter.get());
+                }
+                Metric::Gauge(gauge) => {
+                    let _ = writeln!(out, "{name} {}", gauge.get());
+                }
+                Metric::Histogram(histogram) => {
+                    let snapshot = histogram.snapshot();
+                    for (bound, count) in &snapshot.buckets {
+                        let _ = writeln!(
+                            out,
+                            "{name}_bucket{{le=\"{}\"}} {count}",
+                            format_bound(*bound)
+                        );
+                    }
+                    let _ = writeln!(out, "{name}_sum {}", snapshot.sum);
+                    let _ = writeln!(out, "{name}_count {}", snapshot.count);
+                }
+            }
+        }
+        out
+    }
+}
+
+fn already_registered(name: &str, kind: &str) -> ! {
+    panic!("metric `{name}` is already registered as a {kind}")
+}
+
+/// `[a-zA-Z_:][a-zA-Z0-9_:]*`, as required by Prometheus.
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_' || first == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn format_bound(bound: f64) -> String {
+    if bound == f64::INFINITY {
+        "+Inf".to_string()
+    } else {
+        bound.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn counter_adds_up_all_threads() {
+        let counter = Counter::new();
+        thread::scope(|s| {
+            for _ in 0..SHARDS * 2 {
+                s.spawn(|| {
+                    for _ in 0..1000 {
+                        counter.inc();
+                    }
+                    counter.add(5);
+                });
+            }
+        });
+        assert_eq!(counter.get(), (SHARDS * 2 * 1005) as u64);
+        // clones share the value
+        counter.clone().inc();
+        assert_eq!(counter.get(), (SHARDS * 2 * 1005) as u64 + 1);
+    }
+    #[test]
+    fn gauge_goes_both_ways() {
+        let gauge = Gauge::new();
+        gauge.inc();
+        gauge.inc();
+        gauge.dec();
+        gauge.add(-5);
+        assert_eq!(gauge.get(), -4);
+        gauge.set(42);
+        assert_eq!(gauge.get(), 42);
+    }
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let histogram = Histogram::new(&[1.0, 5.0, 10.0]);
+        for value in [0.5, 1.0, 3.0, 7.0, 100.0] {
+            histogram.observe(value);
+        }
+        let snapshot = histogram.snapshot();
+        assert_eq!(
+            snapshot.buckets,
+            vec![(1.0, 2), (5.0, 3), (10.0, 4), (f64::INFINITY, 5)]
+        );
+        assert_eq!(snapshot.count, 5);
+        assert_eq!(snapshot.sum, 111.5);
+    }
+    #[test]
+    fn histogram_from_many_threads() {
+        let histogram = Histogram::default();
+        thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| (0..500).for_each(|_| histogram.observe(0.25)));
+            }
+        });
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.count, 4000);
+        assert_eq!(snapshot.sum, 1000.0);
+        assert_eq!(snapshot.buckets[4], (0.1, 0));
+        assert_eq!(snapshot.buckets[5], (0.25, 4000));
+    }
+    #[test]
+    #[should_panic(expected = "strictly increasing")]
+    fn unsorted_bounds_panic() {
+        Histogram::new(&[1.0, 1.0]);
+    }
+    #[test]
+    #[should_panic(expected = "must be finite")]
+    fn infinite_bounds_panic() {
+        Histogram::new(&[1.0, f64::INFINITY]);
+    }
+    #[test]
+    fn registry_returns_the_same_metric_for_a_name() {
+        let registry = Registry::new();
+        registry.counter("jobs_total", "Jobs.").add(2);
+        registry.clone().counter("jobs_total", "ignored").inc();
+        assert_eq!(registry.counter("jobs_total", "").get(), 3);
+    }
+    #[test]
+    #[should_panic(expected = "already registered as a counter")]
+    fn registry_rejects_kind_mismatch() {
+        let registry = Registry::new();
+        registry.counter("jobs", "");
+        registry.gauge("jobs", "");
+    }
+    #[test]
+    #[should_panic(expected = "invalid metric name")]
+    fn registry_rejects_invalid_names() {
+        Registry::new().counter("2fast", "");
+    }
+    #[test]
+    fn renders_prometheus_text_format() {
+        let registry = Registry::new();
+        registry.gauge("connections", "Open\nconnections.").set(3);
+        registry.counter("bytes_total", "Bytes \\ sent.").add(1024);
+        let latency = registry.histogram("latency_seconds", "Latency.", &[0.1, 0.5]);
+        latency.observe(0.0625);
+        latency.observe(0.25);
+        let expected = "\
+# HELP bytes_total Bytes \\\\ sent.
+# TYPE bytes_total counter
+bytes_total 1024
+# HELP connections Open\\nconnections.
+# TYPE connections gauge
+connections 3
+# HELP latency_seconds Latency.
+# TYPE latency_seconds histogram
+latency_seconds_bucket{le=\"0.1\"} 1
+latency_seconds_bucket{le=\"0.5\"} 2
+latency_seconds_bucket{le=\"+Inf\"} 2
+latency_seconds_sum 0.3125
+latency_seconds_count 2
+";
+        assert_eq!(registry.render(), expected);
+    }
+}
@@ -0,0 +1,350 @@
+//! Parallel versions of `map`, `filter`, `reduce` and `for_each` over slices.
+//!
+//! The slice is split into index ranges that live in one deque per worker.
+//! A worker splits big ranges in half and keeps working on the first half
+//! while the second half waits at the back of its deque. Idle workers steal
+//! from the front of the other deques, where the biggest ranges are, so the
+//! load evens out even if some elements are much more expensive than others.
+
+use std::collections::VecDeque;
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+/// Ranges with at most this many elements per worker are not split further
+/// (the actual grain also depends on the slice length).
+const MIN_GRAIN: usize = 1;
+
+/// Number of workers used by the `ParallelSlice` methods.
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Parallel operations on slices, see the module documentation.
+///
+/// # Examples
+///
+/// ```
+/// use concurrency::par_iter::ParallelSlice;
+/// let numbers: Vec<u64> = (1..=1000).collect();
+/// let squares = numbers.par_map(|n| n * n);
+/// assert_eq!(squares[9], 100);
+/// let even = numbers.par_filter(|n| *n % 2 == 0);
+/// assert_eq!(even.len(), 500);
+/// assert_eq!(numbers.par_reduce(|a, b| a + b), Some(500500));
+/// ```
+pub trait ParallelSlice<T: Sync> {
+    /// Apply `f` to every element, the results keep the order of the slice.
+    fn par_map<U, F>(&self, f: F) -> Vec<U>
+    where
+        U: Send,
+        F: Fn(&T) -> U + Sync;
+
+    /// References to all elements matching `predicate`, in slice order.
+    fn par_filter<F>(&self, predicate: F) -> Vec<&T>
+    where
+        F: Fn(&T) -> bool + Sync;
+
+    /// Combine all elements with `op`, which has to be associative since
+    /// parts of the slice are reduced independently. `None` for an empty slice.
+    fn par_reduce<F>(&self, op: F) -> Option<T>
+    where
+        T: Clone + Send,
+        F: Fn(T, T) -> T + Sync;
+
+    /// Call `f` on every element, in no particular order.
+    fn par_for_each<F>(&self, f: F)
+    where
+        F: Fn(&T) + Sync;
+}
+
+impl<T: Sync> ParallelSlice<T> for [T] {
+    fn par_map<U, F>(&self, f: F) -> Vec<U>
+    where
+        U: Send,
+        F: Fn(&T) -> U + Sync,
+    {
+        par_map_with(self, default_threads(), f)
+    }
+
+    fn par_filter<F>(&self, predicate: F) -> Vec<&T>
+    where
+        F: Fn(&T) -> bool + Sync,
+    {
+        par_filter_with(self, default_threads(), predicate)
+    }
+
+    fn par_reduce<F>(&self, op: F) -> Option<T>
+    where
+        T: Clone + Send,
+        F: Fn(T, T) -> T + Sync,
+    {
+        par_reduce_with(self, default_threads(), op)
+    }
+
+    fn par_for_each<F>(&self, f: F)
+    where
+        F: Fn(&T) + Sync,
+    {
+        par_for_each_with(self, default_threads(), f)
+    }
+}
+
+/// `par_map` with an explicit number of worker threads.
+pub fn par_map_with<T, U, F>(items: &[T], threads: usize, f: F) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> U + Sync,
+{
+    let chunks = run_chunks(items, threads, |chunk| {
+        chunk.iter().map(&f).collect::<Vec<_>>()
+    });
+    chunks.into_iter().flatten().collect()
+}
+
+/// `par_filter` with an explicit number of worker threads.
+pub fn par_filter_with<T, F>(items: &[T], threads: usize, predicate: F) -> Vec<&T>
+where
+    T: Sync,
+    F: Fn(&T) -> bool + Sync,
+{
+    let chunks = run_chunks(items, threads, |chunk| {
+        chunk
+            .iter()
+            .filter(|item| predicate(item))
+            .collect::<Vec<_>>()
+    });
+    chunks.into_iter().flatten().collect()
+}
+
+/// `par_reduce` with an explicit number of worker threads.
+pub fn par_reduce_with<T, F>(items: &[T], threads: usize, op: F) -> Option<T>
+where
+    T: Sync + Send + Clone,
+    F: Fn(T, T) -> T + Sync,
+{
+    let chunks = run_chunks(items, threads, |chunk| chunk.iter().cloned().reduce(&op));
+    // chunk results are in slice order, so `op` only needs to be associative
+    chunks.into_iter().flatten().reduce(&op)
+}
+
+/// `par_for_each` with an explicit number of worker threads.
+pub fn par_for_each_with<T, F>(items: &[T], threads: usize, f: F)
+where
+    T: Sync,
+    F: Fn(&T) + Sync,
+{
+    run_chunks(items, threads, |chunk| chunk.iter().for_each(&f));
+}
+
+/// Run `work` on disjoint chunks of `items` with work stealing and return
+/// the chunk results in slice order.
+fn run_chunks<'a, T, R, F>(items: &'a [T], threads: usize, work: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&'a [T]) -> R + Sync,
+{
+    let threads = threads.clamp(1, items.len().max(1));
+    if threads == 1 {
+        return vec![work(items)];
+    }
+    // small enough for stealing to pay off, big enough to not drown in overhead
+    let grain = (items.len() / (threads * 8)).max(MIN_GRAIN);
+
+    // start with one contiguous range per worker
+    let per_worker = items.len().div_ceil(threads);
+    let deques: Vec<Mutex<VecDeque<Range<usize>>>> = (0..threads)
+        .map(|w| {
+            let start = (w * per_worker).min(items.len());
+            let end = ((w + 1) * per_worker).min(items.len());
+            let mut deque = VecDeque::new();
+            if start < end {
+                deque.push_back(start..end);
+            }
+            Mutex::new(deque)
+        })
+        .collect();
+    let remaining = AtomicUsize::new(items.len());
+    let aborted = AtomicBool::new(false);
+    let signal = Signal::default();
+    let results = Mutex::new(Vec::new());
+    let done = || remaining.load(Ordering::Acquire) == 0 || aborted.load(Ordering::Relaxed);
+
+    thread::scope(|scope| {
+        for worker in 0..threads {
+            let (deques, remaining, aborted) = (&deques, &remaining, &aborted);
+            let (signal, results, work, done) = (&signal, &results, &work, &done);
+            scope.spawn(move || {
+                // the range of a panicking worker is never finished, so the
+                // others must not wait for it (`scope` re-raises the panic)
+                let _guard = AbortOnPanic(aborted, signal);
+                let mut local = Vec::new();
+                while !done() {
+                    let seen = signal.generation();
+                    let Some(mut range) = pop_or_steal(deques, worker) else {
+                        // others are still busy and may split off more work
+                        signal.wait(seen, done);
+                        continue;
+                    };
+                    // keep the first half, offer the second half for stealing
+                    while range.len() > grain {
+                        let middle = range.start + range.len() / 2;
+                        lock(&deques[worker]).push_back(middle..range.end);
+                        signal.notify();
+                        range.end = middle;
+                    }
+                    local.push((range.start, work(&items[range.clone()])));
+                    if remaining.fetch_sub(range.len(), Ordering::AcqRel) == range.len() {
+                        signal.notify();
+                    }
+                }
+                lock(results).extend(local);
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap_or_else(|p| p.into_inner());
+    results.sort_unstable_by_key(|(start, _)| *start);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+struct AbortOnPanic<'a>(&'a AtomicBool, &'a Signal);
+
+impl Drop for AbortOnPanic<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.store(true, Ordering::Relaxed);
+            self.1.notify();
+        }
+    }
+}
+
+/// Wakes idle workers when there is new work to steal or nothing is left.
+#[derive(Default)]
+struct Signal {
+    // bumped on every notification, so a worker can't miss one that comes
+    // between looking for work and going to sleep
+    generation: Mutex<u64>,
+    condvar: Condvar,
+}
+
+impl Signal {
+    fn generation(&self) -> u64 {
+        *lock(&self.generation)
+    }
+
+    fn notify(&self) {
+        *lock(&self.generation) += 1;
+        self.condvar.notify_all();
+    }
+
+    /// Block until a notification newer than `seen` or until `done`.
+    fn wait(&self, seen: u64, done: impl Fn() -> bool) {
+        let mut generation = lock(&self.generation);
+        while *generation == seen && !done() {
+            generation = self
+                .condvar
+                .wait(generation)
+                .unwrap_or_else(|p| p.into_inner());
+        }
+    }
+}
+
+/// Take the newest range of our own deque or the oldest one of another worker.
+fn pop_or_steal(deques: &[Mutex<VecDeque<Range<usize>>>], worker: usize) -> Option<Range<usize>> {
+    if let Some(range) = lock(&deques[worker]).pop_back() {
+        return Some(range);
+    }
+    // start with the neighbour, so not every idle worker hits the same deque
+    (1..deques.len())
+        .map(|offset| (worker + offset) % deques.len())
+        .find_map(|victim| lock(&deques[victim]).pop_front())
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    // a panicking closure propagates through `thread::scope` anyway
+    mutex.lock().unwrap_or_else(|p| p.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn fibonacci(n: u64) -> u64 {
+        if n < 2 {
+            n
+        } else {
+            fibonacci(n - 1) + fibonacci(n - 2)
+        }
+    }
+
+    #[test]
+    fn map_keeps_order() {
+        let items: Vec<u32> = (0..10_000).collect();
+        for threads in [1, 2, 3, 8, 64] {
+            let doubled = par_map_with(&items, threads, |n| n * 2);
+            assert_eq!(doubled, items.iter().map(|n| n * 2).collect::<Vec<_>>());
+        }
+    }
+    #[test]
+    fn filter_keeps_order() {
+        let items: Vec<i32> = (-500..500).collect();
+        let positive = par_filter_with(&items, 4, |n| *n > 0 && n % 3 == 0);
+        let expected: Vec<&i32> = items.iter().filter(|n| **n > 0 && *n % 3 == 0).collect();
+        assert_eq!(positive, expected);
+    }
+    #[test]
+    fn reduce_with_non_commutative_op() {
+        // string concatenation is associative but not commutative
+        let words: Vec<String> = (0..200).map(|i| i.to_string()).collect();
+        let joined = par_reduce_with(&words, 7, |a, b| a + &b);
+        assert_eq!(joined, Some(words.concat()));
+        assert_eq!(par_reduce_with(&Vec::<u8>::new(), 4, |a, b| a + b), None);
+    }
+    #[test]
+    fn for_each_visits_every_element_once() {
+        let items: Vec<usize> = (0..5_000).collect();
+        let visited: Vec<AtomicUsize> = (0..items.len()).map(|_| AtomicUsize::new(0)).collect();
+        items.par_for_each(|&i| {
+            visited[i].fetch_add(1, Ordering::Relaxed);
+        });
+        assert!(visited.iter().all(|v| v.load(Ordering::Relaxed) == 1));
+    }
+    #[test]
+    fn handles_empty_and_tiny_slices() {
+        let empty: [u8; 0] = [];
+        assert!(empty.par_map(|n| *n).is_empty());
+        assert!(empty.par_filter(|_| true).is_empty());
+        assert_eq!([5].par_reduce(|a, b| a + b), Some(5));
+        assert_eq!(par_map_with(&[1, 2, 3], 16, |n| n + 1), vec![2, 3, 4]);
+    }
+    #[test]
+    fn uneven_work_gets_stolen() {
+        // all the expensive elements are at the start, in the first worker's range
+        let items: Vec<u64> = (0..64).map(|i| if i < 8 { 25 } else { 1 }).collect();
+        let workers = Mutex::new(std::collections::HashSet::new());
+        let results = par_map_with(&items, 4, |&n| {
+            workers.lock().unwrap().insert(thread::current().id());
+            if n > 1 {
+                thread::sleep(Duration::from_millis(5));
+            }
+            fibonacci(n)
+        });
+        assert_eq!(results[0], 75025);
+        assert_eq!(results[63], 1);
+        assert!(workers.lock().unwrap().len() > 1);
+    }
+    #[test]
+    fn panics_propagate() {
+        let items: Vec<i32> = (0..100).collect();
+        let result = std::panic::catch_unwind(|| {
+            par_for_each_with(&items, 4, |&n| assert!(n != 42, "found 42"));
+        });
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,474 @@
+//! A topic-based publish/subscribe bus for in-process messaging.
+//!
+//! Every subscriber of a topic gets its own copy of each message published
+//! to that topic. Subscribers have their own bounded buffer, and an
+//! [`OverflowPolicy`] decides what happens when a slow subscriber's buffer
+//! is full. Dropping a [`Subscription`] unsubscribes it.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+/// What `publish` does when a subscriber's buffer is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait until the subscriber made room (backpressure on the publisher).
+    Block,
+    /// Discard the oldest buffered message to make room for the new one.
+    DropOldest,
+    /// Discard the new message for this subscriber.
+    DropNewest,
+}
+
+struct MailboxState<T> {
+    queue: VecDeque<T>,
+    dropped: usize,
+    // set when the subscription is dropped or the bus goes away
+    closed: bool,
+}
+
+struct Mailbox<T> {
+    id: usize,
+    state: Mutex<MailboxState<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    policy: OverflowPolicy,
+}
+
+impl<T> Mailbox<T> {
+    fn lock(&self) -> MutexGuard<'_, MailboxState<T>> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Returns `false` if the message did not end up in the buffer.
+    fn deliver(&self, message: T) -> bool {
+        let mut state = self.lock();
+        if state.closed {
+            return false;
+        }
+        if state.queue.len() == self.capacity {
+            match self.policy {
+                OverflowPolicy::Block => {
+                    while state.queue.len() == self.capacity && !state.closed {
+                        state = self.not_full.wait(state).unwrap_or_else(|p| p.into_inner());
+                    }
+                    if state.closed {
+                        return false;
+                    }
+                }
+                OverflowPolicy::DropOldest => {
+                    state.queue.pop_front();
+                    state.dropped += 1;
+                }
+                OverflowPolicy::DropNewest => {
+                    state.dropped += 1;
+                    return false;
+                }
+            }
+        }
+        state.queue.push_back(message);
+        drop(state);
+        self.not_empty.notify_one();
+        true
+    }
+
+    fn close(&self) {
+        self.lock().closed = true;
+        // wake up both a waiting subscriber and blocked publishers
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+    }
+}
+
+struct BusInner<T> {
+    topics: Mutex<HashMap<String, Vec<Arc<Mailbox<T>>>>>,
+    next_id: AtomicUsize,
+}
+
+impl<T> BusInner<T> {
+    fn topics(&self) -> MutexGuard<'_, HashMap<String, Vec<Arc<Mailbox<T>>>>> {
+        self.topics
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<T> Drop for BusInner<T> {
+    fn drop(&mut self) {
+        // the last bus handle is gone, nothing will ever be published again
+        for mailboxes in self.topics().values() {
+            for mailbox in mailboxes {
+                mailbox.close();
+            }
+        }
+    }
+}
+
+/// Handle to the bus, cheap to clone and share between threads.
+///
+/// # Examples
+///
+/// ```
+/// use concurrency::pubsub::{EventBus, OverflowPolicy};
+/// let bus = EventBus::new();
+/// let first = bus.subscribe("orders", 8, OverflowPolicy::Block);
+/// let second = bus.subscribe("orders", 8, OverflowPolicy::Block);
+/// assert_eq!(bus.publish("orders", String::from("soup")), 2);
+/// assert_eq!(first.recv().unwrap(), "soup");
+/// assert_eq!(second.recv().unwrap(), "soup");
+/// ```
+pub struct EventBus<T> {
+    inner: Arc<BusInner<T>>,
+}
+
+impl<T> Clone for EventBus<T> {
+    fn clone(&self) -> Self {
+        EventBus {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T> Default for EventBus<T> {
+    fn default() -> Self {
+        EventBus::new()
+    }
+}
+
+impl<T> EventBus<T> {
+    pub fn new() -> EventBus<T> {
+        EventBus {
+            inner: Arc::new(BusInner {
+                topics: Mutex::new(HashMap::new()),
+                next_id: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Subscribe to `topic` with a buffer of `capacity` messages.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn subscribe(
+        &self,
+        topic: &str,
+        capacity: usize,
+        policy: OverflowPolicy,
+    ) -> Subscription<T> {
+        assert!(
+            capacity > 0,
+            "subscription capacity must be greater than zero"
+        );
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let mailbox = Arc::new(Mailbox {
+            id,
+            state: Mutex::new(MailboxState {
+                queue: VecDeque::with_capacity(capacity),
+                dropped: 0,
+                closed: false,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity,
+            policy,
+        });
+        self.inner
+            .topics()
+            .entry(topic.to_string())
+            .or_default()
+            .push(Arc::clone(&mailbox));
+        Subscription {
+            topic: topic.to_string(),
+            mailbox,
+            bus: Arc::downgrade(&self.inner),
+        }
+    }
+
+    /// Number of live subscriptions for `topic`.
+    pub fn subscriber_count(&self, topic: &str) -> usize {
+        self.inner.topics().get(topic).map_or(0, Vec::len)
+    }
+}
+
+impl<T: Clone> EventBus<T> {
+    /// Send a copy of `message` to every subscriber of `topic`.
+    ///
+    /// Returns the number of subscribers that buffered the message. With
+    /// `OverflowPolicy::Block` subscribers this waits until each of them
+    /// has room.
+    pub fn publish(&self, topic: &str, message: T) -> usize {
+        // copy the list, so a blocking subscriber does not lock the whole bus
+        let mailboxes = match self.inner.topics().get(topic) {
+            Some(mailboxes) => mailboxes.clone(),
+            None => return 0,
+        };
+        mailboxes
+            .iter()
+            .filter(|mailbox| mailbox.deliver(message.clone()))
+            .count()
+    }
+}
+
+/// A subscriber's end of a topic, unsubscribes when dropped.
+pub struct Subscription<T> {
+    topic: String,
+    mailbox: Arc<Mailbox<T>>,
+    bus: Weak<BusInner<T>>,
+}
+
+impl<T> Subscription<T> {
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Block until a message arrives.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RecvError` once the buffer is empty and the bus is gone.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let mut state = self.mailbox.lock();
+        loop {
+            if let Some(message) = state.queue.pop_front() {
+                drop(state);
+                self.mailbox.not_full.notify_one();
+                return Ok(message);
+            }
+            if state.closed {
+                return Err(RecvError);
+            }
+            state = self
+                .mailbox
+                .not_empty
+                .wait(state)
+                .unwrap_or_else(|p| p.into_inner());
+        }
+    }
+
+    /// Take a message only if one is buffered right now.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TryRecvError::Empty` if there is no message yet and
+    /// `TryRecvError::Disconnected` if the bus is gone.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.mailbox.lock();
+        match state.queue.pop_front() {
+            Some(message) => {
+                drop(state);
+                self.mailbox.not_full.notify_one();
+                Ok(message)
+            }
+            None if state.closed => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Like `recv`, but gives up after `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RecvTimeoutError::Timeout` if no message arrived in time and
+    /// `RecvTimeoutError::Disconnected` if the bus is gone.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.mailbox.lock();
+        loop {
+            if let Some(message) = state.queue.pop_front() {
+                drop(state);
+                self.mailbox.not_full.notify_one();
+                return Ok(message);
+            }
+            if state.closed {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self
+                .mailbox
+                .not_empty
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|p| p.into_inner())
+                .0;
+        }
+    }
+
+    /// Number of messages discarded for this subscriber by its overflow policy.
+    pub fn dropped(&self) -> usize {
+        self.mailbox.lock().dropped
+    }
+
+    /// Number of messages waiting in the buffer.
+    pub fn len(&self) -> usize {
+        self.mailbox.lock().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Blocking iterator that ends when the bus is gone.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.recv().ok())
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        // wakes up publishers blocked on this subscriber
+        self.mailbox.close();
+        if let Some(bus) = self.bus.upgrade() {
+            let mut topics = bus.topics();
+            if let Some(mailboxes) = topics.get_mut(&self.topic) {
+                mailboxes.retain(|m| m.id != self.mailbox.id);
+                if mailboxes.is_empty() {
+                    topics.remove(&self.topic);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn every_subscriber_gets_a_copy() {
+        let bus = EventBus::new();
+        let subscribers: Vec<_> = (0..3)
+            .map(|_| bus.subscribe("news", 4, OverflowPolicy::Block))
+            .collect();
+        let _other = bus.subscribe("weather", 4, OverflowPolicy::Block);
+        assert_eq!(bus.publish("news", 1), 3);
+        assert_eq!(bus.publish("news", 2), 3);
+        for subscriber in &subscribers {
+            assert_eq!(subscriber.topic(), "news");
+            assert_eq!(subscriber.recv(), Ok(1));
+            assert_eq!(subscriber.recv(), Ok(2));
+            assert_eq!(subscriber.try_recv(), Err(TryRecvError::Empty));
+        }
+        assert_eq!(bus.publish("nobody-listens", 3), 0);
+    }
+    #[test]
+    fn dropping_a_subscription_unsubscribes() {
+        let bus = EventBus::new();
+        let first = bus.subscribe("news", 1, OverflowPolicy::Block);
+        let second = bus.subscribe("news", 1, OverflowPolicy::Block);
+        assert_eq!(bus.subscriber_count("news"), 2);
+        drop(first);
+        assert_eq!(bus.subscriber_count("news"), 1);
+        assert_eq!(bus.publish("news", "hi"), 1);
+        drop(second);
+        assert_eq!(bus.subscriber_count("news"), 0);
+        assert_eq!(bus.publish("news", "hi"), 0);
+    }
+    #[test]
+    fn drop_oldest_keeps_the_latest_messages() {
+        let bus = EventBus::new();
+        let slow = bus.subscribe("ticks", 2, OverflowPolicy::DropOldest);
+        for i in 0..5 {
+            assert_eq!(bus.publish("ticks", i), 1);
+        }
+        assert_eq!(slow.dropped(), 3);
+        assert_eq!(slow.iter().take(2).collect::<Vec<_>>(), vec![3, 4]);
+    }
+    #[test]
+    fn drop_newest_keeps_the_first_messages() {
+        let bus = EventBus::new();
+        let slow = bus.subscribe("ticks", 2, OverflowPolicy::DropNewest);
+        let delivered: usize = (0..5).map(|i| bus.publish("ticks", i)).sum();
+        assert_eq!(delivered, 2);
+        assert_eq!(slow.dropped(), 3);
+        assert_eq!(slow.len(), 2);
+        assert_eq!(slow.recv(), Ok(0));
+        assert_eq!(slow.recv(), Ok(1));
+    }
+    #[test]
+    fn block_policy_applies_backpressure() {
+        let bus = EventBus::new();
+        let slow = bus.subscribe("jobs", 1, OverflowPolicy::Block);
+        let publisher = {
+            let bus = bus.clone();
+            thread::spawn(move || {
+                for i in 0..3 {
+                    bus.publish("jobs", i);
+                }
+            })
+        };
+        thread::sleep(Duration::from_millis(20));
+        // the publisher is stuck on the second message
+        assert_eq!(slow.len(), 1);
+        assert_eq!(slow.iter().take(3).collect::<Vec<_>>(), vec![0, 1, 2]);
+        publisher.join().unwrap();
+        assert_eq!(slow.dropped(), 0);
+    }
+    #[test]
+    fn blocked_publisher_is_released_when_subscriber_leaves() {
+        let bus = EventBus::new();
+        let slow = bus.subscribe("jobs", 1, OverflowPolicy::Block);
+        let fast = bus.subscribe("jobs", 4, OverflowPolicy::Block);
+        bus.publish("jobs", 0);
+        let publisher = {
+            let bus = bus.clone();
+            thread::spawn(move || bus.publish("jobs", 1))
+        };
+        thread::sleep(Duration::from_millis(20));
+        drop(slow);
+        // only the fast subscriber got the second message
+        assert_eq!(publisher.join().unwrap(), 1);
+        assert_eq!(fast.iter().take(2).collect::<Vec<_>>(), vec![0, 1]);
+    }
+    #[test]
+    fn subscriptions_disconnect_when_bus_is_dropped() {
+        let bus = EventBus::new();
+        let subscriber = bus.subscribe("news", 2, OverflowPolicy::Block);
+        bus.publish("news", "last");
+        drop(bus);
+        assert_eq!(subscriber.recv(), Ok("last"));
+        assert_eq!(subscriber.recv(), Err(RecvError));
+        assert_eq!(
+            subscriber.recv_timeout(Duration::from_millis(1)),
+            Err(RecvTimeoutError::Disconnected)
+        );
+    }
+    #[test]
+    fn concurrent_publishers_and_subscribers() {
+        const PUBLISHERS: usize = 4;
+        const MESSAGES: usize = 250;
+        let bus = EventBus::new();
+        let subscribers: Vec<_> = (0..3)
+            .map(|_| bus.subscribe("load", 8, OverflowPolicy::Block))
+            .collect();
+        let readers: Vec<_> = subscribers
+            .into_iter()
+            .map(|s| thread::spawn(move || s.iter().take(PUBLISHERS * MESSAGES).sum::<usize>()))
+            .collect();
+        let publishers: Vec<_> = (0..PUBLISHERS)
+            .map(|p| {
+                let bus = bus.clone();
+                thread::spawn(move || {
+                    for i in 0..MESSAGES {
+                        assert_eq!(bus.publish("load", p * MESSAGES + i), 3);
+                    }
+                })
+            })
+            .collect();
+        for publisher in publishers {
+            publisher.join().unwrap();
+        }
+        let n = PUBLISHERS * MESSAGES;
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), n * (n - 1) / 2);
+        }
+    }
+}
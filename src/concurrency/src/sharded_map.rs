@@ -0,0 +1,412 @@
+//! A `HashMap` that many threads can use at once.
+//!
+//! `Mutex<HashMap>` lets one thread in at a time, even if all of them only
+//! want to read. [`ShardedMap`] splits the keys over several maps, each
+//! behind its own `RwLock`: readers of a shard share it, and writers only
+//! block the keys that hash into the same shard.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::{self, RandomState};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::ops::{Deref, DerefMut};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::par_iter::default_threads;
+
+/// A concurrent hash map with one `RwLock` per shard.
+///
+/// All methods take `&self`, so the map can be shared with a plain `Arc`
+/// or borrowed by scoped threads.
+///
+/// # Examples
+///
+/// ```
+/// use std::thread;
+/// use concurrency::sharded_map::ShardedMap;
+/// let words = ShardedMap::new();
+/// let text = ["a rose is a rose", "is a rose"];
+/// thread::scope(|s| {
+///     for line in text {
+///         let words = &words;
+///         s.spawn(move || {
+///             for word in line.split_whitespace() {
+///                 *words.entry(word).or_insert(0) += 1;
+///             }
+///         });
+///     }
+/// });
+/// assert_eq!(words.get(&"rose"), Some(3));
+/// assert_eq!(words.len(), 3);
+/// ```
+pub struct ShardedMap<K, V, S = RandomState> {
+    shards: Box<[RwLock<HashMap<K, V, S>>]>,
+    hasher: S,
+}
+
+impl<K: Hash + Eq, V> ShardedMap<K, V> {
+    /// A map with a few shards per available thread.
+    pub fn new() -> Self {
+        Self::with_shards(default_threads() * 4)
+    }
+
+    /// A map with `shards` shards, rounded up to a power of two.
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_shards_and_hasher(shards, RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq, V> Default for ShardedMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Clone> ShardedMap<K, V, S> {
+    pub fn with_shards_and_hasher(shards: usize, hasher: S) -> Self {
+        let shards = shards.max(1).next_power_of_two();
+        ShardedMap {
+            shards: (0..shards)
+                .map(|_| RwLock::new(HashMap::with_hasher(hasher.clone())))
+                .collect(),
+            hasher,
+        }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    // `Borrow` promises `Q` hashes like `K`, so lookups find the shard `insert` chose
+    fn shard<Q: ?Sized + Hash>(&self, key: &Q) -> &RwLock<HashMap<K, V, S>> {
+        let hash = self.hasher.hash_one(key);
+        // the shard maps use the same hash, take bits from the middle so
+        // keys of one shard don't all end up in the same buckets
+        let index = (hash >> 32) as usize & (self.shards.len() - 1);
+        &self.shards[index]
+    }
+
+    fn read<Q: ?Sized + Hash>(&self, key: &Q) -> RwLockReadGuard<'_, HashMap<K, V, S>> {
+        // a panic can't leave a `HashMap` half-updated, so poison is ignored
+        self.shard(key).read().unwrap_or_else(|p| p.into_inner())
+    }
+
+    fn write<Q: ?Sized + Hash>(&self, key: &Q) -> RwLockWriteGuard<'_, HashMap<K, V, S>> {
+        self.shard(key).write().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// A clone of the value for `key`. Use `get_with` to avoid the clone.
+    ///
+    /// Like `HashMap::get`, `key` may be any borrowed form of `K`, so a
+    /// `ShardedMap<String, _>` can be searched with a `&str`.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+        V: Clone,
+    {
+        self.read(key).get(key).cloned()
+    }
+
+    /// Call `f` with the value for `key` while its shard is read-locked.
+    pub fn get_with<Q, R>(&self, key: &Q, f: impl FnOnce(&V) -> R) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.read(key).get(key).map(f)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.read(key).contains_key(key)
+    }
+
+    /// Insert `value`, returning the previous value of `key`.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.write(&key).insert(key, value)
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.write(key).remove(key)
+    }
+
+    /// Lock the shard of `key` for writing and return its entry, for
+    /// read-modify-write updates that no other thread can interleave with.
+    ///
+    /// The key is cloned because the returned reference has to look the
+    /// value up again after inserting it.
+    ///
+    /// # Deadlocks
+    ///
+    /// The shard stays locked until the `Entry` or `RefMut` is dropped, so
+    /// don't call other methods of the map while holding one.
+    pub fn entry(&self, key: K) -> Entry<'_, K, V, S>
+    where
+        K: Clone,
+    {
+        Entry {
+            guard: self.write(&key),
+            key,
+        }
+    }
+
+    /// Number of entries. Shards are counted one after another, so with
+    /// concurrent updates this is only a rough figure.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| read(shard).len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| read(shard).is_empty())
+    }
+
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            write(shard).clear();
+        }
+    }
+
+    /// Keep only the entries for which `f` returns `true`.
+    pub fn retain(&self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        for shard in self.shards.iter() {
+            write(shard).retain(&mut f);
+        }
+    }
+
+    /// Copies of all entries, in no particular order.
+    ///
+    /// Every shard is copied consistently, but shards are locked one at a
+    /// time: an update running concurrently may show up in one shard and
+    /// not yet in another.
+    pub fn snapshot(&self) -> Vec<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let mut entries = Vec::with_capacity(self.len());
+        for shard in self.shards.iter() {
+            entries.extend(read(shard).iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        entries
+    }
+
+    /// Unwrap into a plain `HashMap`.
+    pub fn into_inner(self) -> HashMap<K, V, S> {
+        let mut map = HashMap::with_hasher(self.hasher);
+        for shard in self.shards.into_vec() {
+            map.extend(shard.into_inner().unwrap_or_else(|p| p.into_inner()));
+        }
+        map
+    }
+}
+
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(|p| p.into_inner())
+}
+
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(|p| p.into_inner())
+}
+
+impl<K: Hash + Eq, V> FromIterator<(K, V)> for ShardedMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let map = ShardedMap::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+impl<K, V, S> std::fmt::Debug for ShardedMap<K, V, S>
+where
+    K: Hash + Eq + std::fmt::Debug,
+    V: std::fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut map = f.debug_map();
+        for shard in self.shards.iter() {
+            map.entries(read(shard).iter());
+        }
+        map.finish()
+    }
+}
+
+/// An entry of a `ShardedMap`, holding the write lock of its shard.
+pub struct Entry<'a, K, V, S> {
+    guard: RwLockWriteGuard<'a, HashMap<K, V, S>>,
+    key: K,
+}
+
+impl<'a, K: Hash + Eq + Clone, V, S: BuildHasher> Entry<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn is_occupied(&self) -> bool {
+        self.guard.contains_key(&self.key)
+    }
+
+    /// Update the value if there is one.
+    pub fn and_modify(mut self, f: impl FnOnce(&mut V)) -> Self {
+        if let Some(value) = self.guard.get_mut(&self.key) {
+            f(value);
+        }
+        self
+    }
+
+    pub fn or_insert(self, default: V) -> RefMut<'a, K, V, S> {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with(mut self, default: impl FnOnce() -> V) -> RefMut<'a, K, V, S> {
+        if let hash_map::Entry::Vacant(vacant) = self.guard.entry(self.key.clone()) {
+            vacant.insert(default());
+        }
+        RefMut {
+            guard: self.guard,
+            key: self.key,
+        }
+    }
+
+    pub fn or_default(self) -> RefMut<'a, K, V, S>
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+}
+
+/// A value in a `ShardedMap` that is locked for writing.
+pub struct RefMut<'a, K, V, S> {
+    guard: RwLockWriteGuard<'a, HashMap<K, V, S>>,
+    key: K,
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> Deref for RefMut<'_, K, V, S> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        // only created after the value was inserted, and the lock is held
+        self.guard.get(&self.key).expect("entry was inserted")
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> DerefMut for RefMut<'_, K, V, S> {
+    fn deref_mut(&mut self) -> &mut V {
+        self.guard.get_mut(&self.key).expect("entry was inserted")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn basic_operations() {
+        let map = ShardedMap::with_shards(3);
+        assert_eq!(map.shard_count(), 4);
+        assert!(map.is_empty());
+        assert_eq!(map.insert("a", 1), None);
+        assert_eq!(map.insert("a", 2), Some(1));
+        assert_eq!(map.get(&"a"), Some(2));
+        assert_eq!(map.get_with(&"a", |v| v * 10), Some(20));
+        assert!(map.contains_key(&"a"));
+        assert_eq!(map.remove(&"a"), Some(2));
+        assert_eq!(map.remove(&"a"), None);
+        assert_eq!(map.get(&"a"), None);
+    }
+    #[test]
+    fn entry_api() {
+        let map = ShardedMap::with_shards(1);
+        *map.entry("x").or_insert(1) += 1;
+        assert_eq!(map.get(&"x"), Some(2));
+        let entry = map.entry("x").and_modify(|v| *v *= 10);
+        assert!(entry.is_occupied());
+        assert_eq!(*entry.or_insert(0), 20);
+        let entry = map.entry("y").and_modify(|v| *v *= 10);
+        assert_eq!(entry.key(), &"y");
+        assert!(!entry.is_occupied());
+        assert_eq!(*entry.or_default(), 0);
+    }
+    #[test]
+    fn concurrent_inserts_from_many_threads() {
+        let map = ShardedMap::new();
+        thread::scope(|s| {
+            for t in 0..8 {
+                let map = &map;
+                s.spawn(move || {
+                    for i in 0..1000 {
+                        map.insert(t * 1000 + i, i);
+                    }
+                });
+            }
+        });
+        assert_eq!(map.len(), 8000);
+        assert_eq!(map.get(&7999), Some(999));
+    }
+    #[test]
+    fn entry_updates_are_not_lost() {
+        let map = ShardedMap::with_shards(4);
+        thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    for i in 0..1000 {
+                        *map.entry(i % 10).or_insert(0) += 1;
+                    }
+                });
+            }
+        });
+        let mut counts = map.snapshot();
+        counts.sort();
+        assert_eq!(counts, (0..10).map(|k| (k, 800)).collect::<Vec<_>>());
+    }
+    #[test]
+    fn readers_share_a_shard() {
+        let map: ShardedMap<_, _> = [(1, "one")].into_iter().collect();
+        // a second reader gets in while the first holds the read lock
+        map.get_with(&1, |_| {
+            thread::scope(|s| {
+                s.spawn(|| assert_eq!(map.get(&1), Some("one")));
+            });
+        });
+    }
+    #[test]
+    fn retain_clear_and_into_inner() {
+        let map: ShardedMap<_, _> = (0..100).map(|i| (i, i)).collect();
+        map.retain(|k, v| {
+            *v += 1;
+            k % 2 == 0
+        });
+        assert_eq!(map.len(), 50);
+        assert_eq!(map.get(&10), Some(11));
+        let inner = map.into_inner();
+        assert_eq!(inner.len(), 50);
+
+        let map: ShardedMap<_, _> = (0..10).map(|i| (i, i)).collect();
+        map.clear();
+        assert!(map.is_empty());
+    }
+    #[test]
+    fn owned_keys_are_found_by_borrowed_ones() {
+        let map = ShardedMap::with_shards(16);
+        for word in ["rose", "tulip", "daisy"] {
+            map.insert(word.to_string(), word.len());
+        }
+        assert_eq!(map.get("tulip"), Some(5));
+        assert_eq!(map.get_with("rose", |len| len * 2), Some(8));
+        assert!(map.contains_key("daisy"));
+        assert_eq!(map.remove("daisy"), Some(5));
+        assert!(!map.contains_key("daisy"));
+    }
+}
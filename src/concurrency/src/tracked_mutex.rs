@@ -0,0 +1,437 @@
+//! A `Mutex` wrapper that detects lock-order inversions.
+//!
+//! Two threads that take the same two locks in opposite order can deadlock,
+//! but only if they happen to interleave badly, which makes it hard to
+//! reproduce. In debug builds every [`TrackedMutex`] records which locks
+//! the current thread already holds when it is locked, and adds an edge
+//! "held before" to a global lock graph. Locking in an order that closes a
+//! cycle in that graph panics right away with both lock sites, whether or
+//! not this run would actually have deadlocked. In release builds the
+//! tracking is compiled out and only the plain `Mutex` remains.
+//!
+//! # Examples
+//!
+//! ```
+//! use concurrency::tracked_mutex::TrackedMutex;
+//! let accounts = TrackedMutex::with_name("accounts", 100);
+//! let audit_log = TrackedMutex::with_name("audit_log", Vec::new());
+//! {
+//!     let balance = accounts.lock().unwrap();
+//!     audit_log.lock().unwrap().push(*balance); // accounts -> audit_log
+//! }
+//! // the opposite order is reported even though nothing deadlocks here
+//! let inverted = std::panic::catch_unwind(|| {
+//!     let _log = audit_log.lock().unwrap();
+//!     let _balance = accounts.lock().unwrap();
+//! });
+//! assert!(inverted.is_err() || !cfg!(debug_assertions));
+//! ```
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::ops::{Deref, DerefMut};
+use std::panic::Location;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{LockResult, Mutex, MutexGuard, OnceLock, PoisonError};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// Where an edge of the lock graph was first seen.
+#[derive(Clone, Copy)]
+struct EdgeSites {
+    held: &'static Location<'static>,
+    acquired: &'static Location<'static>,
+}
+
+#[derive(Default)]
+struct LockGraph {
+    // `edges[a]` contains `b` if `b` was locked while `a` was held
+    edges: HashMap<usize, HashSet<usize>>,
+    sites: HashMap<(usize, usize), EdgeSites>,
+    names: HashMap<usize, String>,
+}
+
+impl LockGraph {
+    /// A path of lock ids from `from` to `to`, if there is one.
+    fn path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let mut stack = vec![vec![from]];
+        let mut visited = HashSet::new();
+        while let Some(path) = stack.pop() {
+            let last = *path.last().expect("paths are never empty");
+            if last == to {
+                return Some(path);
+            }
+            if !visited.insert(last) {
+                continue;
+            }
+            for next in self.edges.get(&last).into_iter().flatten() {
+                let mut longer = path.clone();
+                longer.push(*next);
+                stack.push(longer);
+            }
+        }
+        None
+    }
+
+    fn name(&self, id: usize) -> String {
+        match self.names.get(&id) {
+            Some(name) => format!("`{name}` (#{id})"),
+            None => format!("#{id}"),
+        }
+    }
+
+    fn remove(&mut self, id: usize) {
+        self.edges.remove(&id);
+        for targets in self.edges.values_mut() {
+            targets.remove(&id);
+        }
+        self.sites.retain(|(a, b), _| *a != id && *b != id);
+        self.names.remove(&id);
+    }
+}
+
+fn graph() -> MutexGuard<'static, LockGraph> {
+    static GRAPH: OnceLock<Mutex<LockGraph>> = OnceLock::new();
+    GRAPH
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+}
+
+thread_local! {
+    // locks held by this thread, with the place they were locked at
+    static HELD: RefCell<Vec<(usize, &'static Location<'static>)>> = const { RefCell::new(Vec::new()) };
+}
+
+pub struct TrackedMutex<T> {
+    id: usize,
+    inner: Mutex<T>,
+}
+
+impl<T> TrackedMutex<T> {
+    pub fn new(value: T) -> TrackedMutex<T> {
+        TrackedMutex {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            inner: Mutex::new(value),
+        }
+    }
+
+    /// Like `new`, the name shows up in lock-order reports.
+    pub fn with_name(name: &str, value: T) -> TrackedMutex<T> {
+        let mutex = TrackedMutex::new(value);
+        if cfg!(debug_assertions) {
+            graph().names.insert(mutex.id, name.to_string());
+        }
+        mutex
+    }
+
+    /// Lock the mutex, just like `Mutex::lock`.
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, panics if this thread already holds this lock or if
+    /// taking it now contradicts the order of earlier acquisitions.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PoisonError` if another thread panicked while holding it.
+    #[track_caller]
+    pub fn lock(&self) -> LockResult<TrackedMutexGuard<'_, T>> {
+        let site = Location::caller();
+        if cfg!(debug_assertions) {
+            self.check_order(site);
+        }
+        let (guard, poisoned) = match self.inner.lock() {
+            Ok(guard) => (guard, false),
+            Err(poisoned) => (poisoned.into_inner(), true),
+        };
+        if cfg!(debug_assertions) {
+            HELD.with(|held| held.borrow_mut().push((self.id, site)));
+        }
+        let guard = TrackedMutexGuard { id: self.id, guard };
+        if poisoned {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+
+    /// Mutable access without locking, the borrow checker proves exclusivity.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PoisonError` if another thread panicked while holding it.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        self.inner.get_mut()
+    }
+
+    /// Record the new edges and panic before they close a cycle.
+    fn check_order(&self, site: &'static Location<'static>) {
+        let held: Vec<_> = HELD.with(|held| held.borrow().clone());
+        if let Some((_, first_site)) = held.iter().find(|(id, _)| *id == self.id) {
+            let name = graph().name(self.id);
+            panic!("lock {name} locked at {site} while this thread already holds it since {first_site}, this would deadlock");
+        }
+
+        let mut graph = graph();
+        // known edges were checked when they were added
+        let new: Vec<_> = held
+            .into_iter()
+            .filter(|(held_id, _)| {
+                !graph
+                    .edges
+                    .get(held_id)
+                    .is_some_and(|e| e.contains(&self.id))
+            })
+            .collect();
+        // check all of them before adding any, a rejected lock leaves no edges behind
+        for &(held_id, held_site) in &new {
+            if let Some(path) = graph.path(self.id, held_id) {
+                let report = inversion_report(&graph, &path, held_id, held_site, self.id, site);
+                // release the graph before unwinding, other threads still need it
+                drop(graph);
+                panic!("{report}");
+            }
+        }
+        for (held_id, held_site) in new {
+            graph.edges.entry(held_id).or_default().insert(self.id);
+            graph.sites.insert(
+                (held_id, self.id),
+                EdgeSites {
+                    held: held_site,
+                    acquired: site,
+                },
+            );
+        }
+    }
+}
+
+/// Describe the new edge `held -> acquired` and the existing path
+/// `acquired -> ... -> held` it would close into a cycle.
+fn inversion_report(
+    graph: &LockGraph,
+    path: &[usize],
+    held: usize,
+    held_site: &'static Location<'static>,
+    acquired: usize,
+    acquired_site: &'static Location<'static>,
+) -> String {
+    let mut report = format!(
+        "lock order violation: locking {} at {acquired_site} while holding {} (locked at {held_site}),\n\
+         but earlier the locks were taken in the opposite order:",
+        graph.name(acquired),
+        graph.name(held),
+    );
+    for pair in path.windows(2) {
+        let sites = graph.sites[&(pair[0], pair[1])];
+        // writing into a `String` cannot fail
+        let _ = write!(
+            report,
+            "\n  {} was locked at {} while holding {} (locked at {})",
+            graph.name(pair[1]),
+            sites.acquired,
+            graph.name(pair[0]),
+            sites.held,
+        );
+    }
+    report
+}
+
+impl<T> Drop for TrackedMutex<T> {
+    fn drop(&mut self) {
+        if cfg!(debug_assertions) {
+            // ids are never reused, but the graph should not grow forever
+            graph().remove(self.id);
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for TrackedMutex<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("TrackedMutex")
+            .field("id", &self.id)
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+/// Releases the lock and forgets it in the per-thread bookkeeping on drop.
+pub struct TrackedMutexGuard<'a, T> {
+    id: usize,
+    guard: MutexGuard<'a, T>,
+}
+
+impl<T> Deref for TrackedMutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for TrackedMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for TrackedMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&*self.guard, f)
+    }
+}
+
+impl<T> Drop for TrackedMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        if cfg!(debug_assertions) {
+            // guards are not always dropped in reverse order, so search for it
+            HELD.with(|held| {
+                let mut held = held.borrow_mut();
+                if let Some(index) = held.iter().rposition(|(id, _)| *id == self.id) {
+                    held.remove(index);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use super::*;
+    use std::panic;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn panic_message(result: thread::Result<()>) -> String {
+        let payload = result.expect_err("expected a lock order panic");
+        match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => payload.downcast_ref::<&str>().unwrap_or(&"").to_string(),
+        }
+    }
+
+    #[test]
+    fn consistent_order_is_fine() {
+        let a = TrackedMutex::new(1);
+        let b = TrackedMutex::new(2);
+        for _ in 0..3 {
+            let a = a.lock().unwrap();
+            let b = b.lock().unwrap();
+            assert_eq!(*a + *b, 3);
+        }
+        // taking them one at a time in any order is fine, too
+        drop(b.lock().unwrap());
+        drop(a.lock().unwrap());
+    }
+    #[test]
+    fn inverted_order_panics_with_both_sites() {
+        let a = TrackedMutex::with_name("a", ());
+        let b = TrackedMutex::with_name("b", ());
+        {
+            let _a = a.lock().unwrap();
+            let _b = b.lock().unwrap();
+        }
+        let message = panic_message(panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let _b = b.lock().unwrap();
+            let _a = a.lock().unwrap();
+        })));
+        assert!(
+            message.starts_with("lock order violation: locking `a`"),
+            "{message}"
+        );
+        assert!(message.contains("while holding `b`"), "{message}");
+        assert!(message.contains("`b` (#"), "{message}");
+        // both the current and the earlier lock sites are in the report
+        assert_eq!(message.matches(file!()).count(), 4, "{message}");
+        // the panic released everything, so the good order still works
+        // (`b` was held while panicking, which poisoned it)
+        let _a = a.lock().unwrap();
+        let _b = b.lock().unwrap_or_else(PoisonError::into_inner);
+    }
+    #[test]
+    fn detects_inversion_across_threads_and_longer_cycles() {
+        let a = Arc::new(TrackedMutex::with_name("first", 0));
+        let b = Arc::new(TrackedMutex::with_name("second", 0));
+        let c = Arc::new(TrackedMutex::with_name("third", 0));
+        // two threads establish first -> second and second -> third
+        {
+            let (a, b) = (Arc::clone(&a), Arc::clone(&b));
+            thread::spawn(move || {
+                let _a = a.lock().unwrap();
+                let _b = b.lock().unwrap();
+            })
+            .join()
+            .unwrap();
+        }
+        {
+            let (b, c) = (Arc::clone(&b), Arc::clone(&c));
+            thread::spawn(move || {
+                let _b = b.lock().unwrap();
+                let _c = c.lock().unwrap();
+            })
+            .join()
+            .unwrap();
+        }
+        // another one takes third -> first, which would close the cycle
+        let result = thread::spawn(move || {
+            let _c = c.lock().unwrap();
+            let _a = a.lock().unwrap();
+        })
+        .join();
+        let message = panic_message(result);
+        assert!(message.contains("locking `first`"), "{message}");
+        assert!(message.contains("`second` (#"), "{message}");
+    }
+    #[test]
+    fn rejected_locks_add_no_edges() {
+        let a = TrackedMutex::with_name("a", ());
+        let b = TrackedMutex::with_name("b", ());
+        let c = TrackedMutex::with_name("c", ());
+        {
+            let _a = a.lock().unwrap();
+            let _b = b.lock().unwrap();
+        }
+        // `c -> a` is fine on its own, but `b -> a` is rejected
+        let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let _c = c.lock().unwrap();
+            let _b = b.lock().unwrap();
+            let _a = a.lock().unwrap();
+        }));
+        // so `a -> c` doesn't close a cycle through a `c -> a` that never happened
+        let _a = a.lock().unwrap_or_else(PoisonError::into_inner);
+        let _c = c.lock().unwrap_or_else(PoisonError::into_inner);
+    }
+    #[test]
+    fn relocking_on_the_same_thread_panics() {
+        let a = TrackedMutex::new(());
+        let _guard = a.lock().unwrap();
+        let message = panic_message(panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let _again = a.lock();
+        })));
+        assert!(message.contains("already holds it"), "{message}");
+    }
+    #[test]
+    fn guards_can_be_dropped_out_of_order() {
+        let a = TrackedMutex::new(());
+        let b = TrackedMutex::new(());
+        let guard_a = a.lock().unwrap();
+        let guard_b = b.lock().unwrap();
+        drop(guard_a);
+        drop(guard_b);
+        // both are released, otherwise this would report a relock
+        let _a = a.lock().unwrap();
+        let _b = b.lock().unwrap();
+    }
+    #[test]
+    fn poisoning_is_passed_through() {
+        let a = Arc::new(TrackedMutex::new(5));
+        let poisoner = Arc::clone(&a);
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poison the lock");
+        })
+        .join();
+        let guard = a.lock().unwrap_err().into_inner();
+        assert_eq!(*guard, 5);
+    }
+}
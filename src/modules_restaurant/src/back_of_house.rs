@@ -1,9 +1,7 @@
-fn _fix_incorrect_order() {
-    _cook_order();
-    super::_deliver_order(); // use "super::"" since function is defined in parent module
-}
+pub mod kitchen;
+pub mod menu;
 
-fn _cook_order() {}
+use crate::time::Time;
 
 pub struct Breakfast {
     pub toast: String, // need to set "pub" here
@@ -17,9 +15,39 @@ impl Breakfast {
             _seasonal_fruit: String::from("peaches"),
         }
     }
+
+    /// The chef picks the first seasonal fruit on the menu that day, and
+    /// peaches if nothing is in season.
+    pub fn in_season(toast: &str, menu: &menu::Menu, day: Time) -> Breakfast {
+        let fruit = menu.in_season("Seasonal fruit", day);
+        Breakfast {
+            toast: String::from(toast),
+            _seasonal_fruit: fruit
+                .map_or_else(|| String::from("peaches"), |fruit| fruit.name.clone()),
+        }
+    }
 }
 #[derive(Debug)]
 pub enum Appetizer {
     Soup, // default is public!!!
     Salad,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breakfast_comes_with_fruit_in_season() {
+        let menu = menu::Menu::from_toml(include_str!("../menu.toml")).unwrap();
+        let fruit = |month| {
+            let day = Time::from_ymd_hm(2024, month, 1, 8, 0);
+            Breakfast::in_season("Rye", &menu, day)._seasonal_fruit
+        };
+        assert_eq!(fruit(7), "Peaches");
+        assert_eq!(fruit(10), "Apples");
+        assert_eq!(fruit(1), "Oranges");
+        // nothing is in season in April
+        assert_eq!(fruit(4), "peaches");
+    }
+}
@@ -0,0 +1,503 @@
+//! The kitchen: tickets for the stations and how long they took.
+//!
+//! Firing an order splits it into one [`Ticket`] per station that cooks
+//! some of its items. Each station works through its queue in order, and
+//! remakes jump to the front. Once the last ticket of an order is bumped,
+//! the order is ready to be served.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::serving::{OrderError, OrderId, OrderItem, OrderStatus, Orders, Transition};
+use crate::time::Time;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Station {
+    Grill,
+    Fryer,
+    Saute,
+    Cold,
+    Pastry,
+}
+
+impl Station {
+    pub const ALL: [Station; 5] = [
+        Station::Grill,
+        Station::Fryer,
+        Station::Saute,
+        Station::Cold,
+        Station::Pastry,
+    ];
+}
+
+impl fmt::Display for Station {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Station::Grill => "grill",
+            Station::Fryer => "fryer",
+            Station::Saute => "saute",
+            Station::Cold => "cold",
+            Station::Pastry => "pastry",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TicketId(pub u32);
+
+impl fmt::Display for TicketId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "T{}", self.0)
+    }
+}
+
+/// The items of one order that one station cooks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ticket {
+    pub id: TicketId,
+    pub order: OrderId,
+    pub table: u32,
+    pub station: Station,
+    pub items: Vec<OrderItem>,
+    pub fired: Time,
+    /// When the station finished it.
+    pub bumped: Option<Time>,
+    pub remake: bool,
+}
+
+impl Ticket {
+    /// Time from firing to bumping, `None` while it's still cooking.
+    pub fn ticket_time(&self) -> Option<Duration> {
+        self.bumped
+            .map(|bumped| bumped.saturating_since(self.fired))
+    }
+}
+
+/// Ticket times of the bumped tickets of a station.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TicketTimes {
+    pub tickets: usize,
+    pub average: Duration,
+    pub longest: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KitchenError {
+    Order(OrderError),
+    UnknownTicket(TicketId),
+    AlreadyBumped(TicketId),
+}
+
+impl fmt::Display for KitchenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KitchenError::Order(error) => error.fmt(f),
+            KitchenError::UnknownTicket(id) => write!(f, "there is no ticket {id}"),
+            KitchenError::AlreadyBumped(id) => write!(f, "ticket {id} was already bumped"),
+        }
+    }
+}
+
+impl std::error::Error for KitchenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            KitchenError::Order(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<OrderError> for KitchenError {
+    fn from(error: OrderError) -> Self {
+        KitchenError::Order(error)
+    }
+}
+
+/// Routes items to stations and keeps their queues.
+///
+/// # Examples
+///
+/// ```
+/// use modules_restaurant::kitchen::{Kitchen, Station};
+/// use modules_restaurant::serving::{OrderItem, OrderStatus, Orders};
+/// use modules_restaurant::time::Time;
+///
+/// let now = Time::from_ymd_hm(2024, 6, 1, 19, 0);
+/// let mut orders = Orders::new();
+/// let order = orders.take_order(2, vec![OrderItem::new("Steak", 1), OrderItem::new("Salad", 1)], now).unwrap();
+///
+/// let mut kitchen = Kitchen::new()
+///     .with_route("Steak", Station::Grill)
+///     .with_route("Salad", Station::Cold);
+/// let tickets = kitchen.fire(&mut orders, order, now).unwrap();
+/// assert_eq!(tickets.len(), 2);
+///
+/// let steak = kitchen.queue(Station::Grill)[0].id;
+/// let salad = kitchen.queue(Station::Cold)[0].id;
+/// assert_eq!(kitchen.bump(&mut orders, salad, now), Ok(OrderStatus::Cooking));
+/// assert_eq!(kitchen.bump(&mut orders, steak, now), Ok(OrderStatus::Ready));
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Kitchen {
+    routes: HashMap<String, Station>,
+    default_station: Station,
+    queues: BTreeMap<Station, VecDeque<TicketId>>,
+    // indexed by ticket id - 1
+    tickets: Vec<Ticket>,
+}
+
+impl Default for Kitchen {
+    fn default() -> Self {
+        Kitchen::new()
+    }
+}
+
+impl Kitchen {
+    /// A kitchen that cooks everything at the sauté station until told
+    /// otherwise.
+    pub fn new() -> Kitchen {
+        Kitchen {
+            routes: HashMap::new(),
+            default_station: Station::Saute,
+            queues: BTreeMap::new(),
+            tickets: Vec::new(),
+        }
+    }
+
+    /// Cook the item of that name, in any case, at `station`.
+    pub fn with_route(mut self, item: &str, station: Station) -> Kitchen {
+        self.routes.insert(item.to_lowercase(), station);
+        self
+    }
+
+    /// Where items without a route are cooked.
+    pub fn with_default_station(mut self, station: Station) -> Kitchen {
+        self.default_station = station;
+        self
+    }
+
+    pub fn station_for(&self, item: &str) -> Station {
+        self.routes
+            .get(&item.to_lowercase())
+            .copied()
+            .unwrap_or(self.default_station)
+    }
+
+    /// Start cooking a placed order, sending a ticket to every station
+    /// involved.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no such order or it isn't placed.
+    pub fn fire(
+        &mut self,
+        orders: &mut Orders,
+        id: OrderId,
+        now: Time,
+    ) -> Result<Vec<TicketId>, KitchenError> {
+        orders.apply(id, Transition::Cook, now)?;
+        Ok(self.send_tickets(orders, id, now, false))
+    }
+
+    /// Cook a ready or served order again. Its tickets go to the front of
+    /// the queues.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no such order or it isn't ready or served.
+    pub fn remake(
+        &mut self,
+        orders: &mut Orders,
+        id: OrderId,
+        reason: &str,
+        now: Time,
+    ) -> Result<Vec<TicketId>, KitchenError> {
+        let reason = String::from(reason);
+        orders.apply(id, Transition::Remake { reason }, now)?;
+        Ok(self.send_tickets(orders, id, now, true))
+    }
+
+    fn send_tickets(
+        &mut self,
+        orders: &Orders,
+        id: OrderId,
+        now: Time,
+        remake: bool,
+    ) -> Vec<TicketId> {
+        let order = orders.get(id).expect("the order was just updated");
+        let mut by_station: BTreeMap<Station, Vec<OrderItem>> = BTreeMap::new();
+        for item in order.items() {
+            let station = self.station_for(&item.name);
+            by_station.entry(station).or_default().push(item.clone());
+        }
+        let mut ids = Vec::with_capacity(by_station.len());
+        for (station, items) in by_station {
+            let ticket_id = TicketId(self.tickets.len() as u32 + 1);
+            self.tickets.push(Ticket {
+                id: ticket_id,
+                order: id,
+                table: order.table(),
+                station,
+                items,
+                fired: now,
+                bumped: None,
+                remake,
+            });
+            let queue = self.queues.entry(station).or_default();
+            if remake {
+                queue.push_front(ticket_id);
+            } else {
+                queue.push_back(ticket_id);
+            }
+            ids.push(ticket_id);
+        }
+        ids
+    }
+
+    /// The tickets a station still has to cook, next one first.
+    pub fn queue(&self, station: Station) -> Vec<&Ticket> {
+        self.queues
+            .get(&station)
+            .into_iter()
+            .flatten()
+            .map(|id| &self.tickets[id.0 as usize - 1])
+            .collect()
+    }
+
+    pub fn ticket(&self, id: TicketId) -> Option<&Ticket> {
+        let index = (id.0 as usize).checked_sub(1)?;
+        self.tickets.get(index)
+    }
+
+    /// Every ticket fired so far, bumped or not.
+    pub fn tickets(&self) -> &[Ticket] {
+        &self.tickets
+    }
+
+    /// The station is done with a ticket. When it was the order's last
+    /// open ticket, the order becomes ready. Returns the order's status.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no such ticket or it was already bumped.
+    pub fn bump(
+        &mut self,
+        orders: &mut Orders,
+        id: TicketId,
+        now: Time,
+    ) -> Result<OrderStatus, KitchenError> {
+        let index = (id.0 as usize)
+            .checked_sub(1)
+            .filter(|&index| index < self.tickets.len())
+            .ok_or(KitchenError::UnknownTicket(id))?;
+        let ticket = &mut self.tickets[index];
+        if ticket.bumped.is_some() {
+            return Err(KitchenError::AlreadyBumped(id));
+        }
+        ticket.bumped = Some(now);
+        let (order, station) = (ticket.order, ticket.station);
+        if let Some(queue) = self.queues.get_mut(&station) {
+            queue.retain(|&queued| queued != id);
+        }
+
+        let still_cooking = self
+            .tickets
+            .iter()
+            .any(|ticket| ticket.order == order && ticket.bumped.is_none());
+        if still_cooking {
+            let order = orders.get(order).ok_or(OrderError::UnknownOrder(order))?;
+            return Ok(order.status());
+        }
+        Ok(orders.apply(order, Transition::Finish, now)?)
+    }
+
+    /// Ticket times of a station's bumped tickets, `None` if it hasn't
+    /// bumped any.
+    pub fn ticket_times(&self, station: Station) -> Option<TicketTimes> {
+        let times: Vec<Duration> = self
+            .tickets
+            .iter()
+            .filter(|ticket| ticket.station == station)
+            .filter_map(Ticket::ticket_time)
+            .collect();
+        let longest = times.iter().max().copied()?;
+        let total: Duration = times.iter().sum();
+        Some(TicketTimes {
+            tickets: times.len(),
+            average: total / times.len() as u32,
+            longest,
+        })
+    }
+
+    /// [`ticket_times`](Kitchen::ticket_times) of every station that bumped
+    /// a ticket.
+    pub fn report(&self) -> Vec<(Station, TicketTimes)> {
+        Station::ALL
+            .into_iter()
+            .filter_map(|station| Some((station, self.ticket_times(station)?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::testing::{at, minutes};
+
+    fn kitchen() -> Kitchen {
+        Kitchen::new()
+            .with_route("Burger", Station::Grill)
+            .with_route("Steak", Station::Grill)
+            .with_route("Fries", Station::Fryer)
+            .with_route("Salad", Station::Cold)
+    }
+
+    fn order(orders: &mut Orders, table: u32, items: &[&str]) -> OrderId {
+        let items = items.iter().map(|name| OrderItem::new(name, 1)).collect();
+        orders.take_order(table, items, at(19, 0)).unwrap()
+    }
+
+    fn queued(kitchen: &Kitchen, station: Station) -> Vec<(OrderId, bool)> {
+        kitchen
+            .queue(station)
+            .iter()
+            .map(|ticket| (ticket.order, ticket.remake))
+            .collect()
+    }
+
+    #[test]
+    fn routes_items_to_stations() {
+        let mut orders = Orders::new();
+        let id = order(&mut orders, 5, &["Burger", "fries", "Steak", "Soup"]);
+        let mut kitchen = kitchen();
+        let tickets = kitchen.fire(&mut orders, id, at(19, 1)).unwrap();
+        assert_eq!(tickets, [TicketId(1), TicketId(2), TicketId(3)]);
+
+        let grill = kitchen.queue(Station::Grill);
+        let names: Vec<_> = grill[0]
+            .items
+            .iter()
+            .map(|item| item.name.as_str())
+            .collect();
+        assert_eq!(names, ["Burger", "Steak"]);
+        assert_eq!(grill[0].table, 5);
+        assert_eq!(kitchen.queue(Station::Fryer)[0].items[0].name, "fries");
+        // unrouted items go to the default station
+        assert_eq!(kitchen.queue(Station::Saute)[0].items[0].name, "Soup");
+        assert!(kitchen.queue(Station::Pastry).is_empty());
+        assert_eq!(orders.get(id).unwrap().status(), OrderStatus::Cooking);
+
+        let kitchen = Kitchen::new().with_default_station(Station::Pastry);
+        assert_eq!(kitchen.station_for("Tiramisu"), Station::Pastry);
+    }
+    #[test]
+    fn order_is_ready_after_its_last_ticket() {
+        let mut orders = Orders::new();
+        let id = order(&mut orders, 1, &["Burger", "Fries"]);
+        let mut kitchen = kitchen();
+        let tickets = kitchen.fire(&mut orders, id, at(19, 0)).unwrap();
+        assert_eq!(
+            kitchen.bump(&mut orders, tickets[1], at(19, 6)),
+            Ok(OrderStatus::Cooking)
+        );
+        assert_eq!(
+            kitchen.bump(&mut orders, tickets[0], at(19, 12)),
+            Ok(OrderStatus::Ready)
+        );
+        assert!(kitchen.queue(Station::Grill).is_empty());
+        assert_eq!(
+            kitchen.bump(&mut orders, tickets[0], at(19, 13)),
+            Err(KitchenError::AlreadyBumped(tickets[0]))
+        );
+        assert_eq!(
+            kitchen.bump(&mut orders, TicketId(9), at(19, 13)),
+            Err(KitchenError::UnknownTicket(TicketId(9)))
+        );
+        assert_eq!(orders.serve_order(id, at(19, 14)), Ok(OrderStatus::Served));
+    }
+    #[test]
+    fn only_placed_orders_can_be_fired() {
+        let mut orders = Orders::new();
+        let id = order(&mut orders, 1, &["Salad"]);
+        let mut kitchen = kitchen();
+        kitchen.fire(&mut orders, id, at(19, 0)).unwrap();
+        let error = kitchen.fire(&mut orders, id, at(19, 1)).unwrap_err();
+        assert_eq!(error.to_string(), "order #1 is cooking, it can't cook");
+        assert_eq!(kitchen.tickets().len(), 1);
+        assert!(matches!(
+            kitchen.remake(&mut orders, id, "wilted", at(19, 1)),
+            Err(KitchenError::Order(OrderError::InvalidTransition { .. }))
+        ));
+        assert_eq!(
+            kitchen.fire(&mut orders, OrderId(7), at(19, 1)),
+            Err(KitchenError::Order(OrderError::UnknownOrder(OrderId(7))))
+        );
+    }
+    #[test]
+    fn remakes_jump_the_queue() {
+        let mut orders = Orders::new();
+        let mut kitchen = kitchen();
+        let first = order(&mut orders, 1, &["Steak"]);
+        let second = order(&mut orders, 2, &["Steak"]);
+        let third = order(&mut orders, 3, &["Burger"]);
+        for id in [first, second, third] {
+            kitchen.fire(&mut orders, id, at(19, 0)).unwrap();
+        }
+        let ticket = kitchen.queue(Station::Grill)[0].id;
+        kitchen.bump(&mut orders, ticket, at(19, 10)).unwrap();
+        orders.serve_order(first, at(19, 11)).unwrap();
+
+        let remade = kitchen
+            .remake(&mut orders, first, "ordered rare", at(19, 12))
+            .unwrap();
+        assert_eq!(
+            queued(&kitchen, Station::Grill),
+            [(first, true), (second, false), (third, false)]
+        );
+        assert_eq!(orders.get(first).unwrap().status(), OrderStatus::Cooking);
+        assert_eq!(
+            kitchen.bump(&mut orders, remade[0], at(19, 20)),
+            Ok(OrderStatus::Ready)
+        );
+        assert_eq!(orders.get(first).unwrap().remakes(), 1);
+    }
+    #[test]
+    fn reports_ticket_times_per_station() {
+        let mut orders = Orders::new();
+        let mut kitchen = kitchen();
+        assert_eq!(kitchen.ticket_times(Station::Grill), None);
+        let a = order(&mut orders, 1, &["Burger", "Salad"]);
+        let b = order(&mut orders, 2, &["Steak"]);
+        let c = order(&mut orders, 3, &["Burger"]);
+        kitchen.fire(&mut orders, a, at(19, 0)).unwrap();
+        kitchen.fire(&mut orders, b, at(19, 2)).unwrap();
+        kitchen.fire(&mut orders, c, at(19, 4)).unwrap();
+        kitchen.bump(&mut orders, TicketId(1), at(19, 10)).unwrap(); // grill, 10 min
+        kitchen.bump(&mut orders, TicketId(2), at(19, 3)).unwrap(); // cold, 3 min
+        kitchen.bump(&mut orders, TicketId(3), at(19, 22)).unwrap(); // grill, 20 min
+
+        // the last burger is still cooking and doesn't count yet
+        assert_eq!(
+            kitchen.ticket_times(Station::Grill),
+            Some(TicketTimes {
+                tickets: 2,
+                average: minutes(15),
+                longest: minutes(20),
+            })
+        );
+        let stations: Vec<_> = kitchen
+            .report()
+            .into_iter()
+            .map(|(station, _)| station)
+            .collect();
+        assert_eq!(stations, [Station::Grill, Station::Cold]);
+        assert_eq!(kitchen.ticket(TicketId(4)).unwrap().ticket_time(), None);
+        assert_eq!(kitchen.ticket(TicketId(0)), None);
+    }
+}
@@ -0,0 +1,595 @@
+//! The menu the chef writes: categories, prices, seasons and allergens.
+//!
+//! A [`Menu`] is loaded from a TOML or JSON file with one entry per item:
+//!
+//! ```toml
+//! [[items]]
+//! name = "Peaches"
+//! category = "Seasonal fruit"
+//! price_cents = 300
+//! diets = ["vegan"]
+//! available = "summer"   # or { from = "06-15", until = "09-15" }
+//!
+//! [[items]]
+//! name = "Pancakes"
+//! category = "Breakfast"
+//! price_cents = 850
+//! allergens = ["gluten", "eggs", "milk"]
+//! diets = ["vegetarian"]
+//! station = "grill"
+//! ```
+//!
+//! Items without `available` are on the menu all year.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::kitchen::{Kitchen, Station};
+use crate::money::Cents;
+use crate::time::Time;
+
+/// The 14 allergens restaurants in the EU have to declare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Allergen {
+    Celery,
+    Gluten,
+    Crustaceans,
+    Eggs,
+    Fish,
+    Lupin,
+    Milk,
+    Molluscs,
+    Mustard,
+    Nuts,
+    Peanuts,
+    Sesame,
+    Soy,
+    Sulphites,
+}
+
+/// Diets an item is suitable for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Diet {
+    Vegetarian,
+    Vegan,
+}
+
+/// What a guest can't or won't eat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Restriction {
+    Vegetarian,
+    Vegan,
+    Avoid(Allergen),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    /// The (northern, meteorological) season of a day: summer is June to
+    /// August and so on.
+    pub fn of(day: Time) -> Season {
+        let (_, month, _) = day.date();
+        match month {
+            3..=5 => Season::Spring,
+            6..=8 => Season::Summer,
+            9..=11 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+
+    /// The days of the year of this season.
+    pub fn window(self) -> Window {
+        let (from, until) = match self {
+            Season::Spring => (MonthDay::new(3, 1), MonthDay::new(5, 31)),
+            Season::Summer => (MonthDay::new(6, 1), MonthDay::new(8, 31)),
+            Season::Autumn => (MonthDay::new(9, 1), MonthDay::new(11, 30)),
+            Season::Winter => (MonthDay::new(12, 1), MonthDay::new(2, 29)),
+        };
+        Window { from, until }
+    }
+}
+
+/// A day of the year, written `MM-DD`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct MonthDay {
+    month: u32,
+    day: u32,
+}
+
+impl MonthDay {
+    /// # Panics
+    ///
+    /// Panics if there is no such day in a leap year.
+    pub fn new(month: u32, day: u32) -> MonthDay {
+        MonthDay::checked(month, day)
+            .unwrap_or_else(|| panic!("there is no day {month:02}-{day:02}"))
+    }
+
+    fn checked(month: u32, day: u32) -> Option<MonthDay> {
+        let days = match month {
+            2 => 29,
+            4 | 6 | 9 | 11 => 30,
+            1..=12 => 31,
+            _ => return None,
+        };
+        (1..=days).contains(&day).then_some(MonthDay { month, day })
+    }
+
+    pub fn of(day: Time) -> MonthDay {
+        let (_, month, day) = day.date();
+        MonthDay { month, day }
+    }
+}
+
+impl fmt::Display for MonthDay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}-{:02}", self.month, self.day)
+    }
+}
+
+impl TryFrom<String> for MonthDay {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<MonthDay, String> {
+        let error = || format!("invalid day `{text}`, expected MM-DD");
+        let (month, day) = text.split_once('-').ok_or_else(error)?;
+        let month = month.parse().map_err(|_| error())?;
+        let day = day.parse().map_err(|_| error())?;
+        MonthDay::checked(month, day).ok_or_else(error)
+    }
+}
+
+impl From<MonthDay> for String {
+    fn from(day: MonthDay) -> String {
+        day.to_string()
+    }
+}
+
+/// The days of the year from `from` until `until`, both included. The
+/// window wraps around New Year if `until` comes first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Window {
+    pub from: MonthDay,
+    pub until: MonthDay,
+}
+
+impl Window {
+    pub fn contains(&self, day: MonthDay) -> bool {
+        if self.from <= self.until {
+            self.from <= day && day <= self.until
+        } else {
+            day >= self.from || day <= self.until
+        }
+    }
+}
+
+/// When an item is on the menu, as a season or as explicit days.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Availability {
+    Season(Season),
+    Window(Window),
+}
+
+impl Availability {
+    pub fn window(&self) -> Window {
+        match self {
+            Availability::Season(season) => season.window(),
+            Availability::Window(window) => *window,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MenuItem {
+    pub name: String,
+    pub category: String,
+    #[serde(rename = "price_cents")]
+    pub price: Cents,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(default)]
+    pub allergens: BTreeSet<Allergen>,
+    #[serde(default)]
+    pub diets: BTreeSet<Diet>,
+    /// Where the kitchen cooks it, if not at the default station.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub station: Option<Station>,
+    /// `None` for items that are served all year.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub available: Option<Availability>,
+}
+
+impl MenuItem {
+    pub fn is_available_on(&self, day: Time) -> bool {
+        self.available
+            .is_none_or(|available| available.window().contains(MonthDay::of(day)))
+    }
+
+    /// Whether a guest with all of these restrictions can eat it. Vegan
+    /// items count as vegetarian.
+    pub fn is_suitable_for(&self, restrictions: &[Restriction]) -> bool {
+        restrictions.iter().all(|restriction| match restriction {
+            Restriction::Vegan => self.diets.contains(&Diet::Vegan),
+            Restriction::Vegetarian => {
+                self.diets.contains(&Diet::Vegetarian) || self.diets.contains(&Diet::Vegan)
+            }
+            Restriction::Avoid(allergen) => !self.allergens.contains(allergen),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum MenuError {
+    Io(PathBuf, io::Error),
+    Toml(toml::de::Error),
+    Json(serde_json::Error),
+    /// The file is neither `.toml` nor `.json`.
+    UnknownFormat(PathBuf),
+    DuplicateItem(String),
+    /// The item has a negative price.
+    InvalidPrice(String),
+}
+
+impl fmt::Display for MenuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MenuError::Io(path, error) => write!(f, "can't read {}: {error}", path.display()),
+            MenuError::Toml(error) => write!(f, "invalid menu: {error}"),
+            MenuError::Json(error) => write!(f, "invalid menu: {error}"),
+            MenuError::UnknownFormat(path) => {
+                write!(f, "{} is neither a .toml nor a .json file", path.display())
+            }
+            MenuError::DuplicateItem(name) => write!(f, "{name} is on the menu twice"),
+            MenuError::InvalidPrice(name) => write!(f, "{name} can't have a negative price"),
+        }
+    }
+}
+
+impl std::error::Error for MenuError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MenuError::Io(_, error) => Some(error),
+            MenuError::Toml(error) => Some(error),
+            MenuError::Json(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// All items, in the order of the menu file.
+///
+/// # Examples
+///
+/// ```
+/// use modules_restaurant::menu::{Allergen, Menu, Restriction};
+/// use modules_restaurant::time::Time;
+///
+/// let menu = Menu::from_toml(r#"
+///     [[items]]
+///     name = "Pancakes"
+///     category = "Breakfast"
+///     price_cents = 850
+///     allergens = ["gluten", "eggs"]
+///
+///     [[items]]
+///     name = "Peaches"
+///     category = "Seasonal fruit"
+///     price_cents = 300
+///     available = "summer"
+/// "#).unwrap();
+///
+/// let july = Time::from_ymd_hm(2024, 7, 1, 9, 0);
+/// let january = Time::from_ymd_hm(2024, 1, 1, 9, 0);
+/// assert_eq!(menu.available_on(july).len(), 2);
+/// assert_eq!(menu.available_on(january).len(), 1);
+/// let gluten_free = menu.suitable_for(&[Restriction::Avoid(Allergen::Gluten)], july);
+/// assert_eq!(gluten_free[0].name, "Peaches");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Menu {
+    items: Vec<MenuItem>,
+}
+
+impl Menu {
+    /// # Errors
+    ///
+    /// Fails if two items have the same name or an item costs less than
+    /// nothing.
+    pub fn new(items: Vec<MenuItem>) -> Result<Menu, MenuError> {
+        if let Some(item) = items.iter().find(|item| item.price < Cents::ZERO) {
+            return Err(MenuError::InvalidPrice(item.name.clone()));
+        }
+        let mut names = BTreeSet::new();
+        if let Some(duplicate) = items
+            .iter()
+            .find(|item| !names.insert(item.name.to_lowercase()))
+        {
+            return Err(MenuError::DuplicateItem(duplicate.name.clone()));
+        }
+        Ok(Menu { items })
+    }
+
+    /// # Errors
+    ///
+    /// Fails if the text isn't a valid menu.
+    pub fn from_toml(text: &str) -> Result<Menu, MenuError> {
+        let menu: Menu = toml::from_str(text).map_err(MenuError::Toml)?;
+        Menu::new(menu.items)
+    }
+
+    /// # Errors
+    ///
+    /// Fails if the text isn't a valid menu.
+    pub fn from_json(text: &str) -> Result<Menu, MenuError> {
+        let menu: Menu = serde_json::from_str(text).map_err(MenuError::Json)?;
+        Menu::new(menu.items)
+    }
+
+    /// Read a `.toml` or `.json` menu file.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be read, has another extension or isn't a
+    /// valid menu.
+    pub fn load(path: impl AsRef<Path>) -> Result<Menu, MenuError> {
+        let path = path.as_ref();
+        let parse = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Menu::from_toml,
+            Some("json") => Menu::from_json,
+            _ => return Err(MenuError::UnknownFormat(path.to_path_buf())),
+        };
+        let text =
+            fs::read_to_string(path).map_err(|error| MenuError::Io(path.to_path_buf(), error))?;
+        parse(&text)
+    }
+
+    pub fn items(&self) -> &[MenuItem] {
+        &self.items
+    }
+
+    /// Find an item by name, in any case.
+    pub fn item(&self, name: &str) -> Option<&MenuItem> {
+        // the same folding as the duplicate check in `new`
+        let name = name.to_lowercase();
+        self.items
+            .iter()
+            .find(|item| item.name.to_lowercase() == name)
+    }
+
+    /// Category names in the order they first appear.
+    pub fn categories(&self) -> Vec<&str> {
+        let mut categories: Vec<&str> = Vec::new();
+        for item in &self.items {
+            if !categories.contains(&item.category.as_str()) {
+                categories.push(&item.category);
+            }
+        }
+        categories
+    }
+
+    pub fn category(&self, category: &str) -> Vec<&MenuItem> {
+        self.items
+            .iter()
+            .filter(|item| item.category.eq_ignore_ascii_case(category))
+            .collect()
+    }
+
+    pub fn available_on(&self, day: Time) -> Vec<&MenuItem> {
+        self.items
+            .iter()
+            .filter(|item| item.is_available_on(day))
+            .collect()
+    }
+
+    /// Items available that day that a guest with these restrictions can
+    /// eat.
+    pub fn suitable_for(&self, restrictions: &[Restriction], day: Time) -> Vec<&MenuItem> {
+        self.items
+            .iter()
+            .filter(|item| item.is_available_on(day) && item.is_suitable_for(restrictions))
+            .collect()
+    }
+
+    /// The first item of a category that is in season that day.
+    pub fn in_season(&self, category: &str, day: Time) -> Option<&MenuItem> {
+        self.category(category)
+            .into_iter()
+            .find(|item| item.is_available_on(day))
+    }
+
+    /// A kitchen that routes every item to its station.
+    pub fn kitchen(&self) -> Kitchen {
+        self.items
+            .iter()
+            .filter_map(|item| Some((&item.name, item.station?)))
+            .fold(Kitchen::new(), |kitchen, (name, station)| {
+                kitchen.with_route(name, station)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MENU: &str = include_str!("../../menu.toml");
+
+    fn day(month: u32, day: u32) -> Time {
+        Time::from_ymd_hm(2024, month, day, 12, 0)
+    }
+
+    fn names(items: Vec<&MenuItem>) -> Vec<&str> {
+        items.into_iter().map(|item| item.name.as_str()).collect()
+    }
+
+    #[test]
+    fn loads_the_sample_menu() {
+        let menu = Menu::from_toml(MENU).unwrap();
+        assert_eq!(
+            menu.categories(),
+            [
+                "Breakfast",
+                "Seasonal fruit",
+                "Appetizers",
+                "Mains",
+                "Sides",
+                "Desserts"
+            ]
+        );
+        let pancakes = menu.item("pancakes").unwrap();
+        assert_eq!(pancakes.price, Cents(850));
+        assert_eq!(pancakes.price.to_string(), "$8.50");
+        assert!(pancakes.allergens.contains(&Allergen::Gluten));
+        assert_eq!(pancakes.station, Some(Station::Grill));
+        assert_eq!(menu.kitchen().station_for("Fries"), Station::Fryer);
+    }
+    #[test]
+    fn names_match_in_any_case() {
+        let item = r#"
+            [[items]]
+            name = "Crème brûlée"
+            category = "Desserts"
+            price_cents = 900
+        "#;
+        let menu = Menu::from_toml(item).unwrap();
+        assert_eq!(
+            menu.item("CRÈME BRÛLÉE").map(|item| item.price),
+            Some(Cents(900))
+        );
+        let twice = format!("{item}{}", item.replace("Crème brûlée", "CRÈME BRÛLÉE"));
+        assert!(matches!(
+            Menu::from_toml(&twice),
+            Err(MenuError::DuplicateItem(_))
+        ));
+    }
+    #[test]
+    fn toml_and_json_menus_agree() {
+        let menu = Menu::from_toml(MENU).unwrap();
+        let json = serde_json::to_string(&menu).unwrap();
+        assert_eq!(Menu::from_json(&json).unwrap(), menu);
+    }
+    #[test]
+    fn seasonal_items_follow_the_calendar() {
+        let menu = Menu::from_toml(MENU).unwrap();
+        assert_eq!(Season::of(day(7, 14)), Season::Summer);
+        assert_eq!(Season::of(day(12, 24)), Season::Winter);
+        assert_eq!(
+            menu.in_season("seasonal fruit", day(7, 14)).unwrap().name,
+            "Peaches"
+        );
+        assert_eq!(
+            menu.in_season("seasonal fruit", day(10, 1)).unwrap().name,
+            "Apples"
+        );
+        assert_eq!(
+            menu.in_season("seasonal fruit", day(1, 15)).unwrap().name,
+            "Oranges"
+        );
+        // strawberries have an explicit window, from mid May
+        assert_eq!(
+            menu.in_season("seasonal fruit", day(5, 20)).unwrap().name,
+            "Strawberries"
+        );
+        assert_eq!(menu.in_season("seasonal fruit", day(4, 20)), None);
+        // the pumpkin soup is served from October until New Year
+        assert!(menu
+            .item("Pumpkin soup")
+            .unwrap()
+            .is_available_on(day(12, 31)));
+        assert!(!menu
+            .item("Pumpkin soup")
+            .unwrap()
+            .is_available_on(day(1, 1)));
+    }
+    #[test]
+    fn windows_can_wrap_around_new_year() {
+        let winter = Season::Winter.window();
+        assert!(winter.contains(MonthDay::new(12, 1)));
+        assert!(winter.contains(MonthDay::new(2, 29)));
+        assert!(!winter.contains(MonthDay::new(3, 1)));
+        assert!(!winter.contains(MonthDay::new(11, 30)));
+    }
+    #[test]
+    fn filters_by_dietary_restrictions() {
+        let menu = Menu::from_toml(MENU).unwrap();
+        let summer = day(7, 1);
+        assert_eq!(
+            names(menu.suitable_for(&[Restriction::Vegan], summer)),
+            ["Peaches", "Green salad", "Fries"]
+        );
+        let vegetarian = names(menu.suitable_for(&[Restriction::Vegetarian], summer));
+        assert!(vegetarian.contains(&"Pancakes") && vegetarian.contains(&"Fries"));
+        assert!(!vegetarian.contains(&"Burger"));
+        let no_gluten_or_milk = [
+            Restriction::Avoid(Allergen::Gluten),
+            Restriction::Avoid(Allergen::Milk),
+        ];
+        assert_eq!(
+            names(menu.suitable_for(&no_gluten_or_milk, summer)),
+            ["Peaches", "Green salad", "Steak", "Fries"]
+        );
+    }
+    #[test]
+    fn rejects_invalid_menus() {
+        let duplicate = r#"
+            [[items]]
+            name = "Soup"
+            category = "Appetizers"
+            price_cents = 500
+            [[items]]
+            name = "soup"
+            category = "Mains"
+            price_cents = 900
+        "#;
+        assert!(
+            matches!(Menu::from_toml(duplicate), Err(MenuError::DuplicateItem(name)) if name == "soup")
+        );
+        let bad_day = r#"
+            [[items]]
+            name = "Soup"
+            category = "Appetizers"
+            price_cents = 500
+            available = { from = "02-30", until = "03-10" }
+        "#;
+        assert!(matches!(Menu::from_toml(bad_day), Err(MenuError::Toml(_))));
+        let float_price =
+            r#"{"items": [{"name": "Soup", "category": "Appetizers", "price_cents": 5.5}]}"#;
+        assert!(matches!(
+            Menu::from_json(float_price),
+            Err(MenuError::Json(_))
+        ));
+        let negative_price = r#"
+            [[items]]
+            name = "Steak"
+            category = "Mains"
+            price_cents = -2000
+        "#;
+        assert!(
+            matches!(Menu::from_toml(negative_price), Err(MenuError::InvalidPrice(name)) if name == "Steak")
+        );
+        assert!(matches!(
+            Menu::load("menu.yaml"),
+            Err(MenuError::UnknownFormat(_))
+        ));
+        assert!(matches!(
+            Menu::load("no/such/menu.toml"),
+            Err(MenuError::Io(..))
+        ));
+    }
+}
@@ -0,0 +1,582 @@
+//! `restaurant` runs a day of service from the command line.
+//!
+//! Every call loads the restaurant from a JSON state file, runs one
+//! command and saves it again, so a shift can be played step by step:
+//!
+//! ```text
+//! restaurant waitlist add Ana 2
+//! restaurant seat '#1'
+//! restaurant order 1 "2x Burger" Fries
+//! restaurant kitchen queue
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use serde::{Deserialize, Serialize};
+
+use modules_restaurant::billing::{Bill, TaxRules, Tender, Tip};
+use modules_restaurant::hosting::{FloorPlan, Host, PartyId, TableStatus};
+use modules_restaurant::kitchen::{Kitchen, Station, TicketId};
+use modules_restaurant::menu::Menu;
+use modules_restaurant::money::{Cents, Rate};
+use modules_restaurant::serving::{OrderId, OrderItem, OrderStatus, Orders};
+use modules_restaurant::time::Time;
+
+const USAGE: &str = "\
+Usage: restaurant [--state FILE] [--menu FILE] [--at 'YYYY-MM-DD HH:MM'] COMMAND
+
+Commands:
+  waitlist add NAME SIZE   put a party on the waitlist
+  waitlist list            show waiting and called parties
+  seat PARTY               seat a called party, like `seat #1`
+  order TABLE ITEM...      take an order and fire it, like `order 3 \"2x Burger\" Fries`
+  kitchen queue [STATION]  show the tickets of every station or one
+  kitchen bump TICKET      a station is done with a ticket, like `kitchen bump T1`
+  serve ORDER              bring a ready order to its table
+  leave TABLE              a party leaves without anything to pay
+  bill TABLE [--tip 18%|5.00] [--pay AMOUNT [--card LAST4|--gift CODE]]
+                           show the bill of a table or pay (part of) it
+  close-day                sum up the day and start a new one
+  help                     show this message
+
+The state is kept in `restaurant.json` unless --state says otherwise.";
+
+const MENU: &str = include_str!("../../menu.toml");
+
+const TAX: Rate = Rate(825);
+
+enum Command {
+    Help,
+    WaitlistAdd {
+        name: String,
+        size: u32,
+    },
+    WaitlistList,
+    Seat(PartyId),
+    Order {
+        table: u32,
+        items: Vec<OrderItem>,
+    },
+    KitchenQueue(Option<Station>),
+    KitchenBump(TicketId),
+    Serve(OrderId),
+    Leave(u32),
+    Bill {
+        table: u32,
+        tip: Option<Tip>,
+        payment: Option<(Tender, Cents)>,
+    },
+    CloseDay,
+}
+
+struct Config {
+    state: PathBuf,
+    menu: Option<PathBuf>,
+    now: Time,
+    command: Command,
+}
+
+impl Config {
+    /// Build a config from the command line, the first argument being the
+    /// program's name.
+    fn build(mut args: impl Iterator<Item = String>) -> Result<Config, String> {
+        args.next();
+        let mut state = PathBuf::from("restaurant.json");
+        let mut menu = None;
+        let mut now = None;
+        let mut args = args.peekable();
+        while let Some(option) = args.next_if(|arg| arg.starts_with("--")) {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("{option} needs a value"))?;
+            match option.as_str() {
+                "--state" => state = PathBuf::from(value),
+                "--menu" => menu = Some(PathBuf::from(value)),
+                "--at" => now = Some(value.parse().map_err(|e| format!("{e}"))?),
+                _ => return Err(format!("unknown option {option}")),
+            }
+        }
+        let command = Command::parse(args.collect())?;
+        Ok(Config {
+            state,
+            menu,
+            now: now.unwrap_or_else(Time::now),
+            command,
+        })
+    }
+}
+
+impl Command {
+    fn parse(args: Vec<String>) -> Result<Command, String> {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let command = match args[..] {
+            [] => return Err(String::from("missing command")),
+            ["help"] => Command::Help,
+            ["waitlist", "add", name, size] => Command::WaitlistAdd {
+                name: String::from(name),
+                size: number(size, "party size")?,
+            },
+            ["waitlist", "list"] => Command::WaitlistList,
+            ["seat", party] => Command::Seat(PartyId(id(party, "#", "party")?)),
+            ["order", table, ref items @ ..] if !items.is_empty() => Command::Order {
+                table: number(table, "table")?,
+                items: items
+                    .iter()
+                    .map(|item| order_item(item))
+                    .collect::<Result<_, _>>()?,
+            },
+            ["kitchen", "queue"] => Command::KitchenQueue(None),
+            ["kitchen", "queue", station] => Command::KitchenQueue(Some(
+                Station::ALL
+                    .into_iter()
+                    .find(|s| s.to_string().eq_ignore_ascii_case(station))
+                    .ok_or_else(|| format!("there is no station `{station}`"))?,
+            )),
+            ["kitchen", "bump", ticket] => {
+                Command::KitchenBump(TicketId(id(ticket, "T", "ticket")?))
+            }
+            ["serve", order] => Command::Serve(OrderId(id(order, "#", "order")?)),
+            ["leave", table] => Command::Leave(number(table, "table")?),
+            ["bill", table, ref options @ ..] => parse_bill(number(table, "table")?, options)?,
+            ["close-day"] => Command::CloseDay,
+            _ => return Err(format!("unknown command `{}`", args.join(" "))),
+        };
+        Ok(command)
+    }
+}
+
+fn number(arg: &str, what: &str) -> Result<u32, String> {
+    arg.parse().map_err(|_| format!("invalid {what} `{arg}`"))
+}
+
+// `#3`, `T3` or just `3`
+fn id(arg: &str, prefix: &str, what: &str) -> Result<u32, String> {
+    let digits = match arg.get(..prefix.len()) {
+        Some(start) if start.eq_ignore_ascii_case(prefix) => &arg[prefix.len()..],
+        _ => arg,
+    };
+    number(digits, what)
+}
+
+// `2x Burger` or `Burger`
+fn order_item(arg: &str) -> Result<OrderItem, String> {
+    let quantity = arg
+        .split_once("x ")
+        .and_then(|(quantity, name)| Some((quantity.trim().parse().ok()?, name.trim())));
+    match quantity {
+        Some((0, _)) => Err(format!("invalid item `{arg}`, order at least one")),
+        Some((quantity, name)) => Ok(OrderItem::new(name, quantity)),
+        None => Ok(OrderItem::new(arg.trim(), 1)),
+    }
+}
+
+fn parse_bill(table: u32, options: &[&str]) -> Result<Command, String> {
+    let mut tip = None;
+    let mut amount = None;
+    let mut tender = Tender::Cash;
+    for pair in options.chunks(2) {
+        let [option, value] = *pair else {
+            return Err(format!("{} needs a value", pair[0]));
+        };
+        let invalid = |e: &dyn Error| format!("invalid {option} `{value}`: {e}");
+        match option {
+            "--tip" if value.ends_with('%') => {
+                tip = Some(Tip::Percent(value.parse().map_err(|e| invalid(&e))?))
+            }
+            "--tip" => tip = Some(Tip::Amount(value.parse().map_err(|e| invalid(&e))?)),
+            "--pay" => amount = Some(value.parse().map_err(|e| invalid(&e))?),
+            "--card" => {
+                tender = Tender::Card {
+                    last_four: String::from(value),
+                }
+            }
+            "--gift" => {
+                tender = Tender::GiftCard {
+                    code: String::from(value),
+                }
+            }
+            _ => return Err(format!("unknown option {option}")),
+        }
+    }
+    if amount.is_none() && tender != Tender::Cash {
+        return Err(String::from("--card and --gift need --pay"));
+    }
+    Ok(Command::Bill {
+        table,
+        tip,
+        payment: amount.map(|amount| (tender, amount)),
+    })
+}
+
+/// Everything that has to survive between two calls.
+#[derive(Serialize, Deserialize)]
+struct State {
+    host: Host,
+    orders: Orders,
+    kitchen: Kitchen,
+    /// Bills that are partly paid, by table. A table with one can't order
+    /// any more.
+    bills: BTreeMap<u32, Bill>,
+    /// Tips of tables that haven't paid yet, added to the bill when it is
+    /// made.
+    #[serde(default)]
+    tips: BTreeMap<u32, Tip>,
+    /// Orders on a bill that was paid.
+    billed: BTreeSet<OrderId>,
+    settled: Vec<Bill>,
+    seated: u32,
+}
+
+impl State {
+    /// A new day, with two tables for two, two for four and one for six
+    /// unless there is a floor plan from an earlier day.
+    fn new(floor: Option<&FloorPlan>, menu: &Menu) -> State {
+        let mut tables = FloorPlan::new();
+        match floor {
+            Some(floor) => {
+                for table in floor.tables() {
+                    tables.add_table(table.number(), table.capacity());
+                }
+            }
+            None => {
+                for (number, capacity) in [(1, 2), (2, 2), (3, 4), (4, 4), (5, 6)] {
+                    tables.add_table(number, capacity);
+                }
+            }
+        }
+        State {
+            host: Host::new(tables),
+            orders: Orders::new(),
+            kitchen: menu.kitchen(),
+            bills: BTreeMap::new(),
+            tips: BTreeMap::new(),
+            billed: BTreeSet::new(),
+            settled: Vec::new(),
+            seated: 0,
+        }
+    }
+
+    fn load(path: &Path, menu: &Menu) -> io::Result<State> {
+        match fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(State::new(None, menu)),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let partial = path.with_extension("partial");
+        fs::write(&partial, json)?;
+        fs::rename(&partial, path)
+    }
+
+    fn execute(&mut self, command: Command, menu: &Menu, now: Time) -> Result<(), Box<dyn Error>> {
+        for party in self.host.expire_no_shows(now) {
+            println!(
+                "{} ({}) didn't show up, their table is given away",
+                party.name, party.id
+            );
+        }
+        match command {
+            Command::Help => println!("{USAGE}"),
+            Command::WaitlistAdd { name, size } => {
+                let id = self.host.add_to_waitlist(&name, size, now)?;
+                let wait = self.host.estimated_wait(id, now).unwrap_or_default();
+                println!(
+                    "{name}, party of {size}, is {id} on the waitlist, about {} min",
+                    wait.as_secs() / 60
+                );
+            }
+            Command::WaitlistList => self.list_waitlist(now),
+            Command::Seat(id) => {
+                self.call_parties(now);
+                let table = self.host.seat(id, now)?;
+                self.seated += 1;
+                println!("Seated {id} at table {table}");
+            }
+            Command::Order { table, items } => self.take_order(table, items, menu, now)?,
+            Command::KitchenQueue(station) => self.show_queue(station, now),
+            Command::KitchenBump(ticket) => {
+                let status = self.kitchen.bump(&mut self.orders, ticket, now)?;
+                let order = self.kitchen.ticket(ticket).map(|ticket| ticket.order);
+                let order = order.expect("bumped tickets exist");
+                println!("Bumped {ticket}, order {order} is {status}");
+            }
+            Command::Serve(order) => {
+                self.orders.serve_order(order, now)?;
+                let table = self.orders.get(order).map(|order| order.table());
+                println!(
+                    "Served order {order} at table {}",
+                    table.expect("served orders exist")
+                );
+            }
+            Command::Leave(table) => self.leave(table)?,
+            Command::Bill {
+                table,
+                tip,
+                payment,
+            } => self.bill(table, tip, payment, menu)?,
+            Command::CloseDay => self.close_day(menu, now)?,
+        }
+        self.call_parties(now);
+        Ok(())
+    }
+
+    fn call_parties(&mut self, now: Time) {
+        for call in self.host.call_parties(now) {
+            let party = call.party;
+            println!(
+                "Calling {} ({}) to table {}",
+                party.name, party.id, call.table
+            );
+        }
+    }
+
+    fn list_waitlist(&self, now: Time) {
+        let waitlist = self.host.waitlist();
+        if waitlist.is_empty() && self.host.called().is_empty() {
+            println!("Nobody is waiting");
+        }
+        for (position, party) in waitlist.iter().enumerate() {
+            let waited = now.saturating_since(party.arrived).as_secs() / 60;
+            let wait = self.host.estimated_wait(party.id, now).unwrap_or_default();
+            println!(
+                "{}. {} {}, {} guests, waiting {waited} min, about {} min to go",
+                position + 1,
+                party.id,
+                party.name,
+                party.size,
+                wait.as_secs() / 60,
+            );
+        }
+        for call in self.host.called() {
+            let (hour, minute) = call.expires.time_of_day();
+            println!(
+                "Called: {} {} to table {}, held until {hour:02}:{minute:02}",
+                call.party.id, call.party.name, call.table
+            );
+        }
+    }
+
+    fn take_order(
+        &mut self,
+        table: u32,
+        items: Vec<OrderItem>,
+        menu: &Menu,
+        now: Time,
+    ) -> Result<(), Box<dyn Error>> {
+        match self
+            .host
+            .floor_plan()
+            .table(table)
+            .map(|table| table.status())
+        {
+            None => return Err(format!("there is no table {table}").into()),
+            Some(TableStatus::Occupied { .. }) => {}
+            Some(_) => return Err(format!("nobody sits at table {table}").into()),
+        }
+        if self.bills.contains_key(&table) {
+            return Err(format!("table {table} is paying").into());
+        }
+        let mut ordered = Vec::new();
+        for item in items {
+            let on_menu = menu
+                .item(&item.name)
+                .ok_or_else(|| format!("`{}` isn't on the menu", item.name))?;
+            if !on_menu.is_available_on(now) {
+                return Err(format!("{} is out of season", on_menu.name).into());
+            }
+            ordered.push(OrderItem::new(&on_menu.name, item.quantity));
+        }
+        let order = self.orders.take_order(table, ordered, now)?;
+        let tickets = self.kitchen.fire(&mut self.orders, order, now)?;
+        let items = self
+            .orders
+            .get(order)
+            .expect("the order was just taken")
+            .items();
+        let items: Vec<String> = items.iter().map(OrderItem::to_string).collect();
+        println!("Order {order} for table {table}: {}", items.join(", "));
+        let tickets: Vec<String> = tickets
+            .iter()
+            .filter_map(|&id| self.kitchen.ticket(id))
+            .map(|ticket| format!("{} ({})", ticket.id, ticket.station))
+            .collect();
+        println!("Fired {}", tickets.join(", "));
+        Ok(())
+    }
+
+    fn show_queue(&self, station: Option<Station>, now: Time) {
+        let stations = match station {
+            Some(station) => vec![station],
+            None => Station::ALL.to_vec(),
+        };
+        let mut empty = true;
+        for station in stations {
+            let queue = self.kitchen.queue(station);
+            if queue.is_empty() {
+                continue;
+            }
+            empty = false;
+            println!("{station}:");
+            for ticket in queue {
+                let items: Vec<String> = ticket.items.iter().map(OrderItem::to_string).collect();
+                let remake = if ticket.remake { " REMAKE" } else { "" };
+                println!(
+                    "  {} table {} order {}: {}, {} min{remake}",
+                    ticket.id,
+                    ticket.table,
+                    ticket.order,
+                    items.join(", "),
+                    now.saturating_since(ticket.fired).as_secs() / 60,
+                );
+            }
+        }
+        if empty {
+            println!("No tickets");
+        }
+    }
+
+    fn bill(
+        &mut self,
+        table: u32,
+        tip: Option<Tip>,
+        payment: Option<(Tender, Cents)>,
+        menu: &Menu,
+    ) -> Result<(), Box<dyn Error>> {
+        let unbilled: Vec<_> = self
+            .orders
+            .for_table(table)
+            .filter(|order| !self.billed.contains(&order.id()))
+            .collect();
+        let mut bill = match self.bills.get(&table) {
+            Some(bill) => bill.clone(),
+            None if unbilled.is_empty() => {
+                return Err(format!("table {table} has nothing to pay").into())
+            }
+            None => Bill::for_orders(table, unbilled.iter().copied(), menu, TaxRules::new(TAX))?,
+        };
+        if let Some(tip) = tip {
+            self.tips.insert(table, tip);
+        }
+        if let Some(&tip) = self.tips.get(&table) {
+            bill.set_tip(tip);
+        }
+        if let Some((tender, amount)) = payment {
+            let open = unbilled
+                .iter()
+                .find(|order| order.status() != OrderStatus::Served);
+            if let Some(order) = open {
+                return Err(format!("order {} is still {}", order.id(), order.status()).into());
+            }
+            bill.pay(tender, amount)?;
+        }
+        print!("{}", bill.receipt());
+
+        if bill.is_settled() {
+            let ids: Vec<OrderId> = unbilled.iter().map(|order| order.id()).collect();
+            self.billed.extend(ids);
+            self.bills.remove(&table);
+            self.tips.remove(&table);
+            self.settled.push(bill);
+            self.host.clear_table(table)?;
+            println!("Table {table} is free");
+        } else if !bill.payments().is_empty() {
+            // keep the payments for the rest of the bill
+            self.bills.insert(table, bill);
+        }
+        Ok(())
+    }
+
+    /// The party at `table` leaves without ordering, or after paying for
+    /// everything. Tables that still owe something are freed by `bill`.
+    fn leave(&mut self, table: u32) -> Result<(), Box<dyn Error>> {
+        if let Some(order) = self
+            .orders
+            .for_table(table)
+            .find(|order| !self.billed.contains(&order.id()))
+        {
+            return Err(format!("table {table} hasn't paid for order {}", order.id()).into());
+        }
+        self.host.clear_table(table)?;
+        self.tips.remove(&table);
+        println!("Table {table} is free");
+        Ok(())
+    }
+
+    fn close_day(&mut self, menu: &Menu, now: Time) -> Result<(), Box<dyn Error>> {
+        if let Some(order) = self
+            .orders
+            .iter()
+            .find(|order| !self.billed.contains(&order.id()))
+        {
+            let table = order.table();
+            return Err(format!("table {table} hasn't paid for order {}", order.id()).into());
+        }
+        let (year, month, day) = now.date();
+        println!("Closed {year}-{month:02}-{day:02}");
+        let sales: Cents = self
+            .settled
+            .iter()
+            .map(|bill| bill.subtotal() - bill.discount_total())
+            .sum();
+        let tax: Cents = self.settled.iter().map(Bill::tax).sum();
+        let tips: Cents = self.settled.iter().map(Bill::tip).sum();
+        println!("{:<16}{:>12}", "Parties seated", self.seated);
+        println!("{:<16}{:>12}", "Orders", self.orders.len());
+        println!("{:<16}{:>12}", "Sales", sales);
+        println!("{:<16}{:>12}", "Tax", tax);
+        println!("{:<16}{:>12}", "Tips", tips);
+        for (station, times) in self.kitchen.report() {
+            println!(
+                "{station}: {} ticket{}, average {} min, longest {} min",
+                times.tickets,
+                if times.tickets == 1 { "" } else { "s" },
+                times.average.as_secs() / 60,
+                times.longest.as_secs() / 60,
+            );
+        }
+        let waiting = self.host.waitlist().len() + self.host.called().len();
+        if waiting > 0 {
+            println!("Sent {waiting} waiting parties home");
+        }
+        *self = State::new(Some(self.host.floor_plan()), menu);
+        Ok(())
+    }
+}
+
+fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    let menu = match &config.menu {
+        Some(path) => Menu::load(path)?,
+        None => Menu::from_toml(MENU)?,
+    };
+    if let Command::Help = config.command {
+        println!("{USAGE}");
+        return Ok(());
+    }
+    let mut state = State::load(&config.state, &menu)?;
+    state.execute(config.command, &menu, config.now)?;
+    state.save(&config.state)?;
+    Ok(())
+}
+
+// Execute `cargo run --bin restaurant -- help` to see the commands
+fn main() {
+    let config = Config::build(env::args()).unwrap_or_else(|err| {
+        eprintln!("Problem parsing arguments: {err}");
+        eprintln!("{USAGE}");
+        process::exit(1);
+    });
+
+    if let Err(e) = run(config) {
+        eprintln!("Application error: {e}");
+        process::exit(1);
+    }
+}
@@ -1,3 +1,7 @@
 pub mod hosting;
 
-mod serving;
+pub mod serving;
+
+pub mod billing;
+
+pub mod reservations;
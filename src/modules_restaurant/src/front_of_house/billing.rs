@@ -0,0 +1,873 @@
+//! Bills, discounts, splitting the check and taking payment.
+//!
+//! All amounts are [`Cents`]. Whenever an amount has to be divided, like a
+//! discount over several items or a check between guests, it goes through
+//! [`allocate`] so the parts always add up to the cent.
+//!
+//! A bill is worked out in this order:
+//!
+//! 1. the subtotal of all items at menu prices,
+//! 2. discounts, in the order they were applied, each taken off what is
+//!    left of the items it applies to,
+//! 3. tax on what remains, rounded once per tax rate,
+//! 4. the tip, as an amount or a share of the subtotal before discounts.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write as _;
+
+use serde::{Deserialize, Serialize};
+
+use crate::menu::Menu;
+use crate::money::{allocate, Cents, Rate};
+use crate::serving::Order;
+
+/// Tax rates, by menu category.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaxRules {
+    default_rate: Rate,
+    category_rates: HashMap<String, Rate>,
+}
+
+impl TaxRules {
+    pub fn new(default_rate: Rate) -> TaxRules {
+        TaxRules {
+            default_rate,
+            category_rates: HashMap::new(),
+        }
+    }
+
+    /// Tax items of `category` at `rate` instead of the default rate.
+    pub fn with_category_rate(mut self, category: &str, rate: Rate) -> TaxRules {
+        self.category_rates.insert(category.to_lowercase(), rate);
+        self
+    }
+
+    pub fn rate_for(&self, category: &str) -> Rate {
+        self.category_rates
+            .get(&category.to_lowercase())
+            .copied()
+            .unwrap_or(self.default_rate)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiscountKind {
+    Percent(Rate),
+    /// At most the price of the items it applies to.
+    Amount(Cents),
+}
+
+/// A discount, for the whole bill or the items of one category.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Discount {
+    pub name: String,
+    pub kind: DiscountKind,
+    pub category: Option<String>,
+}
+
+impl Discount {
+    pub fn percent(name: &str, rate: Rate) -> Discount {
+        Discount {
+            name: String::from(name),
+            kind: DiscountKind::Percent(rate),
+            category: None,
+        }
+    }
+
+    pub fn amount(name: &str, amount: Cents) -> Discount {
+        Discount {
+            name: String::from(name),
+            kind: DiscountKind::Amount(amount),
+            category: None,
+        }
+    }
+
+    /// Only take the discount off items of this category.
+    pub fn on_category(mut self, category: &str) -> Discount {
+        self.category = Some(String::from(category));
+        self
+    }
+
+    fn applies_to(&self, line: &BillLine) -> bool {
+        self.category
+            .as_ref()
+            .is_none_or(|category| line.category.eq_ignore_ascii_case(category))
+    }
+}
+
+/// Discounts guests can redeem with a code.
+#[derive(Debug, Clone, Default)]
+pub struct Coupons {
+    codes: HashMap<String, Discount>,
+}
+
+impl Coupons {
+    pub fn new() -> Coupons {
+        Coupons::default()
+    }
+
+    pub fn with_coupon(mut self, code: &str, discount: Discount) -> Coupons {
+        self.codes.insert(code.to_uppercase(), discount);
+        self
+    }
+
+    /// The discount for a code, in any case.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no such coupon.
+    pub fn redeem(&self, code: &str) -> Result<Discount, BillingError> {
+        self.codes
+            .get(&code.to_uppercase())
+            .cloned()
+            .ok_or_else(|| BillingError::UnknownCoupon(String::from(code)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Tip {
+    Amount(Cents),
+    /// Of the subtotal before discounts, so discounts don't cut the tip.
+    Percent(Rate),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Tender {
+    Cash,
+    Card { last_four: String },
+    GiftCard { code: String },
+}
+
+impl fmt::Display for Tender {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tender::Cash => f.write_str("Cash"),
+            Tender::Card { last_four } => write!(f, "Card ****{last_four}"),
+            Tender::GiftCard { code } => write!(f, "Gift card {code}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Payment {
+    pub tender: Tender,
+    /// What was applied to the bill, without change.
+    pub amount: Cents,
+    pub change: Cents,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BillingError {
+    UnknownItem(String),
+    UnknownCoupon(String),
+    DuplicateDiscount(String),
+    /// None of the items on the bill qualify for the discount.
+    NotEligible(String),
+    InvalidSplit(String),
+    InvalidAmount(Cents),
+    /// A line can't cost less than nothing, that's what discounts are for.
+    NegativePrice(String),
+    /// A line of nothing, the item was ordered zero times.
+    ZeroQuantity(String),
+    /// Only cash can be more than the balance, the rest is change.
+    Overpayment {
+        balance: Cents,
+    },
+    AlreadySettled,
+}
+
+impl fmt::Display for BillingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BillingError::UnknownItem(name) => write!(f, "{name} is not on the menu"),
+            BillingError::UnknownCoupon(code) => write!(f, "there is no coupon {code}"),
+            BillingError::DuplicateDiscount(name) => write!(f, "{name} was already applied"),
+            BillingError::NotEligible(name) => {
+                write!(f, "no item on the bill qualifies for {name}")
+            }
+            BillingError::InvalidSplit(reason) => write!(f, "can't split the bill: {reason}"),
+            BillingError::InvalidAmount(amount) => write!(f, "can't pay {amount}"),
+            BillingError::NegativePrice(name) => write!(f, "{name} can't have a negative price"),
+            BillingError::ZeroQuantity(name) => write!(f, "can't bill zero of {name}"),
+            BillingError::Overpayment { balance } => {
+                write!(f, "only {balance} is left to pay")
+            }
+            BillingError::AlreadySettled => f.write_str("the bill is already paid"),
+        }
+    }
+}
+
+impl std::error::Error for BillingError {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BillLine {
+    pub name: String,
+    pub category: String,
+    pub quantity: u32,
+    pub unit_price: Cents,
+}
+
+impl BillLine {
+    pub fn gross(&self) -> Cents {
+        self.unit_price * self.quantity
+    }
+}
+
+/// How much of the total falls on one line, see [`Bill::breakdown`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineTotals {
+    pub gross: Cents,
+    pub discount: Cents,
+    pub tax: Cents,
+    pub tip: Cents,
+}
+
+impl LineTotals {
+    pub fn net(&self) -> Cents {
+        self.gross - self.discount
+    }
+
+    pub fn total(&self) -> Cents {
+        self.net() + self.tax + self.tip
+    }
+}
+
+/// The check of a table.
+///
+/// # Examples
+///
+/// ```
+/// use modules_restaurant::billing::{Bill, Discount, TaxRules, Tender, Tip};
+/// use modules_restaurant::money::{Cents, Rate};
+///
+/// let mut bill = Bill::new(4, TaxRules::new(Rate(825)));
+/// bill.add_line("Burger", "Mains", 2, Cents(1450)).unwrap();
+/// bill.add_line("Fries", "Sides", 1, Cents(450)).unwrap();
+/// bill.apply_discount(Discount::percent("Happy hour", Rate::percent(10))).unwrap();
+/// bill.set_tip(Tip::Percent(Rate::percent(18)));
+///
+/// assert_eq!(bill.subtotal(), Cents(3350));
+/// assert_eq!(bill.discount_total(), Cents(335));
+/// assert_eq!(bill.tax(), Cents(249)); // 8.25% of $30.15
+/// assert_eq!(bill.tip(), Cents(603));
+/// assert_eq!(bill.total(), Cents(3867));
+///
+/// // two guests pay half each, one cent more for the first
+/// assert_eq!(bill.split_evenly(2).unwrap(), [Cents(1934), Cents(1933)]);
+/// bill.pay(Tender::Cash, Cents(4000)).unwrap();
+/// assert!(bill.is_settled());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bill {
+    table: u32,
+    lines: Vec<BillLine>,
+    tax_rules: TaxRules,
+    discounts: Vec<Discount>,
+    tip: Option<Tip>,
+    payments: Vec<Payment>,
+}
+
+impl Bill {
+    pub fn new(table: u32, tax_rules: TaxRules) -> Bill {
+        Bill {
+            table,
+            lines: Vec::new(),
+            tax_rules,
+            discounts: Vec::new(),
+            tip: None,
+            payments: Vec::new(),
+        }
+    }
+
+    /// A bill with every item of the orders at its menu price. Remade
+    /// dishes are only charged once.
+    ///
+    /// # Errors
+    ///
+    /// Fails if an item isn't on the menu.
+    pub fn for_orders<'a>(
+        table: u32,
+        orders: impl IntoIterator<Item = &'a Order>,
+        menu: &Menu,
+        tax_rules: TaxRules,
+    ) -> Result<Bill, BillingError> {
+        let mut bill = Bill::new(table, tax_rules);
+        for item in orders.into_iter().flat_map(Order::items) {
+            let on_menu = menu
+                .item(&item.name)
+                .ok_or_else(|| BillingError::UnknownItem(item.name.clone()))?;
+            bill.add_line(
+                &on_menu.name,
+                &on_menu.category,
+                item.quantity,
+                on_menu.price,
+            )?;
+        }
+        Ok(bill)
+    }
+
+    /// # Errors
+    ///
+    /// Fails if the quantity is zero or the unit price is negative.
+    pub fn add_line(
+        &mut self,
+        name: &str,
+        category: &str,
+        quantity: u32,
+        unit_price: Cents,
+    ) -> Result<(), BillingError> {
+        if quantity == 0 {
+            return Err(BillingError::ZeroQuantity(String::from(name)));
+        }
+        if unit_price < Cents::ZERO {
+            return Err(BillingError::NegativePrice(String::from(name)));
+        }
+        self.lines.push(BillLine {
+            name: String::from(name),
+            category: String::from(category),
+            quantity,
+            unit_price,
+        });
+        Ok(())
+    }
+
+    pub fn table(&self) -> u32 {
+        self.table
+    }
+
+    pub fn lines(&self) -> &[BillLine] {
+        &self.lines
+    }
+
+    /// # Errors
+    ///
+    /// Fails if a discount of that name was already applied or no item
+    /// qualifies for it.
+    pub fn apply_discount(&mut self, discount: Discount) -> Result<(), BillingError> {
+        if self
+            .discounts
+            .iter()
+            .any(|applied| applied.name == discount.name)
+        {
+            return Err(BillingError::DuplicateDiscount(discount.name));
+        }
+        if !self.lines.iter().any(|line| discount.applies_to(line)) {
+            return Err(BillingError::NotEligible(discount.name));
+        }
+        self.discounts.push(discount);
+        Ok(())
+    }
+
+    pub fn set_tip(&mut self, tip: Tip) {
+        self.tip = Some(tip);
+    }
+
+    /// What every line costs after discounts, tax and tip. The columns add
+    /// up to the totals of the bill.
+    pub fn breakdown(&self) -> Vec<LineTotals> {
+        let (lines, _) = self.work_out();
+        lines
+    }
+
+    // the line totals and how much each discount took off
+    fn work_out(&self) -> (Vec<LineTotals>, Vec<Cents>) {
+        let mut lines: Vec<LineTotals> = self
+            .lines
+            .iter()
+            .map(|line| LineTotals {
+                gross: line.gross(),
+                ..LineTotals::default()
+            })
+            .collect();
+
+        let mut discounted = Vec::with_capacity(self.discounts.len());
+        for discount in &self.discounts {
+            let eligible: Vec<usize> = (0..lines.len())
+                .filter(|&i| discount.applies_to(&self.lines[i]))
+                .collect();
+            let left: Vec<Cents> = eligible.iter().map(|&i| lines[i].net()).collect();
+            let base: Cents = left.iter().sum();
+            let amount = match discount.kind {
+                DiscountKind::Percent(rate) => rate.of(base).min(base),
+                DiscountKind::Amount(amount) => amount.clamp(Cents::ZERO, base),
+            };
+            // shares of the amount are never more than what's left of a line
+            for (&i, share) in eligible.iter().zip(allocate(amount, &left)) {
+                lines[i].discount += share;
+            }
+            discounted.push(amount);
+        }
+
+        let mut by_rate: HashMap<Rate, Vec<usize>> = HashMap::new();
+        for (i, line) in self.lines.iter().enumerate() {
+            by_rate
+                .entry(self.tax_rules.rate_for(&line.category))
+                .or_default()
+                .push(i);
+        }
+        for (rate, indexes) in by_rate {
+            let nets: Vec<Cents> = indexes.iter().map(|&i| lines[i].net()).collect();
+            let tax = rate.of(nets.iter().sum());
+            for (&i, share) in indexes.iter().zip(allocate(tax, &nets)) {
+                lines[i].tax = share;
+            }
+        }
+
+        let tip = match self.tip {
+            None => Cents::ZERO,
+            Some(Tip::Amount(amount)) => amount.max(Cents::ZERO),
+            Some(Tip::Percent(rate)) => rate.of(lines.iter().map(|line| line.gross).sum()),
+        };
+        let nets: Vec<Cents> = lines.iter().map(LineTotals::net).collect();
+        for (line, share) in lines.iter_mut().zip(allocate(tip, &nets)) {
+            line.tip = share;
+        }
+        (lines, discounted)
+    }
+
+    fn sum(&self, column: impl Fn(&LineTotals) -> Cents) -> Cents {
+        self.breakdown().iter().map(column).sum()
+    }
+
+    /// Items at menu prices.
+    pub fn subtotal(&self) -> Cents {
+        self.lines.iter().map(BillLine::gross).sum()
+    }
+
+    pub fn discount_total(&self) -> Cents {
+        self.sum(|line| line.discount)
+    }
+
+    pub fn tax(&self) -> Cents {
+        self.sum(|line| line.tax)
+    }
+
+    pub fn tip(&self) -> Cents {
+        self.sum(|line| line.tip)
+    }
+
+    pub fn total(&self) -> Cents {
+        self.sum(LineTotals::total)
+    }
+
+    /// Split the total between `guests` as evenly as cents allow, the
+    /// first guests paying the odd cents.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there are no guests.
+    pub fn split_evenly(&self, guests: usize) -> Result<Vec<Cents>, BillingError> {
+        if guests == 0 {
+            return Err(BillingError::InvalidSplit(String::from(
+                "there are no guests",
+            )));
+        }
+        Ok(allocate(self.total(), &vec![Cents(1); guests]))
+    }
+
+    /// Every guest pays for their items, with their tax and tip.
+    /// `assignments` has the guests (numbered from 0) for every line; a
+    /// line shared by several guests is divided evenly between them.
+    ///
+    /// # Errors
+    ///
+    /// Fails unless every line has at least one guest, all below `guests`.
+    pub fn split_by_item(
+        &self,
+        guests: usize,
+        assignments: &[&[usize]],
+    ) -> Result<Vec<Cents>, BillingError> {
+        if assignments.len() != self.lines.len() {
+            let reason = format!(
+                "{} lines but {} assignments",
+                self.lines.len(),
+                assignments.len()
+            );
+            return Err(BillingError::InvalidSplit(reason));
+        }
+        let mut shares = vec![Cents::ZERO; guests];
+        for (i, (line, sharing)) in self.breakdown().iter().zip(assignments).enumerate() {
+            if sharing.is_empty() {
+                return Err(BillingError::InvalidSplit(format!(
+                    "nobody has line {}",
+                    i + 1
+                )));
+            }
+            let parts = allocate(line.total(), &vec![Cents(1); sharing.len()]);
+            for (&guest, part) in sharing.iter().zip(parts) {
+                let share = shares.get_mut(guest).ok_or_else(|| {
+                    BillingError::InvalidSplit(format!("there is no guest {guest}"))
+                })?;
+                *share += part;
+            }
+        }
+        Ok(shares)
+    }
+
+    pub fn payments(&self) -> &[Payment] {
+        &self.payments
+    }
+
+    pub fn paid(&self) -> Cents {
+        self.payments.iter().map(|payment| payment.amount).sum()
+    }
+
+    /// What is left to pay.
+    pub fn balance(&self) -> Cents {
+        self.total() - self.paid()
+    }
+
+    pub fn is_settled(&self) -> bool {
+        self.balance() <= Cents::ZERO
+    }
+
+    /// Pay part or all of the balance. Cash beyond the balance is given
+    /// back as change, which is returned.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the amount isn't positive, the bill is already paid, or a
+    /// card or gift card would pay more than the balance.
+    pub fn pay(&mut self, tender: Tender, amount: Cents) -> Result<Cents, BillingError> {
+        if amount <= Cents::ZERO {
+            return Err(BillingError::InvalidAmount(amount));
+        }
+        let balance = self.balance();
+        if balance <= Cents::ZERO {
+            return Err(BillingError::AlreadySettled);
+        }
+        let change = (amount - balance).max(Cents::ZERO);
+        if change > Cents::ZERO && tender != Tender::Cash {
+            return Err(BillingError::Overpayment { balance });
+        }
+        self.payments.push(Payment {
+            tender,
+            amount: amount - change,
+            change,
+        });
+        Ok(change)
+    }
+
+    /// The receipt, 40 characters wide.
+    pub fn receipt(&self) -> String {
+        let mut receipt = String::new();
+        let (lines, discounted) = self.work_out();
+        let mut row = |label: &str, amount: Cents| {
+            let _ = writeln!(receipt, "{label:<28}{amount:>12}");
+        };
+        for line in &self.lines {
+            row(&format!("{}x {}", line.quantity, line.name), line.gross());
+        }
+        row("Subtotal", self.subtotal());
+        for (discount, amount) in self.discounts.iter().zip(discounted) {
+            row(&discount.name, -amount);
+        }
+        let column = |column: fn(&LineTotals) -> Cents| lines.iter().map(column).sum::<Cents>();
+        let (tax, tip, total) = (
+            column(|l| l.tax),
+            column(|l| l.tip),
+            column(LineTotals::total),
+        );
+        row("Tax", tax);
+        if tip > Cents::ZERO {
+            row("Tip", tip);
+        }
+        row("Total", total);
+        for payment in &self.payments {
+            row(&payment.tender.to_string(), payment.amount);
+            if payment.change > Cents::ZERO {
+                row("Change", payment.change);
+            }
+        }
+        if !self.payments.is_empty() {
+            let balance = total - self.paid();
+            row("Balance", balance.max(Cents::ZERO));
+        }
+        format!("Table {}\n{}{}", self.table, "-".repeat(40) + "\n", receipt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serving::{OrderItem, Orders};
+    use crate::time::testing::at;
+
+    fn dinner() -> Bill {
+        let mut bill = Bill::new(
+            3,
+            TaxRules::new(Rate(800)).with_category_rate("Drinks", Rate(1000)),
+        );
+        bill.add_line("Steak", "Mains", 1, Cents(2600)).unwrap();
+        bill.add_line("Burger", "Mains", 2, Cents(1450)).unwrap();
+        bill.add_line("Wine", "Drinks", 2, Cents(900)).unwrap();
+        bill
+    }
+
+    fn check_columns_add_up(bill: &Bill) {
+        let lines = bill.breakdown();
+        let sum = |column: fn(&LineTotals) -> Cents| lines.iter().map(column).sum::<Cents>();
+        assert_eq!(sum(|line| line.gross), bill.subtotal());
+        assert_eq!(
+            bill.subtotal() - bill.discount_total() + bill.tax() + bill.tip(),
+            bill.total()
+        );
+    }
+
+    #[test]
+    fn taxes_by_category() {
+        let bill = dinner();
+        assert_eq!(bill.subtotal(), Cents(7300));
+        // 8% of $55.00 and 10% of $18.00
+        assert_eq!(bill.tax(), Cents(440 + 180));
+        assert_eq!(bill.total(), Cents(7920));
+        check_columns_add_up(&bill);
+    }
+    #[test]
+    fn tax_is_rounded_once_per_rate() {
+        let mut bill = Bill::new(1, TaxRules::new(Rate(825)));
+        for _ in 0..3 {
+            bill.add_line("Coffee", "Drinks", 1, Cents(100)).unwrap();
+        }
+        // 8.25 cents each would round to 8, but 24.75 rounds to 25
+        assert_eq!(bill.tax(), Cents(25));
+        let taxes: Vec<Cents> = bill.breakdown().iter().map(|line| line.tax).collect();
+        assert_eq!(taxes, [Cents(9), Cents(8), Cents(8)]);
+        // exactly half a cent rounds up
+        let mut bill = Bill::new(1, TaxRules::new(Rate(500)));
+        bill.add_line("Cookie", "Desserts", 1, Cents(10)).unwrap();
+        bill.add_line("Cookie", "Desserts", 1, Cents(1)).unwrap();
+        assert_eq!(bill.tax(), Cents(1)); // 0.55 cents
+        let mut bill = Bill::new(1, TaxRules::new(Rate(500)));
+        bill.add_line("Mint", "Desserts", 1, Cents(9)).unwrap();
+        assert_eq!(bill.tax(), Cents(0)); // 0.45 cents
+    }
+    #[test]
+    fn discounts_stack_in_order() {
+        let mut bill = dinner();
+        bill.apply_discount(Discount::amount("Birthday", Cents(1000)).on_category("mains"))
+            .unwrap();
+        bill.apply_discount(Discount::percent("Staff", Rate::percent(50)))
+            .unwrap();
+        // $10 off the $55 of mains, then half of the $63 left
+        assert_eq!(bill.discount_total(), Cents(1000 + 3150));
+        let lines = bill.breakdown();
+        assert_eq!(lines[0].discount, Cents(473 + 1064)); // 26/55 of $10, then half
+        assert_eq!(lines[2].discount, Cents(900));
+        // tax is on what's left: 8% of $22.50 and 10% of $9.00
+        assert_eq!(bill.tax(), Cents(180 + 90));
+        check_columns_add_up(&bill);
+
+        assert_eq!(
+            bill.apply_discount(Discount::percent("Staff", Rate::percent(10))),
+            Err(BillingError::DuplicateDiscount(String::from("Staff")))
+        );
+        assert_eq!(
+            bill.apply_discount(Discount::percent("Kids", Rate::percent(10)).on_category("Kids")),
+            Err(BillingError::NotEligible(String::from("Kids")))
+        );
+    }
+    #[test]
+    fn discounts_never_go_below_zero() {
+        let mut bill = Bill::new(1, TaxRules::new(Rate(800)));
+        bill.add_line("Soup", "Appetizers", 1, Cents(650)).unwrap();
+        bill.add_line("Fries", "Sides", 1, Cents(450)).unwrap();
+        bill.apply_discount(Discount::amount("Voucher", Cents(5000)).on_category("Sides"))
+            .unwrap();
+        bill.apply_discount(Discount::percent("Everything", Rate::percent(150)))
+            .unwrap();
+        assert_eq!(bill.discount_total(), bill.subtotal());
+        assert_eq!(bill.total(), Cents::ZERO);
+        assert!(bill
+            .breakdown()
+            .iter()
+            .all(|line| line.net() == Cents::ZERO));
+    }
+    #[test]
+    fn coupons_are_looked_up_by_code() {
+        let coupons = Coupons::new()
+            .with_coupon("SUMMER10", Discount::percent("Summer", Rate::percent(10)))
+            .with_coupon(
+                "FREEFRIES",
+                Discount::amount("Free fries", Cents(450)).on_category("Sides"),
+            );
+        let mut bill = dinner();
+        bill.apply_discount(coupons.redeem("summer10").unwrap())
+            .unwrap();
+        assert_eq!(bill.discount_total(), Cents(730));
+        assert_eq!(
+            coupons.redeem("WINTER"),
+            Err(BillingError::UnknownCoupon(String::from("WINTER")))
+        );
+        assert!(matches!(
+            bill.apply_discount(coupons.redeem("FREEFRIES").unwrap()),
+            Err(BillingError::NotEligible(_))
+        ));
+    }
+    #[test]
+    fn tips_are_on_the_subtotal_before_discounts() {
+        let mut bill = dinner();
+        bill.apply_discount(Discount::percent("Half off", Rate::percent(50)))
+            .unwrap();
+        bill.set_tip(Tip::Percent(Rate::percent(15)));
+        assert_eq!(bill.tip(), Cents(1095));
+        bill.set_tip(Tip::Amount(Cents(500)));
+        assert_eq!(bill.tip(), Cents(500));
+        check_columns_add_up(&bill);
+    }
+    #[test]
+    fn rejects_negative_prices_and_zero_quantities() {
+        let mut bill = Bill::new(1, TaxRules::new(Rate(825)));
+        assert_eq!(
+            bill.add_line("Steak", "Mains", 1, Cents(-2000)),
+            Err(BillingError::NegativePrice(String::from("Steak")))
+        );
+        assert_eq!(
+            bill.add_line("Burger", "Mains", 0, Cents(1450)),
+            Err(BillingError::ZeroQuantity(String::from("Burger")))
+        );
+        assert!(bill.lines().is_empty());
+        assert_eq!(bill.total(), Cents(0));
+    }
+    #[test]
+    fn splits_evenly_to_the_cent() {
+        let mut bill = Bill::new(1, TaxRules::new(Rate(0)));
+        bill.add_line("Pizza", "Mains", 1, Cents(1000)).unwrap();
+        assert_eq!(
+            bill.split_evenly(3).unwrap(),
+            [Cents(334), Cents(333), Cents(333)]
+        );
+        assert_eq!(bill.split_evenly(1).unwrap(), [Cents(1000)]);
+        let shares = dinner().split_evenly(7).unwrap();
+        assert_eq!(shares.iter().sum::<Cents>(), dinner().total());
+        assert!(shares
+            .iter()
+            .all(|share| *share == Cents(1131) || *share == Cents(1132)));
+        assert!(matches!(
+            bill.split_evenly(0),
+            Err(BillingError::InvalidSplit(_))
+        ));
+    }
+    #[test]
+    fn splits_by_item_with_shared_lines() {
+        let mut bill = dinner();
+        bill.set_tip(Tip::Percent(Rate::percent(20)));
+        // guest 0 had the steak, guests 1 and 2 the burgers, all shared the wine
+        let shares = bill.split_by_item(3, &[&[0], &[1, 2], &[0, 1, 2]]).unwrap();
+        assert_eq!(shares.iter().sum::<Cents>(), bill.total());
+        let lines = bill.breakdown();
+        let wine = allocate(lines[2].total(), &[Cents(1); 3]);
+        assert_eq!(shares[0], lines[0].total() + wine[0]);
+        assert_eq!(
+            shares[1] - shares[2],
+            wine[1] - wine[2] + Cents(lines[1].total().0 % 2)
+        );
+
+        let bad: [&[&[usize]]; 3] = [&[&[0], &[1]], &[&[0], &[], &[1]], &[&[0], &[3], &[1]]];
+        for bad in bad {
+            assert!(matches!(
+                bill.split_by_item(3, bad),
+                Err(BillingError::InvalidSplit(_))
+            ));
+        }
+    }
+    #[test]
+    fn takes_payments_with_several_tenders() {
+        let mut bill = dinner(); // $79.20
+        assert_eq!(
+            bill.pay(
+                Tender::GiftCard {
+                    code: String::from("GC-7")
+                },
+                Cents(2500)
+            ),
+            Ok(Cents::ZERO)
+        );
+        let card = Tender::Card {
+            last_four: String::from("4242"),
+        };
+        assert_eq!(
+            bill.pay(card.clone(), Cents(6000)),
+            Err(BillingError::Overpayment {
+                balance: Cents(5420)
+            })
+        );
+        bill.pay(card, Cents(3000)).unwrap();
+        assert_eq!(bill.balance(), Cents(2420));
+        assert_eq!(bill.pay(Tender::Cash, Cents(3000)), Ok(Cents(580)));
+        assert!(bill.is_settled());
+        assert_eq!(bill.paid(), bill.total());
+        assert_eq!(
+            bill.pay(Tender::Cash, Cents(100)),
+            Err(BillingError::AlreadySettled)
+        );
+        assert_eq!(
+            bill.pay(Tender::Cash, Cents(0)),
+            Err(BillingError::InvalidAmount(Cents(0)))
+        );
+    }
+    #[test]
+    fn bills_orders_at_menu_prices() {
+        let menu = Menu::from_toml(include_str!("../../menu.toml")).unwrap();
+        let now = at(19, 0);
+        let mut orders = Orders::new();
+        let items = vec![
+            OrderItem::new("burger", 2).with_modifier("no onions"),
+            OrderItem::new("Fries", 1),
+        ];
+        orders.take_order(5, items, now).unwrap();
+        orders
+            .take_order(5, vec![OrderItem::new("Cheesecake", 1)], now)
+            .unwrap();
+        orders
+            .take_order(6, vec![OrderItem::new("Steak", 1)], now)
+            .unwrap();
+
+        let bill = Bill::for_orders(5, orders.for_table(5), &menu, TaxRules::new(Rate(0))).unwrap();
+        let names: Vec<_> = bill.lines().iter().map(|line| line.name.as_str()).collect();
+        assert_eq!(names, ["Burger", "Fries", "Cheesecake"]);
+        assert_eq!(bill.subtotal(), Cents(2 * 1450 + 450 + 750));
+
+        orders
+            .take_order(6, vec![OrderItem::new("Lobster", 1)], now)
+            .unwrap();
+        assert_eq!(
+            Bill::for_orders(6, orders.for_table(6), &menu, TaxRules::default()),
+            Err(BillingError::UnknownItem(String::from("Lobster")))
+        );
+    }
+    #[test]
+    fn renders_a_receipt() {
+        let mut bill = Bill::new(7, TaxRules::new(Rate(825)));
+        bill.add_line("Burger", "Mains", 2, Cents(1450)).unwrap();
+        bill.add_line("Fries", "Sides", 1, Cents(450)).unwrap();
+        bill.apply_discount(Discount::percent("Happy hour", Rate::percent(10)))
+            .unwrap();
+        bill.set_tip(Tip::Amount(Cents(500)));
+        bill.pay(
+            Tender::Card {
+                last_four: String::from("1234"),
+            },
+            Cents(2000),
+        )
+        .unwrap();
+        bill.pay(Tender::Cash, Cents(2000)).unwrap();
+        let expected = "\
+Table 7
+----------------------------------------
+2x Burger                         $29.00
+1x Fries                           $4.50
+Subtotal                          $33.50
+Happy hour                        -$3.35
+Tax                                $2.49
+Tip                                $5.00
+Total                             $37.64
+Card ****1234                     $20.00
+Cash                              $17.64
+Change                             $2.36
+Balance                            $0.00
+";
+        assert_eq!(bill.receipt(), expected);
+    }
+}
@@ -1,3 +1,733 @@
-pub fn add_to_waitlist() {}
+//! Greeting guests, keeping the waitlist and seating parties at tables.
+//!
+//! Parties that can't be seated right away join the [`Waitlist`]. When a
+//! table that fits frees up, the [`Host`] calls the party and holds the
+//! table for it; a party that doesn't show up before the hold runs out is
+//! dropped and the table goes to the next one.
 
-fn _seat_at_table() {}
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::time::Time;
+
+/// Identifies a party for as long as it is waiting or seated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PartyId(pub u32);
+
+impl fmt::Display for PartyId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Party {
+    pub id: PartyId,
+    pub name: String,
+    pub size: u32,
+    pub arrived: Time,
+}
+
+/// Parties in the order they arrived.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Waitlist {
+    parties: VecDeque<Party>,
+    next_id: u32,
+}
+
+impl Waitlist {
+    pub fn new() -> Waitlist {
+        Waitlist::default()
+    }
+
+    /// Put a party at the end of the list.
+    pub fn add(&mut self, name: &str, size: u32, arrived: Time) -> PartyId {
+        self.next_id += 1;
+        let id = PartyId(self.next_id);
+        self.parties.push_back(Party {
+            id,
+            name: String::from(name),
+            size,
+            arrived,
+        });
+        id
+    }
+
+    /// Take a party off the list, wherever it is.
+    pub fn remove(&mut self, id: PartyId) -> Option<Party> {
+        let index = self.position(id)?;
+        self.parties.remove(index)
+    }
+
+    /// How many parties are ahead of this one.
+    pub fn position(&self, id: PartyId) -> Option<usize> {
+        self.parties.iter().position(|party| party.id == id)
+    }
+
+    pub fn get(&self, id: PartyId) -> Option<&Party> {
+        self.parties.iter().find(|party| party.id == id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.parties.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parties.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Party> {
+        self.parties.iter()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TableStatus {
+    Free,
+    /// Kept for a party that was called but hasn't sat down yet.
+    Held {
+        party: PartyId,
+        until: Time,
+    },
+    Occupied {
+        party: PartyId,
+        since: Time,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Table {
+    number: u32,
+    capacity: u32,
+    status: TableStatus,
+}
+
+impl Table {
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    pub fn status(&self) -> TableStatus {
+        self.status
+    }
+
+    pub fn is_free(&self) -> bool {
+        self.status == TableStatus::Free
+    }
+}
+
+/// The tables of the dining room.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FloorPlan {
+    tables: Vec<Table>,
+}
+
+impl FloorPlan {
+    pub fn new() -> FloorPlan {
+        FloorPlan::default()
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the table number is already taken or the table has no
+    /// seats.
+    pub fn add_table(&mut self, number: u32, capacity: u32) {
+        assert!(capacity > 0, "table {number} needs at least one seat");
+        assert!(
+            self.table(number).is_none(),
+            "there already is a table {number}"
+        );
+        self.tables.push(Table {
+            number,
+            capacity,
+            status: TableStatus::Free,
+        });
+    }
+
+    pub fn table(&self, number: u32) -> Option<&Table> {
+        self.tables.iter().find(|table| table.number == number)
+    }
+
+    fn table_mut(&mut self, number: u32) -> Option<&mut Table> {
+        self.tables.iter_mut().find(|table| table.number == number)
+    }
+
+    pub fn tables(&self) -> impl Iterator<Item = &Table> {
+        self.tables.iter()
+    }
+
+    /// Seats of the biggest table.
+    pub fn largest_table(&self) -> u32 {
+        self.tables.iter().map(Table::capacity).max().unwrap_or(0)
+    }
+
+    /// The free table that fits the party with the fewest seats to spare,
+    /// so big tables stay available for big parties. Ties go to the lower
+    /// table number.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use modules_restaurant::hosting::FloorPlan;
+    /// let mut floor = FloorPlan::new();
+    /// floor.add_table(1, 6);
+    /// floor.add_table(2, 4);
+    /// floor.add_table(3, 2);
+    /// assert_eq!(floor.best_fit(3), Some(2));
+    /// assert_eq!(floor.best_fit(7), None);
+    /// ```
+    pub fn best_fit(&self, size: u32) -> Option<u32> {
+        self.tables
+            .iter()
+            .filter(|table| table.is_free() && table.capacity >= size)
+            .min_by_key(|table| (table.capacity, table.number))
+            .map(Table::number)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostError {
+    InvalidPartySize(u32),
+    /// Even the largest table is too small.
+    NoTableFits {
+        size: u32,
+    },
+    UnknownParty(PartyId),
+    /// The party is still waiting, there is no table held for it.
+    NotCalled(PartyId),
+    /// The party came after its hold ran out, the table was given away.
+    HoldExpired(PartyId),
+    UnknownTable(u32),
+    TableNotOccupied(u32),
+}
+
+impl fmt::Display for HostError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HostError::InvalidPartySize(size) => write!(f, "a party can't have {size} guests"),
+            HostError::NoTableFits { size } => write!(f, "no table seats a party of {size}"),
+            HostError::UnknownParty(id) => write!(f, "there is no party {id}"),
+            HostError::NotCalled(id) => write!(f, "party {id} hasn't been called yet"),
+            HostError::HoldExpired(id) => {
+                write!(f, "the table held for party {id} has been given away")
+            }
+            HostError::UnknownTable(number) => write!(f, "there is no table {number}"),
+            HostError::TableNotOccupied(number) => write!(f, "nobody sits at table {number}"),
+        }
+    }
+}
+
+impl std::error::Error for HostError {}
+
+/// A party that was called to the table held for it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Call {
+    pub party: Party,
+    pub table: u32,
+    /// When the table is given away if the party hasn't sat down.
+    pub expires: Time,
+}
+
+/// The host stand: the waitlist, the floor plan and the parties that were
+/// called.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use modules_restaurant::hosting::{FloorPlan, Host};
+/// use modules_restaurant::time::Time;
+///
+/// let mut floor = FloorPlan::new();
+/// floor.add_table(1, 2);
+/// let mut host = Host::new(floor);
+/// let seven = Time::from_ymd_hm(2024, 6, 1, 19, 0);
+///
+/// let ana = host.add_to_waitlist("Ana", 2, seven).unwrap();
+/// let ben = host.add_to_waitlist("Ben", 2, seven).unwrap();
+/// let calls = host.call_parties(seven);
+/// assert_eq!(calls[0].party.id, ana);
+/// assert_eq!(host.seat(ana, seven), Ok(1));
+/// // Ben waits until Ana is expected to leave
+/// assert_eq!(host.estimated_wait(ben, seven), Some(Duration::from_secs(60 * 60)));
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Host {
+    waitlist: Waitlist,
+    floor: FloorPlan,
+    called: Vec<Call>,
+    dining_time: Duration,
+    no_show_timeout: Duration,
+}
+
+impl Host {
+    /// A host that expects parties to stay an hour and holds tables for
+    /// called parties for ten minutes.
+    pub fn new(floor: FloorPlan) -> Host {
+        Host {
+            waitlist: Waitlist::new(),
+            floor,
+            called: Vec::new(),
+            dining_time: Duration::from_secs(60 * 60),
+            no_show_timeout: Duration::from_secs(10 * 60),
+        }
+    }
+
+    /// How long a party is expected to stay, used to estimate waits.
+    pub fn with_dining_time(mut self, dining_time: Duration) -> Host {
+        self.dining_time = dining_time;
+        self
+    }
+
+    /// How long a table is held for a called party.
+    pub fn with_no_show_timeout(mut self, timeout: Duration) -> Host {
+        self.no_show_timeout = timeout;
+        self
+    }
+
+    pub fn waitlist(&self) -> &Waitlist {
+        &self.waitlist
+    }
+
+    pub fn floor_plan(&self) -> &FloorPlan {
+        &self.floor
+    }
+
+    /// Parties that were called and haven't sat down yet.
+    pub fn called(&self) -> &[Call] {
+        &self.called
+    }
+
+    /// # Errors
+    ///
+    /// Fails if the party is empty or bigger than any table.
+    pub fn add_to_waitlist(
+        &mut self,
+        name: &str,
+        size: u32,
+        now: Time,
+    ) -> Result<PartyId, HostError> {
+        self.check_size(size)?;
+        Ok(self.waitlist.add(name, size, now))
+    }
+
+    fn check_size(&self, size: u32) -> Result<(), HostError> {
+        if size == 0 {
+            Err(HostError::InvalidPartySize(size))
+        } else if size > self.floor.largest_table() {
+            Err(HostError::NoTableFits { size })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Leave before being seated, whether waiting or already called.
+    pub fn cancel(&mut self, id: PartyId) -> Option<Party> {
+        if let Some(party) = self.waitlist.remove(id) {
+            return Some(party);
+        }
+        let index = self.called.iter().position(|call| call.party.id == id)?;
+        let call = self.called.remove(index);
+        self.free_table(call.table);
+        Some(call.party)
+    }
+
+    /// Hold a table for every waiting party that fits one, in the order
+    /// they arrived. A party is skipped if no table fits it right now, so a
+    /// couple doesn't wait behind a party of eight.
+    pub fn call_parties(&mut self, now: Time) -> Vec<Call> {
+        let mut calls = Vec::new();
+        let mut index = 0;
+        while index < self.waitlist.parties.len() {
+            let size = self.waitlist.parties[index].size;
+            let Some(number) = self.floor.best_fit(size) else {
+                index += 1;
+                continue;
+            };
+            let party = self
+                .waitlist
+                .parties
+                .remove(index)
+                .expect("index is in range");
+            let expires = now + self.no_show_timeout;
+            if let Some(table) = self.floor.table_mut(number) {
+                table.status = TableStatus::Held {
+                    party: party.id,
+                    until: expires,
+                };
+            }
+            calls.push(Call {
+                party,
+                table: number,
+                expires,
+            });
+        }
+        self.called.extend(calls.iter().cloned());
+        calls
+    }
+
+    /// The called party sits down at the table held for it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the party is unknown or still waiting to be called, or if
+    /// its hold ran out before [`expire_no_shows`](Host::expire_no_shows)
+    /// noticed; the table is freed then.
+    pub fn seat(&mut self, id: PartyId, now: Time) -> Result<u32, HostError> {
+        let Some(index) = self.called.iter().position(|call| call.party.id == id) else {
+            return Err(if self.waitlist.get(id).is_some() {
+                HostError::NotCalled(id)
+            } else {
+                HostError::UnknownParty(id)
+            });
+        };
+        let call = self.called.remove(index);
+        if call.expires <= now {
+            self.free_table(call.table);
+            return Err(HostError::HoldExpired(id));
+        }
+        if let Some(table) = self.floor.table_mut(call.table) {
+            table.status = TableStatus::Occupied {
+                party: id,
+                since: now,
+            };
+        }
+        Ok(call.table)
+    }
+
+    /// The party at the table has left. Returns who sat there.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no such table or nobody sits at it.
+    pub fn clear_table(&mut self, number: u32) -> Result<PartyId, HostError> {
+        let table = self
+            .floor
+            .table_mut(number)
+            .ok_or(HostError::UnknownTable(number))?;
+        match table.status {
+            TableStatus::Occupied { party, .. } => {
+                table.status = TableStatus::Free;
+                Ok(party)
+            }
+            _ => Err(HostError::TableNotOccupied(number)),
+        }
+    }
+
+    fn free_table(&mut self, number: u32) {
+        if let Some(table) = self.floor.table_mut(number) {
+            table.status = TableStatus::Free;
+        }
+    }
+
+    /// Drop the called parties whose hold has run out and free their
+    /// tables, which the next [`call_parties`](Host::call_parties) gives
+    /// to someone else.
+    pub fn expire_no_shows(&mut self, now: Time) -> Vec<Party> {
+        let (expired, called): (Vec<_>, Vec<_>) =
+            self.called.drain(..).partition(|call| call.expires <= now);
+        self.called = called;
+        expired
+            .into_iter()
+            .map(|call| {
+                self.free_table(call.table);
+                call.party
+            })
+            .collect()
+    }
+
+    /// How long a waiting party will probably wait from `now`, or `None`
+    /// if it isn't on the waitlist.
+    pub fn estimated_wait(&self, id: PartyId, now: Time) -> Option<Duration> {
+        let position = self.waitlist.position(id)?;
+        let ahead = self
+            .waitlist
+            .parties
+            .range(..position)
+            .map(|party| party.size);
+        Some(self.simulate_wait(ahead, self.waitlist.parties[position].size, now))
+    }
+
+    /// How long a party of `size` arriving `now` would wait, to tell guests
+    /// before they join the list.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the party is empty or bigger than any table.
+    pub fn quote(&self, size: u32, now: Time) -> Result<Duration, HostError> {
+        self.check_size(size)?;
+        let ahead = self.waitlist.parties.iter().map(|party| party.size);
+        Ok(self.simulate_wait(ahead, size, now))
+    }
+
+    // Every table becomes free once its guests are expected to leave. The
+    // parties ahead are seated in order at the fitting table that frees up
+    // first, staying `dining_time` each, and then it's this party's turn.
+    fn simulate_wait(&self, ahead: impl Iterator<Item = u32>, size: u32, now: Time) -> Duration {
+        let mut free_at: Vec<(u32, Time)> = self
+            .floor
+            .tables
+            .iter()
+            .map(|table| {
+                let free = match table.status {
+                    TableStatus::Free => now,
+                    TableStatus::Held { .. } => now + self.dining_time,
+                    TableStatus::Occupied { since, .. } => (since + self.dining_time).max(now),
+                };
+                (table.capacity, free)
+            })
+            .collect();
+        let mut seat = |size: u32| {
+            let (_, free) = free_at
+                .iter_mut()
+                .filter(|(capacity, _)| *capacity >= size)
+                .min_by_key(|(capacity, free)| (*free, *capacity))?;
+            let start = *free;
+            *free = start + self.dining_time;
+            Some(start)
+        };
+        for size in ahead {
+            seat(size);
+        }
+        seat(size).map_or(Duration::ZERO, |start| start.saturating_since(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::testing::{at, minutes};
+
+    /// Two two-tops, a four-top and a six-top.
+    fn dining_room() -> Host {
+        let mut floor = FloorPlan::new();
+        floor.add_table(1, 2);
+        floor.add_table(2, 2);
+        floor.add_table(3, 4);
+        floor.add_table(4, 6);
+        Host::new(floor)
+    }
+
+    fn seat_everyone_called(host: &mut Host, now: Time) -> Vec<(String, u32)> {
+        host.call_parties(now)
+            .into_iter()
+            .map(|call| {
+                (
+                    call.party.name.clone(),
+                    host.seat(call.party.id, now).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn best_fit_keeps_big_tables_free() {
+        let host = dining_room();
+        let floor = host.floor_plan();
+        assert_eq!(floor.best_fit(1), Some(1));
+        assert_eq!(floor.best_fit(3), Some(3));
+        assert_eq!(floor.best_fit(5), Some(4));
+        assert_eq!(floor.best_fit(7), None);
+    }
+    #[test]
+    fn busy_night_seats_small_parties_around_big_ones() {
+        let mut host = dining_room();
+        host.add_to_waitlist("Ana", 2, at(19, 0)).unwrap();
+        host.add_to_waitlist("Ben", 4, at(19, 1)).unwrap();
+        host.add_to_waitlist("Cho", 3, at(19, 2)).unwrap();
+        host.add_to_waitlist("Dev", 6, at(19, 3)).unwrap();
+        host.add_to_waitlist("Eli", 2, at(19, 4)).unwrap();
+        host.add_to_waitlist("Fay", 2, at(19, 5)).unwrap();
+
+        let seated = seat_everyone_called(&mut host, at(19, 5));
+        // Cho takes the six-top since the four-top went to Ben, so Dev waits
+        // while Eli still gets the second two-top
+        let expected = [("Ana", 1), ("Ben", 3), ("Cho", 4), ("Eli", 2)];
+        assert_eq!(
+            seated,
+            expected.map(|(name, table)| (String::from(name), table))
+        );
+        let waiting: Vec<_> = host
+            .waitlist()
+            .iter()
+            .map(|party| party.name.as_str())
+            .collect();
+        assert_eq!(waiting, ["Dev", "Fay"]);
+        assert!(host.floor_plan().tables().all(|table| !table.is_free()));
+
+        // Ana leaves, her table goes to Fay; Dev needs the six-top
+        host.clear_table(1).unwrap();
+        assert_eq!(
+            seat_everyone_called(&mut host, at(20, 0)),
+            [(String::from("Fay"), 1)]
+        );
+        host.clear_table(4).unwrap();
+        assert_eq!(
+            seat_everyone_called(&mut host, at(20, 10)),
+            [(String::from("Dev"), 4)]
+        );
+        assert!(host.waitlist().is_empty());
+    }
+    #[test]
+    fn estimates_waits_from_expected_departures() {
+        let mut host = dining_room();
+        for (name, size) in [("a", 2), ("b", 2), ("c", 4), ("d", 6)] {
+            host.add_to_waitlist(name, size, at(19, 0)).unwrap();
+        }
+        seat_everyone_called(&mut host, at(19, 0));
+        assert_eq!(host.quote(2, at(19, 0)), Ok(minutes(60)));
+
+        let first = host.add_to_waitlist("first", 2, at(19, 20)).unwrap();
+        let second = host.add_to_waitlist("second", 2, at(19, 20)).unwrap();
+        let third = host.add_to_waitlist("third", 2, at(19, 20)).unwrap();
+        let big = host.add_to_waitlist("big", 5, at(19, 20)).unwrap();
+        // the two-tops free up at 20:00; the third couple gets the four-top
+        assert_eq!(host.estimated_wait(first, at(19, 20)), Some(minutes(40)));
+        assert_eq!(host.estimated_wait(second, at(19, 20)), Some(minutes(40)));
+        assert_eq!(host.estimated_wait(third, at(19, 20)), Some(minutes(40)));
+        // and the six-top is the only one fitting five
+        assert_eq!(host.estimated_wait(big, at(19, 20)), Some(minutes(40)));
+        // the next couple waits for the first couple to finish
+        assert_eq!(host.quote(2, at(19, 20)), Ok(minutes(100)));
+        // overdue tables are expected to free up any moment
+        assert_eq!(host.estimated_wait(first, at(20, 30)), Some(Duration::ZERO));
+        assert_eq!(host.estimated_wait(PartyId(99), at(19, 20)), None);
+    }
+    #[test]
+    fn shorter_dining_time_shortens_waits() {
+        let mut floor = FloorPlan::new();
+        floor.add_table(1, 4);
+        let mut host = Host::new(floor).with_dining_time(minutes(45));
+        let id = host.add_to_waitlist("x", 4, at(12, 0)).unwrap();
+        host.call_parties(at(12, 0));
+        host.seat(id, at(12, 0)).unwrap();
+        host.add_to_waitlist("y", 3, at(12, 0)).unwrap();
+        assert_eq!(host.quote(1, at(12, 15)), Ok(minutes(75)));
+    }
+    #[test]
+    fn no_shows_lose_their_table() {
+        let mut floor = FloorPlan::new();
+        floor.add_table(1, 4);
+        let mut host = Host::new(floor).with_no_show_timeout(minutes(5));
+        let gone = host.add_to_waitlist("gone", 2, at(19, 0)).unwrap();
+        let next = host.add_to_waitlist("next", 3, at(19, 1)).unwrap();
+
+        let calls = host.call_parties(at(19, 2));
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].expires, at(19, 7));
+        assert_eq!(
+            host.floor_plan().table(1).unwrap().status(),
+            TableStatus::Held {
+                party: gone,
+                until: at(19, 7)
+            }
+        );
+        assert!(host.expire_no_shows(at(19, 6)).is_empty());
+        let expired = host.expire_no_shows(at(19, 7));
+        assert_eq!(
+            expired.iter().map(|party| party.id).collect::<Vec<_>>(),
+            [gone]
+        );
+        assert!(host.called().is_empty());
+        assert_eq!(
+            host.seat(gone, at(19, 8)),
+            Err(HostError::UnknownParty(gone))
+        );
+
+        let calls = host.call_parties(at(19, 8));
+        assert_eq!(calls[0].party.id, next);
+        assert_eq!(host.seat(next, at(19, 9)), Ok(1));
+        assert_eq!(
+            host.floor_plan().table(1).unwrap().status(),
+            TableStatus::Occupied {
+                party: next,
+                since: at(19, 9)
+            }
+        );
+    }
+    #[test]
+    fn seating_after_the_hold_ran_out_frees_the_table() {
+        let mut floor = FloorPlan::new();
+        floor.add_table(1, 4);
+        let mut host = Host::new(floor).with_no_show_timeout(minutes(5));
+        let late = host.add_to_waitlist("late", 2, at(19, 0)).unwrap();
+        host.call_parties(at(19, 0));
+        assert_eq!(
+            host.seat(late, at(19, 5)),
+            Err(HostError::HoldExpired(late))
+        );
+        assert!(host.called().is_empty());
+        assert_eq!(
+            host.floor_plan().table(1).unwrap().status(),
+            TableStatus::Free
+        );
+    }
+    #[test]
+    fn cancelling_frees_the_held_table_and_moves_the_line_up() {
+        let mut floor = FloorPlan::new();
+        floor.add_table(1, 2);
+        let mut host = Host::new(floor);
+        let a = host.add_to_waitlist("a", 2, at(18, 0)).unwrap();
+        let b = host.add_to_waitlist("b", 2, at(18, 0)).unwrap();
+        let c = host.add_to_waitlist("c", 2, at(18, 0)).unwrap();
+        host.call_parties(at(18, 0));
+        assert_eq!(host.waitlist().position(c), Some(1));
+        assert_eq!(
+            host.cancel(b).map(|party| party.name),
+            Some(String::from("b"))
+        );
+        assert_eq!(host.waitlist().position(c), Some(0));
+        assert!(host.cancel(a).is_some());
+        assert!(host.floor_plan().table(1).unwrap().is_free());
+        assert_eq!(host.cancel(a), None);
+    }
+    #[test]
+    fn reports_errors() {
+        let mut host = dining_room();
+        assert_eq!(
+            host.add_to_waitlist("x", 0, at(18, 0)),
+            Err(HostError::InvalidPartySize(0))
+        );
+        assert_eq!(
+            host.add_to_waitlist("x", 7, at(18, 0)),
+            Err(HostError::NoTableFits { size: 7 })
+        );
+        assert_eq!(
+            host.quote(7, at(18, 0)),
+            Err(HostError::NoTableFits { size: 7 })
+        );
+        assert_eq!(host.clear_table(9), Err(HostError::UnknownTable(9)));
+        assert_eq!(host.clear_table(1), Err(HostError::TableNotOccupied(1)));
+
+        let mut floor = FloorPlan::new();
+        floor.add_table(1, 2);
+        let mut host = Host::new(floor);
+        host.add_to_waitlist("first", 2, at(18, 0)).unwrap();
+        host.call_parties(at(18, 0));
+        let waiting = host.add_to_waitlist("second", 2, at(18, 0)).unwrap();
+        assert!(host.call_parties(at(18, 0)).is_empty());
+        assert_eq!(
+            host.seat(waiting, at(18, 0)),
+            Err(HostError::NotCalled(waiting))
+        );
+        assert_eq!(
+            HostError::NotCalled(waiting).to_string(),
+            "party #2 hasn't been called yet"
+        );
+    }
+    #[test]
+    #[should_panic(expected = "already is a table 1")]
+    fn duplicate_table_numbers_panic() {
+        let mut floor = FloorPlan::new();
+        floor.add_table(1, 2);
+        floor.add_table(1, 4);
+    }
+}
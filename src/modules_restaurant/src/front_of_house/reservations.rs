@@ -0,0 +1,694 @@
+//! Booking tables ahead of time.
+//!
+//! A [`Reservation`] holds its table from its start until the party is
+//! expected to leave, which depends on the party's size, plus some time to
+//! turn the table over. Two reservations of the same table conflict when
+//! those spans overlap.
+//!
+//! The [`ReservationBook`] is saved to and loaded from a JSON file, so the
+//! bookings survive a restart.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::hosting::FloorPlan;
+use crate::time::Time;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ReservationId(pub u32);
+
+impl fmt::Display for ReservationId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "R{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reservation {
+    pub id: ReservationId,
+    pub name: String,
+    pub size: u32,
+    pub table: u32,
+    pub start: Time,
+    /// When the party is expected to leave.
+    pub end: Time,
+}
+
+/// Opening hours, how long parties stay and how bookings are spaced.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BookingRules {
+    // minutes of the day
+    opens: u32,
+    last_seating: u32,
+    slot_minutes: u64,
+    turnover_minutes: u64,
+    // (largest party size, minutes), by size
+    durations: Vec<(u32, u64)>,
+    default_minutes: u64,
+}
+
+impl Default for BookingRules {
+    /// Seatings from 17:00 until 22:00 every 15 minutes. Couples stay 75
+    /// minutes, up to four guests 90, up to six 105 and bigger parties two
+    /// hours; tables take 15 minutes to turn over.
+    fn default() -> Self {
+        BookingRules {
+            opens: 17 * 60,
+            last_seating: 22 * 60,
+            slot_minutes: 15,
+            turnover_minutes: 15,
+            durations: vec![(2, 75), (4, 90), (6, 105)],
+            default_minutes: 120,
+        }
+    }
+}
+
+impl BookingRules {
+    /// # Panics
+    ///
+    /// Panics if the last seating is before opening or a time is invalid.
+    pub fn with_hours(mut self, opens: (u32, u32), last_seating: (u32, u32)) -> BookingRules {
+        let minute_of_day = |(hour, minute): (u32, u32)| {
+            assert!(
+                hour < 24 && minute < 60,
+                "invalid time of day {hour:02}:{minute:02}"
+            );
+            hour * 60 + minute
+        };
+        self.opens = minute_of_day(opens);
+        self.last_seating = minute_of_day(last_seating);
+        assert!(
+            self.opens <= self.last_seating,
+            "the last seating is before opening"
+        );
+        self
+    }
+
+    /// Offer a table every `slot` when listing availability.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slot` is shorter than a minute.
+    pub fn with_slot(mut self, slot: Duration) -> BookingRules {
+        self.slot_minutes = slot.as_secs() / 60;
+        assert!(self.slot_minutes > 0, "slots must be at least a minute");
+        self
+    }
+
+    /// Time to clear and set a table between two parties.
+    pub fn with_turnover(mut self, turnover: Duration) -> BookingRules {
+        self.turnover_minutes = turnover.as_secs() / 60;
+        self
+    }
+
+    /// Parties of up to `size` guests stay this long.
+    pub fn with_dining_time(mut self, size: u32, duration: Duration) -> BookingRules {
+        let minutes = duration.as_secs() / 60;
+        match self
+            .durations
+            .binary_search_by_key(&size, |&(up_to, _)| up_to)
+        {
+            Ok(index) => self.durations[index].1 = minutes,
+            Err(index) => self.durations.insert(index, (size, minutes)),
+        }
+        self
+    }
+
+    /// How long parties bigger than all sizes given to
+    /// [`with_dining_time`](BookingRules::with_dining_time) stay.
+    pub fn with_default_dining_time(mut self, duration: Duration) -> BookingRules {
+        self.default_minutes = duration.as_secs() / 60;
+        self
+    }
+
+    pub fn dining_time(&self, size: u32) -> Duration {
+        let minutes = self
+            .durations
+            .iter()
+            .find(|&&(up_to, _)| size <= up_to)
+            .map_or(self.default_minutes, |&(_, minutes)| minutes);
+        Duration::from_secs(minutes * 60)
+    }
+
+    fn turnover(&self) -> Duration {
+        Duration::from_secs(self.turnover_minutes * 60)
+    }
+
+    fn is_open_for(&self, start: Time) -> bool {
+        let minute_of_day = start.minutes() - start.start_of_day().minutes();
+        (u64::from(self.opens)..=u64::from(self.last_seating)).contains(&minute_of_day)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReservationError {
+    InvalidPartySize(u32),
+    /// Before opening or after the last seating.
+    Closed(Time),
+    NoTableFits {
+        size: u32,
+    },
+    UnknownTable(u32),
+    TableTooSmall {
+        table: u32,
+        capacity: u32,
+    },
+    /// The table is booked by another reservation at that time.
+    Conflict {
+        table: u32,
+        with: ReservationId,
+    },
+    /// Every table that fits is booked at that time.
+    FullyBooked(Time),
+    UnknownReservation(ReservationId),
+}
+
+impl fmt::Display for ReservationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReservationError::InvalidPartySize(size) => {
+                write!(f, "a party can't have {size} guests")
+            }
+            ReservationError::Closed(time) => write!(f, "we don't seat guests at {time}"),
+            ReservationError::NoTableFits { size } => write!(f, "no table seats a party of {size}"),
+            ReservationError::UnknownTable(table) => write!(f, "there is no table {table}"),
+            ReservationError::TableTooSmall { table, capacity } => {
+                write!(f, "table {table} only seats {capacity}")
+            }
+            ReservationError::Conflict { table, with } => {
+                write!(f, "table {table} is already booked by {with}")
+            }
+            ReservationError::FullyBooked(time) => write!(f, "we are fully booked at {time}"),
+            ReservationError::UnknownReservation(id) => write!(f, "there is no reservation {id}"),
+        }
+    }
+}
+
+impl std::error::Error for ReservationError {}
+
+/// What to change about a reservation; `None` keeps it as it is.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Changes {
+    pub size: Option<u32>,
+    pub start: Option<Time>,
+    pub table: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct TableSpec {
+    number: u32,
+    capacity: u32,
+}
+
+/// All reservations, for the tables of a floor plan.
+///
+/// # Examples
+///
+/// ```
+/// use modules_restaurant::hosting::FloorPlan;
+/// use modules_restaurant::reservations::{BookingRules, ReservationBook, ReservationError};
+/// use modules_restaurant::time::Time;
+///
+/// let mut floor = FloorPlan::new();
+/// floor.add_table(1, 4);
+/// let mut book = ReservationBook::new(&floor, BookingRules::default());
+///
+/// let seven = Time::from_ymd_hm(2024, 6, 1, 19, 0);
+/// let ana = book.book("Ana", 4, seven, None).unwrap();
+/// // four guests stay 90 minutes, plus 15 to turn the table over
+/// let eight = Time::from_ymd_hm(2024, 6, 1, 20, 0);
+/// assert_eq!(book.book("Ben", 2, eight, None), Err(ReservationError::FullyBooked(eight)));
+/// let quarter_to_nine = Time::from_ymd_hm(2024, 6, 1, 20, 45);
+/// assert!(book.book("Ben", 2, quarter_to_nine, None).is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReservationBook {
+    tables: Vec<TableSpec>,
+    rules: BookingRules,
+    reservations: BTreeMap<ReservationId, Reservation>,
+    next_id: u32,
+}
+
+impl ReservationBook {
+    pub fn new(floor: &FloorPlan, rules: BookingRules) -> ReservationBook {
+        let tables = floor
+            .tables()
+            .map(|table| TableSpec {
+                number: table.number(),
+                capacity: table.capacity(),
+            })
+            .collect();
+        ReservationBook {
+            tables,
+            rules,
+            reservations: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn rules(&self) -> &BookingRules {
+        &self.rules
+    }
+
+    pub fn get(&self, id: ReservationId) -> Option<&Reservation> {
+        self.reservations.get(&id)
+    }
+
+    /// Book a table for a party. Without a table, the smallest free one
+    /// that fits is taken.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the restaurant doesn't seat guests at `start`, the table
+    /// doesn't fit or is booked, or no table is free.
+    pub fn book(
+        &mut self,
+        name: &str,
+        size: u32,
+        start: Time,
+        table: Option<u32>,
+    ) -> Result<ReservationId, ReservationError> {
+        let (table, end) = self.find_table(size, start, table, None)?;
+        self.next_id += 1;
+        let id = ReservationId(self.next_id);
+        let reservation = Reservation {
+            id,
+            name: String::from(name),
+            size,
+            table,
+            start,
+            end,
+        };
+        self.reservations.insert(id, reservation);
+        Ok(id)
+    }
+
+    /// # Errors
+    ///
+    /// Fails if there is no such reservation.
+    pub fn cancel(&mut self, id: ReservationId) -> Result<Reservation, ReservationError> {
+        self.reservations
+            .remove(&id)
+            .ok_or(ReservationError::UnknownReservation(id))
+    }
+
+    /// Change the size, time or table of a reservation. A party keeps its
+    /// table if it still fits and is free, unless another one is asked for.
+    /// Nothing changes if the new booking isn't possible.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no such reservation or, like
+    /// [`book`](ReservationBook::book), the changed one can't be booked.
+    pub fn modify(&mut self, id: ReservationId, changes: Changes) -> Result<(), ReservationError> {
+        let current = self
+            .get(id)
+            .ok_or(ReservationError::UnknownReservation(id))?;
+        let size = changes.size.unwrap_or(current.size);
+        let start = changes.start.unwrap_or(current.start);
+        let (table, end) = match changes.table {
+            Some(table) => self.find_table(size, start, Some(table), Some(id))?,
+            None => self
+                .find_table(size, start, Some(current.table), Some(id))
+                .or_else(|_| self.find_table(size, start, None, Some(id)))?,
+        };
+        let reservation = self.reservations.get_mut(&id).expect("it was found above");
+        reservation.size = size;
+        reservation.start = start;
+        reservation.table = table;
+        reservation.end = end;
+        Ok(())
+    }
+
+    // the table to book and when the party leaves it
+    fn find_table(
+        &self,
+        size: u32,
+        start: Time,
+        table: Option<u32>,
+        ignore: Option<ReservationId>,
+    ) -> Result<(u32, Time), ReservationError> {
+        if size == 0 {
+            return Err(ReservationError::InvalidPartySize(size));
+        }
+        if !self.rules.is_open_for(start) {
+            return Err(ReservationError::Closed(start));
+        }
+        let end = start + self.rules.dining_time(size);
+        if let Some(number) = table {
+            let spec = self
+                .tables
+                .iter()
+                .find(|spec| spec.number == number)
+                .ok_or(ReservationError::UnknownTable(number))?;
+            if spec.capacity < size {
+                return Err(ReservationError::TableTooSmall {
+                    table: number,
+                    capacity: spec.capacity,
+                });
+            }
+            return match self.conflict(number, start, end, ignore) {
+                Some(with) => Err(ReservationError::Conflict {
+                    table: number,
+                    with,
+                }),
+                None => Ok((number, end)),
+            };
+        }
+        let mut fitting = self
+            .tables
+            .iter()
+            .filter(|spec| spec.capacity >= size)
+            .peekable();
+        if fitting.peek().is_none() {
+            return Err(ReservationError::NoTableFits { size });
+        }
+        fitting
+            .filter(|spec| self.conflict(spec.number, start, end, ignore).is_none())
+            .min_by_key(|spec| (spec.capacity, spec.number))
+            .map(|spec| (spec.number, end))
+            .ok_or(ReservationError::FullyBooked(start))
+    }
+
+    // a reservation of the table whose time, with turnover, overlaps
+    fn conflict(
+        &self,
+        table: u32,
+        start: Time,
+        end: Time,
+        ignore: Option<ReservationId>,
+    ) -> Option<ReservationId> {
+        let turnover = self.rules.turnover();
+        self.reservations
+            .values()
+            .filter(|other| other.table == table && Some(other.id) != ignore)
+            .find(|other| start < other.end + turnover && other.start < end + turnover)
+            .map(|other| other.id)
+    }
+
+    /// The reservations of a day, by start time and then table.
+    pub fn schedule(&self, day: Time) -> Vec<&Reservation> {
+        let mut reservations: Vec<&Reservation> = self
+            .reservations
+            .values()
+            .filter(|reservation| reservation.start.day() == day.day())
+            .collect();
+        reservations.sort_by_key(|reservation| (reservation.start, reservation.table));
+        reservations
+    }
+
+    /// The times of a day at which a party of `size` can still book a
+    /// table.
+    pub fn availability(&self, day: Time, size: u32) -> Vec<Time> {
+        let midnight = day.start_of_day();
+        let slot = Duration::from_secs(self.rules.slot_minutes * 60);
+        let mut start = midnight + Duration::from_secs(u64::from(self.rules.opens) * 60);
+        let last = midnight + Duration::from_secs(u64::from(self.rules.last_seating) * 60);
+        let mut times = Vec::new();
+        while start <= last {
+            if self.find_table(size, start, None, None).is_ok() {
+                times.push(start);
+            }
+            start = start + slot;
+        }
+        times
+    }
+
+    /// Write the book to a JSON file, replacing it only once the new file
+    /// is complete.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let json = serde_json::to_string_pretty(self)?;
+        let partial = path.with_extension("partial");
+        fs::write(&partial, json)?;
+        fs::rename(&partial, path)
+    }
+
+    /// # Errors
+    ///
+    /// Fails if the file can't be read or isn't a reservation book.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<ReservationBook> {
+        let json = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::testing::{at, minutes};
+
+    /// Two two-tops and a six-top.
+    fn book() -> ReservationBook {
+        let mut floor = FloorPlan::new();
+        floor.add_table(1, 2);
+        floor.add_table(2, 2);
+        floor.add_table(3, 6);
+        ReservationBook::new(&floor, BookingRules::default())
+    }
+
+    #[test]
+    fn dining_time_depends_on_party_size() {
+        let rules = BookingRules::default()
+            .with_dining_time(2, minutes(60))
+            .with_dining_time(8, minutes(150))
+            .with_default_dining_time(minutes(180));
+        assert_eq!(rules.dining_time(1), minutes(60));
+        assert_eq!(rules.dining_time(4), minutes(90));
+        assert_eq!(rules.dining_time(7), minutes(150));
+        assert_eq!(rules.dining_time(12), minutes(180));
+    }
+    #[test]
+    fn books_the_smallest_free_table() {
+        let mut book = book();
+        let a = book.book("a", 2, at(19, 0), None).unwrap();
+        let b = book.book("b", 2, at(19, 0), None).unwrap();
+        let c = book.book("c", 2, at(19, 0), None).unwrap();
+        let tables: Vec<_> = [a, b, c]
+            .iter()
+            .map(|&id| book.get(id).unwrap().table)
+            .collect();
+        assert_eq!(tables, [1, 2, 3]);
+        assert_eq!(book.get(a).unwrap().end, at(20, 15));
+        assert_eq!(
+            book.book("d", 1, at(19, 30), None),
+            Err(ReservationError::FullyBooked(at(19, 30)))
+        );
+        assert_eq!(
+            book.book("e", 7, at(19, 30), None),
+            Err(ReservationError::NoTableFits { size: 7 })
+        );
+    }
+    #[test]
+    fn detects_overlaps_including_turnover() {
+        let mut book = book();
+        let first = book.book("first", 2, at(18, 0), Some(1)).unwrap();
+        // 18:00 + 75 minutes + 15 to turn the table over
+        let conflict = ReservationError::Conflict {
+            table: 1,
+            with: first,
+        };
+        assert_eq!(
+            book.book("x", 2, at(19, 15), Some(1)),
+            Err(conflict.clone())
+        );
+        assert!(book.book("back to back", 2, at(19, 30), Some(1)).is_ok());
+        // a booking before must leave time to turn over before 18:00 too
+        assert_eq!(book.book("x", 2, at(17, 0), Some(1)), Err(conflict));
+        let rules = BookingRules::default().with_turnover(Duration::ZERO);
+        let mut floor = FloorPlan::new();
+        floor.add_table(1, 2);
+        let mut book = ReservationBook::new(&floor, rules);
+        book.book("first", 2, at(18, 0), None).unwrap();
+        assert!(book.book("second", 2, at(19, 15), None).is_ok());
+        assert!(book.book("early", 2, at(17, 0), None).is_err());
+    }
+    #[test]
+    fn rejects_bookings_that_cant_happen() {
+        let mut book = book();
+        assert_eq!(
+            book.book("x", 2, at(16, 45), None),
+            Err(ReservationError::Closed(at(16, 45)))
+        );
+        assert_eq!(
+            book.book("x", 2, at(22, 15), None),
+            Err(ReservationError::Closed(at(22, 15)))
+        );
+        assert!(book.book("late", 2, at(22, 0), None).is_ok());
+        assert_eq!(
+            book.book("x", 0, at(19, 0), None),
+            Err(ReservationError::InvalidPartySize(0))
+        );
+        assert_eq!(
+            book.book("x", 2, at(19, 0), Some(9)),
+            Err(ReservationError::UnknownTable(9))
+        );
+        assert_eq!(
+            book.book("x", 4, at(19, 0), Some(1)),
+            Err(ReservationError::TableTooSmall {
+                table: 1,
+                capacity: 2
+            })
+        );
+    }
+    #[test]
+    fn cancelling_frees_the_table() {
+        let mut book = book();
+        let id = book.book("a", 6, at(19, 0), None).unwrap();
+        assert!(book.book("b", 5, at(19, 0), None).is_err());
+        assert_eq!(book.cancel(id).unwrap().name, "a");
+        assert_eq!(
+            book.cancel(id),
+            Err(ReservationError::UnknownReservation(id))
+        );
+        assert!(book.book("b", 5, at(19, 0), None).is_ok());
+    }
+    #[test]
+    fn modifications_keep_the_table_when_possible() {
+        let mut book = book();
+        let id = book.book("a", 2, at(19, 0), None).unwrap();
+        book.modify(
+            id,
+            Changes {
+                start: Some(at(19, 30)),
+                ..Changes::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            (book.get(id).unwrap().table, book.get(id).unwrap().end),
+            (1, at(20, 45))
+        );
+
+        // a bigger party needs the six-top
+        book.modify(
+            id,
+            Changes {
+                size: Some(5),
+                ..Changes::default()
+            },
+        )
+        .unwrap();
+        let reservation = book.get(id).unwrap();
+        assert_eq!((reservation.table, reservation.size), (3, 5));
+        assert_eq!(reservation.end, at(21, 15));
+        // it doesn't conflict with itself
+        book.modify(
+            id,
+            Changes {
+                start: Some(at(19, 45)),
+                ..Changes::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(book.get(id).unwrap().table, 3);
+    }
+    #[test]
+    fn failed_modifications_change_nothing() {
+        let mut book = book();
+        let a = book.book("a", 2, at(19, 0), Some(1)).unwrap();
+        let b = book.book("b", 2, at(19, 0), Some(2)).unwrap();
+        let before = book.clone();
+        assert_eq!(
+            book.modify(
+                b,
+                Changes {
+                    table: Some(1),
+                    ..Changes::default()
+                }
+            ),
+            Err(ReservationError::Conflict { table: 1, with: a })
+        );
+        assert_eq!(
+            book.modify(
+                b,
+                Changes {
+                    start: Some(at(23, 0)),
+                    ..Changes::default()
+                }
+            ),
+            Err(ReservationError::Closed(at(23, 0)))
+        );
+        assert_eq!(
+            book.modify(ReservationId(9), Changes::default()),
+            Err(ReservationError::UnknownReservation(ReservationId(9)))
+        );
+        assert_eq!(book, before);
+    }
+    #[test]
+    fn lists_the_days_availability() {
+        let rules = BookingRules::default()
+            .with_hours((18, 0), (20, 0))
+            .with_slot(minutes(30));
+        let mut floor = FloorPlan::new();
+        floor.add_table(1, 4);
+        let mut book = ReservationBook::new(&floor, rules);
+        let all = book.availability(at(12, 0), 2);
+        assert_eq!(
+            all,
+            [at(18, 0), at(18, 30), at(19, 0), at(19, 30), at(20, 0)]
+        );
+
+        // 18:30 until 20:00, plus turnover until 20:15
+        book.book("a", 4, at(18, 30), None).unwrap();
+        assert_eq!(book.availability(at(9, 0), 2), [] as [Time; 0]);
+        book.cancel(ReservationId(1)).unwrap();
+        book.book("a", 2, at(19, 30), None).unwrap();
+        // an earlier couple must be gone, table turned, by 19:30
+        assert_eq!(book.availability(at(9, 0), 2), [at(18, 0)]);
+        assert!(book.availability(at(9, 0), 5).is_empty());
+        // other days are still free
+        let next_day = Time::from_ymd_hm(2024, 6, 2, 9, 0);
+        assert_eq!(book.availability(next_day, 2).len(), 5);
+    }
+    #[test]
+    fn schedules_by_day() {
+        let mut book = book();
+        let late = book.book("late", 2, at(21, 0), None).unwrap();
+        let early = book.book("early", 6, at(17, 0), None).unwrap();
+        book.book("tomorrow", 2, Time::from_ymd_hm(2024, 6, 2, 19, 0), None)
+            .unwrap();
+        let ids: Vec<_> = book.schedule(at(0, 0)).iter().map(|r| r.id).collect();
+        assert_eq!(ids, [early, late]);
+    }
+    #[test]
+    fn saves_and_loads_the_book() {
+        let mut book = book();
+        book.book("Ana", 2, at(19, 0), None).unwrap();
+        book.book("Ben", 6, at(20, 0), None).unwrap();
+        let dir = std::env::temp_dir().join(format!("reservations-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("book.json");
+        book.save(&path).unwrap();
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains("\"2024-06-01 19:00\""));
+
+        let mut loaded = ReservationBook::load(&path).unwrap();
+        assert_eq!(loaded, book);
+        // ids keep counting after a restart
+        assert_eq!(loaded.book("Cy", 2, at(19, 0), None), Ok(ReservationId(3)));
+
+        fs::write(&path, "{ not json").unwrap();
+        let error = ReservationBook::load(&path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            ReservationBook::load(dir.join("missing.json"))
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
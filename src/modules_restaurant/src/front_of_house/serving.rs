@@ -1,5 +1,497 @@
-fn _take_order() {}
+//! Taking orders at the table and serving them when the kitchen is done.
+//!
+//! Every [`Order`] moves through a small state machine:
+//!
+//! ```text
+//! Placed --cook--> Cooking --finish--> Ready --serve--> Served
+//!                     ^                 | ^               |
+//!                     |              fix| |finish         |fix
+//!                     |                 v |               |
+//!                     |                Fixing <-----------+
+//!                     +-------- remake (from Ready or Served)
+//! ```
+//!
+//! A fix is a correction at the pass, like a missing side, while a remake
+//! sends the dish back to the kitchen to be cooked again. Any other
+//! transition is rejected with [`OrderError::InvalidTransition`].
 
-fn _serve_order() {}
+use std::collections::BTreeMap;
+use std::fmt;
 
-fn _take_payment() {}
+use serde::{Deserialize, Serialize};
+
+use crate::time::Time;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct OrderId(pub u32);
+
+impl fmt::Display for OrderId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// A line of an order, like "2x Burger (no onions)".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderItem {
+    pub name: String,
+    pub quantity: u32,
+    pub modifiers: Vec<String>,
+}
+
+impl OrderItem {
+    pub fn new(name: &str, quantity: u32) -> OrderItem {
+        OrderItem {
+            name: String::from(name),
+            quantity,
+            modifiers: Vec::new(),
+        }
+    }
+
+    pub fn with_modifier(mut self, modifier: &str) -> OrderItem {
+        self.modifiers.push(String::from(modifier));
+        self
+    }
+}
+
+impl fmt::Display for OrderItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x {}", self.quantity, self.name)?;
+        if !self.modifiers.is_empty() {
+            write!(f, " ({})", self.modifiers.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum OrderStatus {
+    Placed,
+    Cooking,
+    Ready,
+    Served,
+    Fixing,
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            OrderStatus::Placed => "placed",
+            OrderStatus::Cooking => "cooking",
+            OrderStatus::Ready => "ready",
+            OrderStatus::Served => "served",
+            OrderStatus::Fixing => "fixing",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Transition {
+    Cook,
+    Finish,
+    Serve,
+    Fix { reason: String },
+    Remake { reason: String },
+}
+
+impl Transition {
+    /// The status an order in `from` moves to, `None` if it can't.
+    pub fn apply_to(&self, from: OrderStatus) -> Option<OrderStatus> {
+        use OrderStatus::*;
+        match (from, self) {
+            (Placed, Transition::Cook) => Some(Cooking),
+            (Cooking | Fixing, Transition::Finish) => Some(Ready),
+            (Ready, Transition::Serve) => Some(Served),
+            (Ready | Served, Transition::Fix { .. }) => Some(Fixing),
+            (Ready | Served, Transition::Remake { .. }) => Some(Cooking),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Transition::Cook => f.write_str("cook"),
+            Transition::Finish => f.write_str("finish"),
+            Transition::Serve => f.write_str("serve"),
+            Transition::Fix { reason } => write!(f, "fix ({reason})"),
+            Transition::Remake { reason } => write!(f, "remake ({reason})"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderError {
+    NoItems,
+    /// An item was ordered zero times.
+    ZeroQuantity(String),
+    UnknownOrder(OrderId),
+    InvalidTransition {
+        order: OrderId,
+        from: OrderStatus,
+        transition: Transition,
+    },
+}
+
+impl fmt::Display for OrderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OrderError::NoItems => f.write_str("an order needs at least one item"),
+            OrderError::ZeroQuantity(name) => write!(f, "can't order zero of {name}"),
+            OrderError::UnknownOrder(id) => write!(f, "there is no order {id}"),
+            OrderError::InvalidTransition {
+                order,
+                from,
+                transition,
+            } => write!(f, "order {order} is {from}, it can't {transition}"),
+        }
+    }
+}
+
+impl std::error::Error for OrderError {}
+
+/// An entry of an order's history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusChange {
+    /// `None` for placing the order.
+    pub transition: Option<Transition>,
+    pub status: OrderStatus,
+    pub at: Time,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Order {
+    id: OrderId,
+    table: u32,
+    items: Vec<OrderItem>,
+    history: Vec<StatusChange>,
+}
+
+impl Order {
+    pub fn id(&self) -> OrderId {
+        self.id
+    }
+
+    pub fn table(&self) -> u32 {
+        self.table
+    }
+
+    pub fn items(&self) -> &[OrderItem] {
+        &self.items
+    }
+
+    pub fn status(&self) -> OrderStatus {
+        self.history.last().expect("an order has a history").status
+    }
+
+    pub fn placed_at(&self) -> Time {
+        self.history[0].at
+    }
+
+    /// Every status the order went through, oldest first.
+    pub fn history(&self) -> &[StatusChange] {
+        &self.history
+    }
+
+    /// How often the kitchen had to cook the order again.
+    pub fn remakes(&self) -> usize {
+        self.history
+            .iter()
+            .filter(|change| matches!(change.transition, Some(Transition::Remake { .. })))
+            .count()
+    }
+
+    /// Move the order to its next status.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`OrderError::InvalidTransition`] if the order can't make
+    /// that transition from its current status, which stays unchanged.
+    pub fn apply(&mut self, transition: Transition, at: Time) -> Result<OrderStatus, OrderError> {
+        let from = self.status();
+        let Some(status) = transition.apply_to(from) else {
+            return Err(OrderError::InvalidTransition {
+                order: self.id,
+                from,
+                transition,
+            });
+        };
+        self.history.push(StatusChange {
+            transition: Some(transition),
+            status,
+            at,
+        });
+        Ok(status)
+    }
+}
+
+/// All orders of the day, taken by the waiters.
+///
+/// # Examples
+///
+/// ```
+/// use modules_restaurant::serving::{OrderItem, OrderStatus, Orders, Transition};
+/// use modules_restaurant::time::Time;
+///
+/// let now = Time::from_ymd_hm(2024, 6, 1, 19, 0);
+/// let mut orders = Orders::new();
+/// let id = orders
+///     .take_order(4, vec![OrderItem::new("Soup", 2).with_modifier("no croutons")], now)
+///     .unwrap();
+/// orders.apply(id, Transition::Cook, now).unwrap();
+/// orders.apply(id, Transition::Finish, now).unwrap();
+/// assert_eq!(orders.serve_order(id, now), Ok(OrderStatus::Served));
+/// // served orders can't be served again
+/// assert!(orders.serve_order(id, now).is_err());
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Orders {
+    orders: BTreeMap<OrderId, Order>,
+    next_id: u32,
+}
+
+impl Orders {
+    pub fn new() -> Orders {
+        Orders::default()
+    }
+
+    /// # Errors
+    ///
+    /// Fails if there are no items or one of them has a quantity of zero.
+    pub fn take_order(
+        &mut self,
+        table: u32,
+        items: Vec<OrderItem>,
+        now: Time,
+    ) -> Result<OrderId, OrderError> {
+        if items.is_empty() {
+            return Err(OrderError::NoItems);
+        }
+        if let Some(item) = items.iter().find(|item| item.quantity == 0) {
+            return Err(OrderError::ZeroQuantity(item.name.clone()));
+        }
+        self.next_id += 1;
+        let id = OrderId(self.next_id);
+        let order = Order {
+            id,
+            table,
+            items,
+            history: vec![StatusChange {
+                transition: None,
+                status: OrderStatus::Placed,
+                at: now,
+            }],
+        };
+        self.orders.insert(id, order);
+        Ok(id)
+    }
+
+    pub fn get(&self, id: OrderId) -> Option<&Order> {
+        self.orders.get(&id)
+    }
+
+    /// See [`Order::apply`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no such order or it can't make the transition.
+    pub fn apply(
+        &mut self,
+        id: OrderId,
+        transition: Transition,
+        now: Time,
+    ) -> Result<OrderStatus, OrderError> {
+        self.orders
+            .get_mut(&id)
+            .ok_or(OrderError::UnknownOrder(id))?
+            .apply(transition, now)
+    }
+
+    /// Bring a ready order to the table.
+    ///
+    /// # Errors
+    ///
+    /// Fails if there is no such order or it isn't ready.
+    pub fn serve_order(&mut self, id: OrderId, now: Time) -> Result<OrderStatus, OrderError> {
+        self.apply(id, Transition::Serve, now)
+    }
+
+    /// Orders in the order they were taken.
+    pub fn iter(&self) -> impl Iterator<Item = &Order> {
+        self.orders.values()
+    }
+
+    pub fn for_table(&self, table: u32) -> impl Iterator<Item = &Order> {
+        self.iter().filter(move |order| order.table == table)
+    }
+
+    /// Orders that haven't been served yet.
+    pub fn open(&self) -> impl Iterator<Item = &Order> {
+        self.iter()
+            .filter(|order| order.status() != OrderStatus::Served)
+    }
+
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::testing::at;
+
+    fn burger_order(orders: &mut Orders) -> OrderId {
+        let items = vec![
+            OrderItem::new("Burger", 2)
+                .with_modifier("no onions")
+                .with_modifier("extra cheese"),
+            OrderItem::new("Fries", 1),
+        ];
+        orders.take_order(7, items, at(19, 0)).unwrap()
+    }
+
+    fn fix(reason: &str) -> Transition {
+        Transition::Fix {
+            reason: String::from(reason),
+        }
+    }
+
+    fn remake(reason: &str) -> Transition {
+        Transition::Remake {
+            reason: String::from(reason),
+        }
+    }
+
+    #[test]
+    fn goes_from_placed_to_served() {
+        let mut orders = Orders::new();
+        let id = burger_order(&mut orders);
+        assert_eq!(orders.get(id).unwrap().status(), OrderStatus::Placed);
+        assert_eq!(
+            orders.apply(id, Transition::Cook, at(19, 1)),
+            Ok(OrderStatus::Cooking)
+        );
+        assert_eq!(
+            orders.apply(id, Transition::Finish, at(19, 12)),
+            Ok(OrderStatus::Ready)
+        );
+        assert_eq!(orders.serve_order(id, at(19, 13)), Ok(OrderStatus::Served));
+
+        let order = orders.get(id).unwrap();
+        let statuses: Vec<_> = order
+            .history()
+            .iter()
+            .map(|change| (change.status, change.at))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                (OrderStatus::Placed, at(19, 0)),
+                (OrderStatus::Cooking, at(19, 1)),
+                (OrderStatus::Ready, at(19, 12)),
+                (OrderStatus::Served, at(19, 13)),
+            ]
+        );
+        assert_eq!(order.placed_at(), at(19, 0));
+        assert_eq!(order.table(), 7);
+        assert_eq!(
+            order.items()[0].to_string(),
+            "2x Burger (no onions, extra cheese)"
+        );
+        assert_eq!(order.items()[1].to_string(), "1x Fries");
+    }
+    #[test]
+    fn rejects_invalid_transitions() {
+        let mut orders = Orders::new();
+        let id = burger_order(&mut orders);
+        let error = orders.serve_order(id, at(19, 1)).unwrap_err();
+        assert_eq!(
+            error,
+            OrderError::InvalidTransition {
+                order: id,
+                from: OrderStatus::Placed,
+                transition: Transition::Serve,
+            }
+        );
+        assert_eq!(error.to_string(), "order #1 is placed, it can't serve");
+        assert!(orders.apply(id, Transition::Finish, at(19, 1)).is_err());
+        assert!(orders.apply(id, fix("cold"), at(19, 1)).is_err());
+
+        orders.apply(id, Transition::Cook, at(19, 1)).unwrap();
+        assert!(orders.apply(id, Transition::Cook, at(19, 2)).is_err());
+        assert!(orders.apply(id, remake("burnt"), at(19, 2)).is_err());
+        // failed transitions leave no trace
+        assert_eq!(orders.get(id).unwrap().history().len(), 2);
+        assert_eq!(
+            orders.apply(OrderId(9), Transition::Cook, at(19, 1)),
+            Err(OrderError::UnknownOrder(OrderId(9)))
+        );
+    }
+    #[test]
+    fn fixes_and_remakes() {
+        let mut orders = Orders::new();
+        let id = burger_order(&mut orders);
+        for transition in [Transition::Cook, Transition::Finish, Transition::Serve] {
+            orders.apply(id, transition, at(19, 5)).unwrap();
+        }
+        // a missing side is fixed at the pass and served again
+        assert_eq!(
+            orders.apply(id, fix("forgot the fries"), at(19, 6)),
+            Ok(OrderStatus::Fixing)
+        );
+        assert!(orders.serve_order(id, at(19, 7)).is_err());
+        assert_eq!(
+            orders.apply(id, Transition::Finish, at(19, 7)),
+            Ok(OrderStatus::Ready)
+        );
+        // an overcooked burger goes back into the kitchen
+        assert_eq!(
+            orders.apply(id, remake("well done"), at(19, 8)),
+            Ok(OrderStatus::Cooking)
+        );
+        orders.apply(id, Transition::Finish, at(19, 18)).unwrap();
+        orders.serve_order(id, at(19, 19)).unwrap();
+
+        let order = orders.get(id).unwrap();
+        assert_eq!(order.remakes(), 1);
+        assert_eq!(order.history()[6].transition, Some(remake("well done")));
+        assert_eq!(order.status(), OrderStatus::Served);
+    }
+    #[test]
+    fn tracks_orders_per_table() {
+        let mut orders = Orders::new();
+        assert_eq!(
+            orders.take_order(1, Vec::new(), at(19, 0)),
+            Err(OrderError::NoItems)
+        );
+        let items = vec![OrderItem::new("Salad", 1), OrderItem::new("Steak", 0)];
+        assert_eq!(
+            orders.take_order(1, items, at(19, 0)),
+            Err(OrderError::ZeroQuantity(String::from("Steak")))
+        );
+        let first = burger_order(&mut orders);
+        let other = orders
+            .take_order(3, vec![OrderItem::new("Salad", 1)], at(19, 1))
+            .unwrap();
+        let second = burger_order(&mut orders);
+        let table_7: Vec<_> = orders.for_table(7).map(Order::id).collect();
+        assert_eq!(table_7, [first, second]);
+
+        orders.apply(other, Transition::Cook, at(19, 2)).unwrap();
+        orders.apply(other, Transition::Finish, at(19, 3)).unwrap();
+        orders.serve_order(other, at(19, 4)).unwrap();
+        assert_eq!(
+            orders.open().map(Order::id).collect::<Vec<_>>(),
+            [first, second]
+        );
+        assert_eq!(orders.len(), 3);
+    }
+}
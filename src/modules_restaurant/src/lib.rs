@@ -1,15 +1,23 @@
 mod front_of_house;
 
-fn _deliver_order() {}
-
 mod back_of_house;
 
-// bring the parent module into scope, so when doing "hosting::add_to_waitlist()"
+pub mod money;
+pub mod reports;
+pub mod time;
+
+// bring the parent module into scope, so when doing "hosting::Host::new()"
 // it is clear where the function comes from (not locally defined therefore)
 // add pub if users of our module should be able to use it too (re-exporting)
-// this will shorten "restaurant::front_of_house::hosting::add_to_waitlist()"
-// to "restaurant::hosting::add_to_waitlist()"
+// this will shorten "restaurant::front_of_house::hosting::Host::new()"
+// to "restaurant::hosting::Host::new()"
+pub use crate::front_of_house::billing;
 pub use crate::front_of_house::hosting;
+pub use crate::front_of_house::reservations;
+pub use crate::front_of_house::serving;
+// "self::" is the same as starting from the current module
+pub use self::back_of_house::kitchen;
+pub use self::back_of_house::menu;
 // BUT, use the full path for structs/emums
 use std::collections::HashMap;
 // If there are two items with the same name you need to "use" parent module
@@ -27,14 +35,63 @@ use std::collections::*;
 
 pub fn eat_at_restaurant() {
     // Absolute path
-    crate::front_of_house::hosting::add_to_waitlist();
+    let mut floor = crate::front_of_house::hosting::FloorPlan::new();
+    floor.add_table(1, 2);
+    floor.add_table(2, 4);
     // Relative path
-    front_of_house::hosting::add_to_waitlist();
+    let mut host = front_of_house::hosting::Host::new(floor);
+    let now = time::Time::now();
     // Path possible thanks to the usage of "use" above
-    hosting::add_to_waitlist();
+    let party: Result<hosting::PartyId, hosting::HostError> =
+        host.add_to_waitlist("Ferris", 2, now);
+    match party {
+        Ok(party) => println!("Party {} is on the waitlist", party),
+        Err(e) => println!("Sorry: {}", e),
+    }
+    for call in host.call_parties(now) {
+        println!("{}, your table {} is ready", call.party.name, call.table);
+        let _ = host.seat(call.party.id, now);
+    }
+    match host.quote(4, now) {
+        Ok(wait) => println!("A party of 4 waits about {} minutes", wait.as_secs() / 60),
+        Err(e) => println!("Sorry: {}", e),
+    }
+
+    // Take an order and send it to the kitchen
+    let mut orders = serving::Orders::new();
+    let items = vec![
+        serving::OrderItem::new("Burger", 1).with_modifier("no onions"),
+        serving::OrderItem::new("Fries", 1),
+    ];
+    let mut kitchen = kitchen::Kitchen::new().with_route("Fries", kitchen::Station::Fryer);
+    if let Ok(order) = orders.take_order(1, items, now) {
+        let tickets = kitchen.fire(&mut orders, order, now).unwrap_or_default();
+        for ticket in tickets {
+            // the order is ready once every station bumped its ticket
+            if let Ok(status) = kitchen.bump(&mut orders, ticket, now) {
+                println!("Order {} is {}", order, status);
+            }
+        }
+        if let Err(e) = orders.serve_order(order, now) {
+            println!("Can't serve: {}", e);
+        }
+    }
+
+    // and pay for it, with 8.25% tax and an 18% tip
+    if let Ok(menu) = menu::Menu::from_toml(include_str!("../menu.toml")) {
+        let tax = billing::TaxRules::new(money::Rate(825));
+        if let Ok(mut bill) = billing::Bill::for_orders(1, orders.for_table(1), &menu, tax) {
+            bill.set_tip(billing::Tip::Percent(money::Rate::percent(18)));
+            print!("{}", bill.receipt());
+        }
+    }
 
     // Order a breakfast in the summer with Rye toast
     let mut meal = back_of_house::Breakfast::summer("Rye");
+    // or with whatever fruit is in season on the menu today
+    if let Ok(menu) = menu::Menu::from_toml(include_str!("../menu.toml")) {
+        meal = back_of_house::Breakfast::in_season("Rye", &menu, now);
+    }
     // Change our mind about what bread we'd like
     meal.toast = String::from("Wheat");
     println!("I'd like {} toast please", meal.toast);
@@ -0,0 +1,341 @@
+//! Amounts of money as whole cents, so sums never pick up rounding errors.
+
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// An amount in cents, negative for discounts and change.
+///
+/// # Examples
+///
+/// ```
+/// use modules_restaurant::money::Cents;
+/// let coffee: Cents = "2.50".parse().unwrap();
+/// assert_eq!(coffee * 3, Cents(750));
+/// assert_eq!((coffee * 3).to_string(), "$7.50");
+/// ```
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Cents(pub i64);
+
+impl Cents {
+    pub const ZERO: Cents = Cents(0);
+
+    pub fn from_dollars(dollars: i64) -> Cents {
+        Cents(dollars * 100)
+    }
+
+    pub fn abs(self) -> Cents {
+        Cents(self.0.abs())
+    }
+}
+
+impl fmt::Display for Cents {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let cents = self.0.unsigned_abs();
+        let amount = format!("{sign}${}.{:02}", cents / 100, cents % 100);
+        // so amounts can be right-aligned in receipts
+        f.pad(&amount)
+    }
+}
+
+/// Error of parsing an amount that isn't like `12`, `12.5` or `12.50`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseCentsError(String);
+
+impl fmt::Display for ParseCentsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid amount `{}`", self.0)
+    }
+}
+
+impl std::error::Error for ParseCentsError {}
+
+impl FromStr for Cents {
+    type Err = ParseCentsError;
+
+    /// Parses dollars with at most two decimals, with an optional `$`.
+    fn from_str(s: &str) -> Result<Cents, ParseCentsError> {
+        let error = || ParseCentsError(String::from(s));
+        let (negative, amount) = match s.trim().strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.trim()),
+        };
+        let amount = amount.strip_prefix('$').unwrap_or(amount);
+        let (dollars, fraction) = match amount.split_once('.') {
+            Some((_, "")) => return Err(error()),
+            Some(parts) => parts,
+            None => (amount, ""),
+        };
+        let digits = |text: &str| !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit());
+        if !digits(dollars) || (!fraction.is_empty() && !digits(fraction)) || fraction.len() > 2 {
+            return Err(error());
+        }
+        let dollars: i64 = dollars.parse().map_err(|_| error())?;
+        let fraction: i64 = format!("{fraction:0<2}").parse().map_err(|_| error())?;
+        let cents = dollars
+            .checked_mul(100)
+            .and_then(|c| c.checked_add(fraction))
+            .ok_or_else(error)?;
+        Ok(Cents(if negative { -cents } else { cents }))
+    }
+}
+
+impl Add for Cents {
+    type Output = Cents;
+
+    fn add(self, other: Cents) -> Cents {
+        Cents(self.0 + other.0)
+    }
+}
+
+impl AddAssign for Cents {
+    fn add_assign(&mut self, other: Cents) {
+        self.0 += other.0;
+    }
+}
+
+impl Sub for Cents {
+    type Output = Cents;
+
+    fn sub(self, other: Cents) -> Cents {
+        Cents(self.0 - other.0)
+    }
+}
+
+impl SubAssign for Cents {
+    fn sub_assign(&mut self, other: Cents) {
+        self.0 -= other.0;
+    }
+}
+
+impl Neg for Cents {
+    type Output = Cents;
+
+    fn neg(self) -> Cents {
+        Cents(-self.0)
+    }
+}
+
+impl Mul<u32> for Cents {
+    type Output = Cents;
+
+    fn mul(self, quantity: u32) -> Cents {
+        Cents(self.0 * i64::from(quantity))
+    }
+}
+
+impl Sum for Cents {
+    fn sum<I: Iterator<Item = Cents>>(iter: I) -> Cents {
+        iter.fold(Cents::ZERO, Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Cents> for Cents {
+    fn sum<I: Iterator<Item = &'a Cents>>(iter: I) -> Cents {
+        iter.copied().sum()
+    }
+}
+
+/// A percentage in basis points, so 8.25% is `Rate(825)`.
+///
+/// # Examples
+///
+/// ```
+/// use modules_restaurant::money::{Cents, Rate};
+/// let tax: Rate = "8.25%".parse().unwrap();
+/// assert_eq!(tax.of(Cents(100)), Cents(8)); // 8.25 cents
+/// assert_eq!(tax.of(Cents(200)), Cents(17)); // 16.5 cents round up
+/// ```
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Rate(pub u32);
+
+impl Rate {
+    pub fn percent(percent: u32) -> Rate {
+        Rate(percent * 100)
+    }
+
+    /// This share of `amount`, rounded to the nearest cent with halves
+    /// rounded away from zero.
+    pub fn of(self, amount: Cents) -> Cents {
+        let scaled = i128::from(amount.0) * i128::from(self.0);
+        let rounded = (scaled.abs() + 5_000) / 10_000;
+        let cents = i64::try_from(rounded).expect("amount is too large");
+        Cents(if scaled < 0 { -cents } else { cents })
+    }
+}
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (whole, fraction) = (self.0 / 100, self.0 % 100);
+        if fraction == 0 {
+            write!(f, "{whole}%")
+        } else if fraction % 10 == 0 {
+            write!(f, "{whole}.{}%", fraction / 10)
+        } else {
+            write!(f, "{whole}.{fraction:02}%")
+        }
+    }
+}
+
+impl FromStr for Rate {
+    type Err = ParseCentsError;
+
+    /// Parses a percentage with at most two decimals, like `8.25%`.
+    fn from_str(s: &str) -> Result<Rate, ParseCentsError> {
+        let trimmed = s.trim();
+        let percent = trimmed.strip_suffix('%').unwrap_or(trimmed);
+        // same digits as an amount of dollars
+        match percent.parse::<Cents>() {
+            Ok(Cents(basis_points)) if !percent.starts_with(['-', '$']) => {
+                u32::try_from(basis_points)
+                    .map(Rate)
+                    .map_err(|_| ParseCentsError(String::from(s)))
+            }
+            _ => Err(ParseCentsError(String::from(s))),
+        }
+    }
+}
+
+/// Split `total` into parts proportional to `weights` that add up to
+/// `total` exactly. Cents left over after rounding down go to the parts
+/// with the largest remainders, earlier parts first on ties; with no
+/// weight at all the parts are equal.
+///
+/// # Panics
+///
+/// Panics if `total` or a weight is negative.
+///
+/// # Examples
+///
+/// ```
+/// use modules_restaurant::money::{allocate, Cents};
+/// let shares = allocate(Cents(100), &[Cents(1), Cents(1), Cents(1)]);
+/// assert_eq!(shares, [Cents(34), Cents(33), Cents(33)]);
+/// ```
+pub fn allocate(total: Cents, weights: &[Cents]) -> Vec<Cents> {
+    assert!(total >= Cents::ZERO, "can't allocate a negative amount");
+    assert!(
+        weights.iter().all(|weight| *weight >= Cents::ZERO),
+        "weights can't be negative"
+    );
+    if weights.is_empty() {
+        return Vec::new();
+    }
+    let mut weights: Vec<i128> = weights.iter().map(|weight| i128::from(weight.0)).collect();
+    if weights.iter().all(|weight| *weight == 0) {
+        weights.fill(1);
+    }
+    let sum: i128 = weights.iter().sum();
+    let total = i128::from(total.0);
+    let mut parts: Vec<i128> = weights.iter().map(|weight| total * weight / sum).collect();
+    let mut order: Vec<usize> = (0..weights.len()).collect();
+    // stable, so ties keep the earlier part first
+    order.sort_by_key(|&i| std::cmp::Reverse(total * weights[i] % sum));
+    let left_over = total - parts.iter().sum::<i128>();
+    for &i in order.iter().take(left_over as usize) {
+        parts[i] += 1;
+    }
+    parts
+        .into_iter()
+        .map(|part| Cents(i64::try_from(part).expect("a part is at most the total")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dollars_and_cents() {
+        for (text, cents) in [
+            ("12", 1200),
+            ("12.5", 1250),
+            ("$0.05", 5),
+            ("-3.10", -310),
+            (" 7.00 ", 700),
+        ] {
+            assert_eq!(text.parse(), Ok(Cents(cents)), "{text}");
+        }
+        for invalid in [
+            "",
+            "1.234",
+            "abc",
+            "1.",
+            ".5",
+            "1.-5",
+            "$",
+            "99999999999999999999",
+        ] {
+            assert!(invalid.parse::<Cents>().is_err(), "{invalid}");
+        }
+    }
+    #[test]
+    fn displays_with_two_decimals() {
+        assert_eq!(Cents(0).to_string(), "$0.00");
+        assert_eq!(Cents(5).to_string(), "$0.05");
+        assert_eq!(Cents(123_456).to_string(), "$1234.56");
+        assert_eq!(Cents(-250).to_string(), "-$2.50");
+        assert_eq!(format!("{:>8}", Cents(250)), "   $2.50");
+    }
+    #[test]
+    fn arithmetic() {
+        let mut total: Cents = [Cents(199), Cents(1)].iter().sum();
+        total += Cents::from_dollars(3);
+        total -= Cents(50);
+        assert_eq!(total, Cents(450));
+        assert_eq!(-total, Cents(-450));
+        assert_eq!((Cents(10) - Cents(25)).abs(), Cents(15));
+    }
+    #[test]
+    fn rates_round_half_away_from_zero() {
+        let rate = Rate(825);
+        assert_eq!(rate.to_string(), "8.25%");
+        assert_eq!(Rate::percent(20).to_string(), "20%");
+        assert_eq!(Rate(750).to_string(), "7.5%");
+        assert_eq!(rate.of(Cents(1)), Cents(0)); // 0.0825
+        assert_eq!(rate.of(Cents(200)), Cents(17)); // 16.5
+        assert_eq!(rate.of(Cents(-200)), Cents(-17));
+        assert_eq!(Rate::percent(10).of(Cents(5)), Cents(1)); // 0.5
+        assert_eq!(Rate::percent(10).of(Cents(4)), Cents(0)); // 0.4
+        for (text, rate) in [("8.25%", 825), ("15", 1500), ("0.5 %", 50)] {
+            assert_eq!(text.parse(), Ok(Rate(rate)), "{text}");
+        }
+        for invalid in ["-5%", "$5", "8.125%", "%"] {
+            assert!(invalid.parse::<Rate>().is_err(), "{invalid}");
+        }
+    }
+    #[test]
+    fn allocation_adds_up_exactly() {
+        let thirds = allocate(Cents(1000), &[Cents(1); 3]);
+        assert_eq!(thirds, [Cents(334), Cents(333), Cents(333)]);
+        let shares = allocate(Cents(10), &[Cents(1), Cents(2), Cents(7)]);
+        assert_eq!(shares, [Cents(1), Cents(2), Cents(7)]);
+        // 1.5 and 1.5: the tie goes to the first part
+        assert_eq!(
+            allocate(Cents(3), &[Cents(5), Cents(5)]),
+            [Cents(2), Cents(1)]
+        );
+        // the largest remainder wins: 0.3, 1.7 and 1.0
+        let shares = allocate(Cents(3), &[Cents(3), Cents(17), Cents(10)]);
+        assert_eq!(shares, [Cents(0), Cents(2), Cents(1)]);
+        assert_eq!(
+            allocate(Cents(5), &[Cents(0), Cents(0)]),
+            [Cents(3), Cents(2)]
+        );
+        assert_eq!(allocate(Cents(5), &[]), []);
+        for total in 0..200 {
+            let shares = allocate(Cents(total), &[Cents(13), Cents(29), Cents(58)]);
+            assert_eq!(shares.iter().sum::<Cents>(), Cents(total));
+        }
+    }
+}
@@ -0,0 +1,675 @@
+//! End-of-day reports from the day's sales.
+//!
+//! A [`DayReport`] sums up [`Sale`]s by category, item, kitchen station and
+//! table. Each part of it is a [`Sheet`] that can be exported as CSV or
+//! printed as a text table.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hosting::PartyId;
+use crate::kitchen::{Station, TicketId};
+use crate::money::Cents;
+use crate::time::Time;
+
+/// A line of a paid bill, with when it was cooked and when its party sat.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sale {
+    pub item: String,
+    pub category: String,
+    pub quantity: u32,
+    /// Per item, before discounts and tax.
+    pub price: Cents,
+    pub station: Station,
+    /// The kitchen ticket the item was cooked on.
+    pub ticket: TicketId,
+    pub table: u32,
+    pub party: PartyId,
+    pub seated: Time,
+    pub left: Time,
+    /// When the kitchen got the ticket and bumped it.
+    pub fired: Time,
+    pub bumped: Time,
+}
+
+impl Sale {
+    pub fn revenue(&self) -> Cents {
+        self.price * self.quantity
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CategorySales {
+    pub category: String,
+    pub quantity: u32,
+    pub revenue: Cents,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemSales {
+    pub item: String,
+    pub category: String,
+    pub quantity: u32,
+    pub revenue: Cents,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StationTimes {
+    pub station: Station,
+    pub tickets: usize,
+    pub average: Duration,
+    pub longest: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableTurnover {
+    pub table: u32,
+    pub parties: usize,
+    /// How long parties sat at the table.
+    pub average_stay: Duration,
+    pub revenue: Cents,
+}
+
+/// A day's sales, summed up.
+///
+/// # Examples
+///
+/// ```
+/// use modules_restaurant::hosting::PartyId;
+/// use modules_restaurant::kitchen::{Station, TicketId};
+/// use modules_restaurant::money::Cents;
+/// use modules_restaurant::reports::{DayReport, Sale};
+/// use modules_restaurant::time::Time;
+///
+/// let at = |minute| Time::from_ymd_hm(2024, 6, 1, 19, minute);
+/// let sale = Sale {
+///     item: String::from("Burger"),
+///     category: String::from("Mains"),
+///     quantity: 2,
+///     price: Cents(1450),
+///     station: Station::Grill,
+///     ticket: TicketId(1),
+///     table: 1,
+///     party: PartyId(1),
+///     seated: at(0),
+///     left: at(50),
+///     fired: at(5),
+///     bumped: at(17),
+/// };
+/// let report = DayReport::new(&[sale]);
+/// assert_eq!(report.revenue(), Cents(2900));
+/// assert_eq!(report.to_csv().lines().nth(1), Some("Category,Items,Revenue"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DayReport {
+    categories: Vec<CategorySales>,
+    items: Vec<ItemSales>,
+    stations: Vec<StationTimes>,
+    tables: Vec<TableTurnover>,
+    top: usize,
+}
+
+impl DayReport {
+    pub fn new(sales: &[Sale]) -> DayReport {
+        DayReport {
+            categories: by_category(sales),
+            items: by_item(sales),
+            stations: by_station(sales),
+            tables: by_table(sales),
+            top: 5,
+        }
+    }
+
+    /// How many of the best selling items [`sheets`](DayReport::sheets)
+    /// lists, five unless told otherwise.
+    pub fn with_top_items(mut self, top: usize) -> DayReport {
+        self.top = top;
+        self
+    }
+
+    pub fn revenue(&self) -> Cents {
+        self.categories
+            .iter()
+            .map(|category| category.revenue)
+            .sum()
+    }
+
+    /// Categories, the one that made the most first.
+    pub fn categories(&self) -> &[CategorySales] {
+        &self.categories
+    }
+
+    /// Items, the most sold first and then the one that made the most.
+    pub fn items(&self) -> &[ItemSales] {
+        &self.items
+    }
+
+    pub fn top_items(&self) -> &[ItemSales] {
+        &self.items[..self.top.min(self.items.len())]
+    }
+
+    /// Stations that cooked something, in the order of [`Station::ALL`].
+    pub fn stations(&self) -> &[StationTimes] {
+        &self.stations
+    }
+
+    /// Tables that had guests, by number.
+    pub fn tables(&self) -> &[TableTurnover] {
+        &self.tables
+    }
+
+    pub fn sheets(&self) -> Vec<Sheet> {
+        let mut categories = Sheet::new("Revenue by category", &["Category", "Items", "Revenue"]);
+        for category in &self.categories {
+            categories.add_row(vec![
+                Cell::Text(category.category.clone()),
+                Cell::Count(category.quantity.into()),
+                Cell::Money(category.revenue),
+            ]);
+        }
+        categories.add_row(vec![
+            Cell::Text(String::from("Total")),
+            Cell::Count(self.categories.iter().map(|c| u64::from(c.quantity)).sum()),
+            Cell::Money(self.revenue()),
+        ]);
+
+        let mut items = Sheet::new("Top items", &["Item", "Category", "Sold", "Revenue"]);
+        for item in self.top_items() {
+            items.add_row(vec![
+                Cell::Text(item.item.clone()),
+                Cell::Text(item.category.clone()),
+                Cell::Count(item.quantity.into()),
+                Cell::Money(item.revenue),
+            ]);
+        }
+
+        let mut stations = Sheet::new(
+            "Ticket times",
+            &["Station", "Tickets", "Average (min)", "Longest (min)"],
+        );
+        for station in &self.stations {
+            stations.add_row(vec![
+                Cell::Text(station.station.to_string()),
+                Cell::Count(station.tickets as u64),
+                Cell::Minutes(station.average),
+                Cell::Minutes(station.longest),
+            ]);
+        }
+
+        let mut tables = Sheet::new(
+            "Table turnover",
+            &["Table", "Parties", "Average stay (min)", "Revenue"],
+        );
+        for table in &self.tables {
+            tables.add_row(vec![
+                Cell::Count(table.table.into()),
+                Cell::Count(table.parties as u64),
+                Cell::Minutes(table.average_stay),
+                Cell::Money(table.revenue),
+            ]);
+        }
+        vec![categories, items, stations, tables]
+    }
+
+    /// Every sheet as CSV, after a line with its title and separated by
+    /// empty lines.
+    pub fn to_csv(&self) -> String {
+        let sheets: Vec<String> = self
+            .sheets()
+            .iter()
+            .map(|sheet| format!("{}\n{}", csv_field(&sheet.title), sheet.to_csv()))
+            .collect();
+        sheets.join("\n")
+    }
+}
+
+impl fmt::Display for DayReport {
+    /// Every sheet as a text table.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, sheet) in self.sheets().iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{sheet}")?;
+        }
+        Ok(())
+    }
+}
+
+fn by_category(sales: &[Sale]) -> Vec<CategorySales> {
+    let mut categories: Vec<CategorySales> = Vec::new();
+    for sale in sales {
+        match categories.iter_mut().find(|c| c.category == sale.category) {
+            Some(category) => {
+                category.quantity += sale.quantity;
+                category.revenue += sale.revenue();
+            }
+            None => categories.push(CategorySales {
+                category: sale.category.clone(),
+                quantity: sale.quantity,
+                revenue: sale.revenue(),
+            }),
+        }
+    }
+    categories.sort_by(|a, b| {
+        b.revenue
+            .cmp(&a.revenue)
+            .then_with(|| a.category.cmp(&b.category))
+    });
+    categories
+}
+
+fn by_item(sales: &[Sale]) -> Vec<ItemSales> {
+    let mut items: Vec<ItemSales> = Vec::new();
+    for sale in sales {
+        match items.iter_mut().find(|item| item.item == sale.item) {
+            Some(item) => {
+                item.quantity += sale.quantity;
+                item.revenue += sale.revenue();
+            }
+            None => items.push(ItemSales {
+                item: sale.item.clone(),
+                category: sale.category.clone(),
+                quantity: sale.quantity,
+                revenue: sale.revenue(),
+            }),
+        }
+    }
+    items.sort_by(|a, b| {
+        (b.quantity, b.revenue)
+            .cmp(&(a.quantity, a.revenue))
+            .then_with(|| a.item.cmp(&b.item))
+    });
+    items
+}
+
+// A ticket has a sale for every item on it, but is counted once.
+fn by_station(sales: &[Sale]) -> Vec<StationTimes> {
+    let tickets: BTreeMap<TicketId, &Sale> = sales.iter().map(|sale| (sale.ticket, sale)).collect();
+    let mut times: BTreeMap<Station, Vec<Duration>> = BTreeMap::new();
+    for sale in tickets.values() {
+        times
+            .entry(sale.station)
+            .or_default()
+            .push(sale.bumped.saturating_since(sale.fired));
+    }
+    Station::ALL
+        .into_iter()
+        .filter_map(|station| {
+            let times = times.get(&station)?;
+            Some(StationTimes {
+                station,
+                tickets: times.len(),
+                average: average(times),
+                longest: times.iter().max().copied().unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+fn by_table(sales: &[Sale]) -> Vec<TableTurnover> {
+    let mut tables: BTreeMap<u32, (HashMap<PartyId, Duration>, Cents)> = BTreeMap::new();
+    for sale in sales {
+        let (parties, revenue) = tables.entry(sale.table).or_default();
+        parties.insert(sale.party, sale.left.saturating_since(sale.seated));
+        *revenue += sale.revenue();
+    }
+    tables
+        .into_iter()
+        .map(|(table, (parties, revenue))| {
+            let stays: Vec<Duration> = parties.into_values().collect();
+            TableTurnover {
+                table,
+                parties: stays.len(),
+                average_stay: average(&stays),
+                revenue,
+            }
+        })
+        .collect()
+}
+
+fn average(durations: &[Duration]) -> Duration {
+    match u32::try_from(durations.len()) {
+        Ok(0) | Err(_) => Duration::ZERO,
+        Ok(count) => durations.iter().sum::<Duration>() / count,
+    }
+}
+
+/// A value of a sheet. Text is left-aligned in text tables and the rest
+/// right-aligned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cell {
+    Text(String),
+    Count(u64),
+    Money(Cents),
+    /// Shown in minutes with one decimal.
+    Minutes(Duration),
+}
+
+impl Cell {
+    fn to_csv(&self) -> String {
+        match self {
+            Cell::Text(text) => csv_field(text),
+            Cell::Count(count) => count.to_string(),
+            // no dollar sign, so spreadsheets read it as a number
+            Cell::Money(Cents(cents)) => {
+                let sign = if *cents < 0 { "-" } else { "" };
+                let cents = cents.unsigned_abs();
+                format!("{sign}{}.{:02}", cents / 100, cents % 100)
+            }
+            Cell::Minutes(duration) => format!("{:.1}", duration.as_secs_f64() / 60.0),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        match self {
+            Cell::Text(text) => text.clone(),
+            Cell::Money(cents) => cents.to_string(),
+            Cell::Count(_) | Cell::Minutes(_) => self.to_csv(),
+        }
+    }
+}
+
+// Quoted if it holds a comma, quote or line break, with quotes doubled.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        String::from(text)
+    }
+}
+
+/// A titled table of a report.
+///
+/// # Examples
+///
+/// ```
+/// use modules_restaurant::money::Cents;
+/// use modules_restaurant::reports::{Cell, Sheet};
+///
+/// let mut sheet = Sheet::new("Sides", &["Item", "Revenue"]);
+/// sheet.add_row(vec![Cell::Text(String::from("Fries, large")), Cell::Money(Cents(450))]);
+/// assert_eq!(sheet.to_csv(), "Item,Revenue\n\"Fries, large\",4.50\n");
+/// assert_eq!(
+///     sheet.to_string(),
+///     "Sides\nItem          Revenue\n------------  -------\nFries, large    $4.50\n"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sheet {
+    pub title: String,
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+impl Sheet {
+    pub fn new(title: &str, header: &[&str]) -> Sheet {
+        Sheet {
+            title: String::from(title),
+            header: header.iter().map(|column| String::from(*column)).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the row doesn't have a cell for every column.
+    pub fn add_row(&mut self, row: Vec<Cell>) {
+        assert_eq!(
+            row.len(),
+            self.header.len(),
+            "a row needs a cell per column"
+        );
+        self.rows.push(row);
+    }
+
+    /// The header and the rows, every line ending with a line break.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        let header: Vec<String> = self.header.iter().map(|column| csv_field(column)).collect();
+        csv.push_str(&header.join(","));
+        csv.push('\n');
+        for row in &self.rows {
+            let cells: Vec<String> = row.iter().map(Cell::to_csv).collect();
+            csv.push_str(&cells.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+impl fmt::Display for Sheet {
+    /// The title, then the columns as wide as their widest value and two
+    /// spaces apart.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(Cell::to_text).collect())
+            .collect();
+        let widths: Vec<usize> = (0..self.header.len())
+            .map(|column| {
+                rows.iter()
+                    .map(|row| row[column].chars().count())
+                    .chain([self.header[column].chars().count()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        // a column is right-aligned when its values aren't text
+        let right: Vec<bool> = (0..self.header.len())
+            .map(|column| {
+                self.rows
+                    .first()
+                    .is_some_and(|row| !matches!(row[column], Cell::Text(_)))
+            })
+            .collect();
+        let line = |f: &mut fmt::Formatter, cells: &[String]| {
+            let cells: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .zip(&right)
+                .map(|((cell, &width), &right)| {
+                    if right {
+                        format!("{cell:>width$}")
+                    } else {
+                        format!("{cell:<width$}")
+                    }
+                })
+                .collect();
+            writeln!(f, "{}", cells.join("  ").trim_end())
+        };
+
+        writeln!(f, "{}", self.title)?;
+        line(f, &self.header)?;
+        let rules: Vec<String> = widths.iter().map(|&width| "-".repeat(width)).collect();
+        line(f, &rules)?;
+        for row in &rows {
+            line(f, row)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::testing::{at, minutes};
+
+    /// Table 1 has two parties, table 2 one. The burgers and steak of the
+    /// first party were one grill ticket.
+    fn sales() -> Vec<Sale> {
+        let sale = |item: &str, category: &str, quantity, price, station, ticket| Sale {
+            item: String::from(item),
+            category: String::from(category),
+            quantity,
+            price: Cents(price),
+            station,
+            ticket: TicketId(ticket),
+            table: 1,
+            party: PartyId(1),
+            seated: at(19, 0),
+            left: at(19, 40),
+            fired: at(19, 5),
+            bumped: at(19, 15),
+        };
+        let second_party = |sale: Sale| Sale {
+            party: PartyId(2),
+            seated: at(19, 45),
+            left: at(20, 45),
+            fired: at(19, 50),
+            bumped: at(19, 56),
+            ..sale
+        };
+        let other_table = |sale: Sale| Sale {
+            table: 2,
+            party: PartyId(3),
+            left: at(20, 30),
+            bumped: at(19, 25),
+            ..sale
+        };
+        vec![
+            sale("Burger", "Mains", 2, 1450, Station::Grill, 1),
+            sale("Steak", "Mains", 1, 2600, Station::Grill, 1),
+            sale("Fries", "Sides", 2, 450, Station::Fryer, 2),
+            second_party(sale("Fries", "Sides", 1, 450, Station::Fryer, 3)),
+            second_party(sale("Cheesecake", "Desserts", 1, 750, Station::Pastry, 4)),
+            other_table(sale("Burger", "Mains", 1, 1450, Station::Grill, 5)),
+            other_table(sale("Fries", "Sides", 2, 450, Station::Fryer, 6)),
+        ]
+    }
+
+    #[test]
+    fn revenue_by_category_and_item() {
+        let report = DayReport::new(&sales());
+        assert_eq!(report.revenue(), Cents(9950));
+        let categories: Vec<_> = report
+            .categories()
+            .iter()
+            .map(|c| (c.category.as_str(), c.quantity, c.revenue))
+            .collect();
+        assert_eq!(
+            categories,
+            [
+                ("Mains", 4, Cents(6950)),
+                ("Sides", 5, Cents(2250)),
+                ("Desserts", 1, Cents(750))
+            ]
+        );
+        let items: Vec<_> = report
+            .items()
+            .iter()
+            .map(|i| (i.item.as_str(), i.quantity))
+            .collect();
+        // the steak and the cheesecake sold once, the steak made more
+        assert_eq!(
+            items,
+            [("Fries", 5), ("Burger", 3), ("Steak", 1), ("Cheesecake", 1)]
+        );
+        let top = report.clone().with_top_items(2);
+        assert_eq!(top.top_items().len(), 2);
+        assert_eq!(report.with_top_items(10).top_items().len(), 4);
+    }
+    #[test]
+    fn ticket_times_count_each_ticket_once() {
+        let report = DayReport::new(&sales());
+        let grill = report.stations()[0];
+        // 10 minutes for table 1's burgers and steak, 20 for table 2
+        assert_eq!(
+            grill,
+            StationTimes {
+                station: Station::Grill,
+                tickets: 2,
+                average: minutes(15),
+                longest: minutes(20)
+            }
+        );
+        let stations: Vec<_> = report.stations().iter().map(|s| s.station).collect();
+        assert_eq!(stations, [Station::Grill, Station::Fryer, Station::Pastry]);
+        assert_eq!(report.stations()[1].tickets, 3);
+
+        // a second round fired and bumped along with the first is a ticket
+        // of its own
+        let mut sales = sales();
+        sales.push(Sale {
+            ticket: TicketId(7),
+            ..sales[0].clone()
+        });
+        assert_eq!(DayReport::new(&sales).stations()[0].tickets, 3);
+    }
+    #[test]
+    fn table_turnover() {
+        let report = DayReport::new(&sales());
+        assert_eq!(
+            report.tables(),
+            [
+                TableTurnover {
+                    table: 1,
+                    parties: 2,
+                    // 40 and 60 minutes
+                    average_stay: minutes(50),
+                    revenue: Cents(7600)
+                },
+                TableTurnover {
+                    table: 2,
+                    parties: 1,
+                    average_stay: minutes(90),
+                    revenue: Cents(2350)
+                },
+            ]
+        );
+    }
+    #[test]
+    fn exports_csv() {
+        let csv = DayReport::new(&sales()).with_top_items(2).to_csv();
+        let expected = "\
+Revenue by category
+Category,Items,Revenue
+Mains,4,69.50
+Sides,5,22.50
+Desserts,1,7.50
+Total,10,99.50
+
+Top items
+Item,Category,Sold,Revenue
+Fries,Sides,5,22.50
+Burger,Mains,3,43.50
+
+Ticket times
+Station,Tickets,Average (min),Longest (min)
+grill,2,15.0,20.0
+fryer,3,12.0,20.0
+pastry,1,6.0,6.0
+
+Table turnover
+Table,Parties,Average stay (min),Revenue
+1,2,50.0,76.00
+2,1,90.0,23.50
+";
+        assert_eq!(csv, expected);
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+    #[test]
+    fn prints_text_tables() {
+        let text = DayReport::new(&sales()).to_string();
+        let expected = "\
+Revenue by category
+Category  Items  Revenue
+--------  -----  -------
+Mains         4   $69.50
+Sides         5   $22.50
+Desserts      1    $7.50
+Total        10   $99.50
+";
+        assert!(text.starts_with(expected), "{text}");
+        assert!(text.contains("\nTable turnover\nTable  Parties  Average stay (min)  Revenue\n"));
+        let empty = DayReport::new(&[]);
+        assert_eq!(empty.revenue(), Cents::ZERO);
+        assert!(empty
+            .to_string()
+            .contains("Top items\nItem  Category  Sold  Revenue\n----"));
+    }
+}
@@ -0,0 +1,282 @@
+//! Points in time with minute precision, which is all a restaurant needs.
+//!
+//! A [`Time`] counts minutes since the Unix epoch in UTC, so it can be
+//! compared, stored in a file and shown as `2024-06-01 19:30`.
+
+use std::fmt;
+use std::ops::{Add, Sub};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+pub const MINUTES_PER_DAY: u64 = 24 * 60;
+
+/// Stored in files the way it is displayed, `2024-06-01 19:30`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Time {
+    minutes: u64,
+}
+
+impl Time {
+    pub fn from_minutes(minutes: u64) -> Time {
+        Time { minutes }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the date doesn't exist, is before 1970, or the time of day
+    /// is out of range.
+    pub fn from_ymd_hm(year: u32, month: u32, day: u32, hour: u32, minute: u32) -> Time {
+        assert!(
+            is_valid_date(year, month, day),
+            "invalid date {year}-{month:02}-{day:02}"
+        );
+        assert!(
+            hour < 24 && minute < 60,
+            "invalid time of day {hour:02}:{minute:02}"
+        );
+        let days = days_from_civil(year, month, day);
+        Time {
+            minutes: days * MINUTES_PER_DAY + u64::from(hour * 60 + minute),
+        }
+    }
+
+    /// The current time according to the system clock.
+    pub fn now() -> Time {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Time {
+            minutes: since_epoch.as_secs() / 60,
+        }
+    }
+
+    pub fn minutes(&self) -> u64 {
+        self.minutes
+    }
+
+    /// Days since the epoch, the same for all times of one (UTC) day.
+    pub fn day(&self) -> u64 {
+        self.minutes / MINUTES_PER_DAY
+    }
+
+    /// Midnight at the start of this day.
+    pub fn start_of_day(&self) -> Time {
+        Time {
+            minutes: self.day() * MINUTES_PER_DAY,
+        }
+    }
+
+    /// `(year, month, day)`
+    pub fn date(&self) -> (u32, u32, u32) {
+        civil_from_days(self.day())
+    }
+
+    /// `(hour, minute)`
+    pub fn time_of_day(&self) -> (u32, u32) {
+        let minute_of_day = (self.minutes % MINUTES_PER_DAY) as u32;
+        (minute_of_day / 60, minute_of_day % 60)
+    }
+
+    /// Time from `earlier` until `self`, zero if `earlier` is later.
+    pub fn saturating_since(&self, earlier: Time) -> Duration {
+        Duration::from_secs(self.minutes.saturating_sub(earlier.minutes) * 60)
+    }
+}
+
+impl Add<Duration> for Time {
+    type Output = Time;
+
+    /// Whole minutes only, the seconds are dropped.
+    fn add(self, duration: Duration) -> Time {
+        Time {
+            minutes: self.minutes + whole_minutes(duration),
+        }
+    }
+}
+
+impl Sub<Duration> for Time {
+    type Output = Time;
+
+    fn sub(self, duration: Duration) -> Time {
+        Time {
+            minutes: self.minutes.saturating_sub(whole_minutes(duration)),
+        }
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (year, month, day) = self.date();
+        let (hour, minute) = self.time_of_day();
+        write!(f, "{year:04}-{month:02}-{day:02} {hour:02}:{minute:02}")
+    }
+}
+
+/// Error of parsing a [`Time`] that isn't `YYYY-MM-DD HH:MM`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseTimeError(String);
+
+impl fmt::Display for ParseTimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid time `{}`, expected YYYY-MM-DD HH:MM", self.0)
+    }
+}
+
+impl std::error::Error for ParseTimeError {}
+
+impl FromStr for Time {
+    type Err = ParseTimeError;
+
+    fn from_str(s: &str) -> Result<Time, ParseTimeError> {
+        let error = || ParseTimeError(String::from(s));
+        let (date, time) = s.trim().split_once([' ', 'T']).ok_or_else(error)?;
+        let numbers = |text: &str, separator: char| -> Option<Vec<u32>> {
+            text.split(separator)
+                .map(|part| part.parse().ok())
+                .collect()
+        };
+        let date = numbers(date, '-').ok_or_else(error)?;
+        let time = numbers(time, ':').ok_or_else(error)?;
+        match (&date[..], &time[..]) {
+            (&[year, month, day], &[hour, minute])
+                if is_valid_date(year, month, day) && hour < 24 && minute < 60 =>
+            {
+                Ok(Time::from_ymd_hm(year, month, day, hour, minute))
+            }
+            _ => Err(error()),
+        }
+    }
+}
+
+impl TryFrom<String> for Time {
+    type Error = ParseTimeError;
+
+    fn try_from(text: String) -> Result<Time, ParseTimeError> {
+        text.parse()
+    }
+}
+
+impl From<Time> for String {
+    fn from(time: Time) -> String {
+        time.to_string()
+    }
+}
+
+fn whole_minutes(duration: Duration) -> u64 {
+    duration.as_secs() / 60
+}
+
+fn is_leap_year(year: u32) -> bool {
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+fn is_valid_date(year: u32, month: u32, day: u32) -> bool {
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => return false,
+    };
+    year >= 1970 && (1..=days_in_month).contains(&day)
+}
+
+// Howard Hinnant's algorithms, with years starting in March so that the
+// leap day is the last day of the year
+fn days_from_civil(year: u32, month: u32, day: u32) -> u64 {
+    let year = u64::from(if month <= 2 { year - 1 } else { year });
+    let (era, year_of_era) = (year / 400, year % 400);
+    let month_from_march = u64::from((month + 9) % 12);
+    let day_of_year = (153 * month_from_march + 2) / 5 + u64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    // the epoch, 1970-01-01, is day 719468 counted from 0000-03-01
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: u64) -> (u32, u32, u32) {
+    let days = days + 719_468;
+    let (era, day_of_era) = (days / 146_097, days % 146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year as u32, month as u32, day as u32)
+}
+
+/// Shorthands for tests elsewhere in the crate, which all happen on the
+/// evening of 2024-06-01.
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+
+    pub(crate) fn at(hour: u32, minute: u32) -> Time {
+        Time::from_ymd_hm(2024, 6, 1, hour, minute)
+    }
+
+    pub(crate) fn minutes(minutes: u64) -> Duration {
+        Duration::from_secs(minutes * 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_dates_both_ways() {
+        assert_eq!(Time::from_ymd_hm(1970, 1, 1, 0, 0).minutes(), 0);
+        assert_eq!(
+            Time::from_ymd_hm(1970, 1, 2, 1, 30).minutes(),
+            MINUTES_PER_DAY + 90
+        );
+        for (year, month, day) in [(2000, 2, 29), (2023, 12, 31), (2024, 3, 1), (2100, 2, 28)] {
+            let time = Time::from_ymd_hm(year, month, day, 19, 45);
+            assert_eq!(time.date(), (year, month, day));
+            assert_eq!(time.time_of_day(), (19, 45));
+        }
+        // 2024-06-01 is 19875 days after the epoch
+        assert_eq!(Time::from_ymd_hm(2024, 6, 1, 0, 0).day(), 19_875);
+    }
+    #[test]
+    fn parses_and_displays() {
+        let time: Time = "2024-06-01 19:30".parse().unwrap();
+        assert_eq!(time.to_string(), "2024-06-01 19:30");
+        assert_eq!(
+            "2024-06-01T07:05".parse::<Time>().unwrap().to_string(),
+            "2024-06-01 07:05"
+        );
+        for invalid in [
+            "2024-06-01",
+            "2023-02-29 10:00",
+            "2024-13-01 10:00",
+            "2024-06-01 24:00",
+            "x y",
+        ] {
+            assert!(invalid.parse::<Time>().is_err(), "{invalid}");
+        }
+        assert_eq!(
+            "noon".parse::<Time>().unwrap_err().to_string(),
+            "invalid time `noon`, expected YYYY-MM-DD HH:MM"
+        );
+    }
+    #[test]
+    fn arithmetic_in_minutes() {
+        let time = Time::from_ymd_hm(2024, 6, 1, 23, 30);
+        let later = time + Duration::from_secs(45 * 60);
+        assert_eq!(later.to_string(), "2024-06-02 00:15");
+        assert_eq!(later.saturating_since(time), Duration::from_secs(45 * 60));
+        assert_eq!(time.saturating_since(later), Duration::ZERO);
+        assert_eq!(later - Duration::from_secs(45 * 60), time);
+        assert_eq!(later.start_of_day().to_string(), "2024-06-02 00:00");
+    }
+}
@@ -0,0 +1,235 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// A state file of its own for every test, removed when the test is done.
+struct Restaurant {
+    dir: PathBuf,
+}
+
+impl Restaurant {
+    fn new(name: &str) -> Restaurant {
+        let dir = std::env::temp_dir().join(format!("restaurant-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let _ = fs::remove_file(dir.join("state.json"));
+        Restaurant { dir }
+    }
+
+    fn state(&self) -> PathBuf {
+        self.dir.join("state.json")
+    }
+
+    /// Run a command at 19:mm on 2024-06-01.
+    fn at(&self, minute: u32, args: &[&str]) -> Output {
+        let state = self.state();
+        let at = format!("2024-06-01 19:{minute:02}");
+        Command::new(env!("CARGO_BIN_EXE_restaurant"))
+            .args(["--state", state.to_str().unwrap(), "--at", &at])
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    /// Run a command that must succeed and return what it printed.
+    fn run(&self, minute: u32, args: &[&str]) -> String {
+        let output = self.at(minute, args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "{args:?} failed: {stderr}");
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    /// Run a command that must fail and return its error.
+    fn fail(&self, minute: u32, args: &[&str]) -> String {
+        let output = self.at(minute, args);
+        assert_eq!(output.status.code(), Some(1), "{args:?} succeeded");
+        String::from_utf8(output.stderr).unwrap()
+    }
+}
+
+impl Drop for Restaurant {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[test]
+fn a_day_of_service() {
+    let restaurant = Restaurant::new("day");
+    let added = restaurant.run(0, &["waitlist", "add", "Ana", "2"]);
+    assert!(
+        added.contains("Ana, party of 2, is #1 on the waitlist"),
+        "{added}"
+    );
+    assert!(added.contains("Calling Ana (#1) to table 1"), "{added}");
+    let list = restaurant.run(1, &["waitlist", "list"]);
+    assert!(
+        list.contains("Called: #1 Ana to table 1, held until 19:10"),
+        "{list}"
+    );
+    assert_eq!(restaurant.run(2, &["seat", "#1"]), "Seated #1 at table 1\n");
+
+    let order = restaurant.run(3, &["order", "1", "2x Burger", "fries"]);
+    assert_eq!(
+        order,
+        "Order #1 for table 1: 2x Burger, 1x Fries\nFired T1 (grill), T2 (fryer)\n"
+    );
+    let queue = restaurant.run(10, &["kitchen", "queue", "grill"]);
+    assert_eq!(queue, "grill:\n  T1 table 1 order #1: 2x Burger, 7 min\n");
+    assert!(restaurant
+        .run(12, &["kitchen", "bump", "T1"])
+        .contains("order #1 is cooking"));
+    assert!(restaurant
+        .run(13, &["kitchen", "bump", "T2"])
+        .contains("order #1 is ready"));
+    assert_eq!(restaurant.run(14, &["kitchen", "queue"]), "No tickets\n");
+    restaurant.run(15, &["serve", "1"]);
+
+    // $33.50 with 8.25% tax is $36.26
+    let bill = restaurant.run(30, &["bill", "1"]);
+    assert!(
+        bill.contains("Total                             $36.26"),
+        "{bill}"
+    );
+    let partly = restaurant.run(31, &["bill", "1", "--pay", "20", "--card", "4242"]);
+    assert!(
+        partly.contains("Card ****4242                     $20.00"),
+        "{partly}"
+    );
+    assert!(
+        partly.contains("Balance                           $16.26"),
+        "{partly}"
+    );
+    let paid = restaurant.run(32, &["bill", "1", "--pay", "20"]);
+    assert!(
+        paid.contains("Change                             $3.74"),
+        "{paid}"
+    );
+    assert!(paid.ends_with("Table 1 is free\n"), "{paid}");
+
+    let closed = restaurant.run(59, &["close-day"]);
+    assert!(closed.starts_with("Closed 2024-06-01\n"), "{closed}");
+    assert!(closed.contains("Sales                 $33.50"), "{closed}");
+    assert!(
+        closed.contains("grill: 1 ticket, average 9 min"),
+        "{closed}"
+    );
+    // the next day starts from scratch
+    assert_eq!(
+        restaurant.run(59, &["waitlist", "list"]),
+        "Nobody is waiting\n"
+    );
+}
+
+#[test]
+fn failed_commands_leave_the_state_alone() {
+    let restaurant = Restaurant::new("errors");
+    restaurant.run(0, &["waitlist", "add", "Ana", "2"]);
+    restaurant.run(0, &["seat", "1"]);
+    let before = fs::read_to_string(restaurant.state()).unwrap();
+
+    let error = restaurant.fail(1, &["order", "1", "Lobster"]);
+    assert_eq!(error, "Application error: `Lobster` isn't on the menu\n");
+    let error = restaurant.fail(1, &["order", "1", "Burger", "Oranges"]);
+    assert!(error.contains("Oranges is out of season"), "{error}");
+    assert!(restaurant
+        .fail(1, &["order", "2", "Burger"])
+        .contains("nobody sits at table 2"));
+    assert!(restaurant
+        .fail(1, &["waitlist", "add", "Big", "9"])
+        .contains("no table"));
+    assert!(restaurant
+        .fail(1, &["bill", "1"])
+        .contains("table 1 has nothing to pay"));
+    assert_eq!(fs::read_to_string(restaurant.state()).unwrap(), before);
+}
+
+#[test]
+fn only_served_and_paid_orders_close_the_day() {
+    let restaurant = Restaurant::new("close");
+    restaurant.run(0, &["waitlist", "add", "Ana", "2"]);
+    restaurant.run(0, &["seat", "1"]);
+    restaurant.run(1, &["order", "1", "Steak"]);
+    let error = restaurant.fail(2, &["bill", "1", "--pay", "30"]);
+    assert!(error.contains("order #1 is still cooking"), "{error}");
+    let error = restaurant.fail(2, &["close-day"]);
+    assert!(
+        error.contains("table 1 hasn't paid for order #1"),
+        "{error}"
+    );
+}
+
+#[test]
+fn tables_keep_ordering_after_a_tip() {
+    let restaurant = Restaurant::new("tip");
+    restaurant.run(0, &["waitlist", "add", "Ana", "2"]);
+    restaurant.run(0, &["seat", "1"]);
+    restaurant.run(1, &["order", "1", "Steak"]);
+    restaurant.run(2, &["bill", "1", "--tip", "18%"]);
+    restaurant.run(3, &["order", "1", "Cheesecake"]);
+    // 18% of $33.50, the cheesecake included
+    let bill = restaurant.run(4, &["bill", "1"]);
+    assert!(bill.contains("$6.03"), "{bill}");
+    // once something is paid, the bill is final
+    for ticket in ["T1", "T2"] {
+        restaurant.run(20, &["kitchen", "bump", ticket]);
+    }
+    restaurant.run(21, &["serve", "1"]);
+    restaurant.run(21, &["serve", "2"]);
+    restaurant.run(30, &["bill", "1", "--pay", "10"]);
+    let error = restaurant.fail(31, &["order", "1", "Fries"]);
+    assert!(error.contains("table 1 is paying"), "{error}");
+}
+
+#[test]
+fn parties_can_leave_without_ordering() {
+    let restaurant = Restaurant::new("leave");
+    restaurant.run(0, &["waitlist", "add", "Ana", "2"]);
+    restaurant.run(0, &["seat", "1"]);
+    assert_eq!(restaurant.run(1, &["leave", "1"]), "Table 1 is free\n");
+    assert!(restaurant
+        .fail(2, &["leave", "1"])
+        .contains("nobody sits at table 1"));
+
+    restaurant.run(3, &["waitlist", "add", "Ben", "2"]);
+    restaurant.run(3, &["seat", "2"]);
+    restaurant.run(4, &["order", "1", "Fries"]);
+    let error = restaurant.fail(5, &["leave", "1"]);
+    assert!(
+        error.contains("table 1 hasn't paid for order #1"),
+        "{error}"
+    );
+}
+
+#[test]
+fn no_show_parties_lose_their_table() {
+    let restaurant = Restaurant::new("no-show");
+    restaurant.run(0, &["waitlist", "add", "Ana", "6"]);
+    let gone = restaurant.run(10, &["waitlist", "list"]);
+    assert_eq!(
+        gone,
+        "Ana (#1) didn't show up, their table is given away\nNobody is waiting\n"
+    );
+    assert!(restaurant
+        .fail(11, &["seat", "1"])
+        .contains("there is no party #1"));
+}
+
+#[test]
+fn bad_arguments_print_the_usage() {
+    let restaurant = Restaurant::new("usage");
+    for args in [
+        &[][..],
+        &["dance"],
+        &["waitlist", "add", "Ana", "two"],
+        &["order", "1", "0x Steak"],
+        &["--at", "noon", "help"],
+    ] {
+        let error = restaurant.fail(0, args);
+        assert!(error.starts_with("Problem parsing arguments: "), "{error}");
+        assert!(error.contains("Usage: restaurant"), "{error}");
+    }
+    assert!(restaurant
+        .run(0, &["help"])
+        .starts_with("Usage: restaurant"));
+    assert!(!restaurant.state().exists());
+}
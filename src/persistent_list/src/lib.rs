@@ -0,0 +1,358 @@
+//! # persistent_list
+//!
+//! `persistent_list` turns the `Rc` cons list of the `smart_pointers` crate
+//! into a reusable immutable list. Adding an element never changes an
+//! existing list, it creates a new one that shares all of the old nodes
+//! (structural sharing), so "copies" of a list are cheap and can be kept
+//! around as snapshots.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::FusedIterator;
+use std::rc::Rc;
+
+struct Node<T> {
+    elem: T,
+    next: Option<Rc<Node<T>>>,
+}
+
+/// An immutable singly linked list whose nodes are shared with `Rc`.
+///
+/// `clone` only copies a pointer, and so do `cons` and `tail`.
+///
+/// # Examples
+///
+/// ```
+/// use persistent_list::List;
+/// let shared: List<i32> = [2, 3].into_iter().collect();
+/// let a = shared.cons(1);
+/// let b = shared.cons(10);
+/// // both lists share the nodes of `shared`, nothing was copied
+/// assert_eq!(a.iter().copied().collect::<Vec<_>>(), [1, 2, 3]);
+/// assert_eq!(b.iter().copied().collect::<Vec<_>>(), [10, 2, 3]);
+/// assert_eq!(a.tail(), b.tail());
+/// ```
+pub struct List<T> {
+    head: Option<Rc<Node<T>>>,
+    len: usize,
+}
+
+impl<T> List<T> {
+    /// The empty list.
+    pub fn new() -> Self {
+        List { head: None, len: 0 }
+    }
+
+    /// A new list with `elem` in front of this one.
+    pub fn cons(&self, elem: T) -> Self {
+        List {
+            head: Some(Rc::new(Node {
+                elem,
+                next: self.head.clone(),
+            })),
+            len: self.len + 1,
+        }
+    }
+
+    /// The first element, `None` for the empty list.
+    pub fn head(&self) -> Option<&T> {
+        self.head.as_ref().map(|node| &node.elem)
+    }
+
+    /// Everything after the first element, `None` for the empty list.
+    pub fn tail(&self) -> Option<Self> {
+        self.head.as_ref().map(|node| List {
+            head: node.next.clone(),
+            len: self.len - 1,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            next: self.head.as_deref(),
+            len: self.len,
+        }
+    }
+
+    /// Whether both lists are the very same nodes (not just equal ones).
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (&self.head, &other.head) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    /// The elements in reverse order. Every node is new, so the elements
+    /// have to be cloned.
+    pub fn rev(&self) -> Self
+    where
+        T: Clone,
+    {
+        self.iter()
+            .fold(List::new(), |reversed, elem| reversed.cons(elem.clone()))
+    }
+
+    /// A new list with `f` applied to every element.
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> List<U> {
+        self.iter().map(f).collect()
+    }
+
+    /// The elements of this list followed by the ones of `other`.
+    ///
+    /// `other` is shared as it is, only the nodes of `self` are copied:
+    /// their `next` pointers have to change.
+    pub fn append(&self, other: &Self) -> Self
+    where
+        T: Clone,
+    {
+        let elems: Vec<&T> = self.iter().collect();
+        elems
+            .into_iter()
+            .rev()
+            .fold(other.clone(), |list, elem| list.cons(elem.clone()))
+    }
+}
+
+impl<T> Drop for List<T> {
+    fn drop(&mut self) {
+        // the default drop would recurse once per node and overflow the
+        // stack for long lists, so unlink the nodes one by one instead
+        let mut next = self.head.take();
+        while let Some(node) = next {
+            match Rc::try_unwrap(node) {
+                Ok(mut node) => next = node.next.take(),
+                // the rest is still used by another list
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+impl<T> Clone for List<T> {
+    fn clone(&self) -> Self {
+        List {
+            head: self.head.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        List::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for List<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for List<T> {
+    fn eq(&self, other: &Self) -> bool {
+        // shared nodes are equal without looking at them
+        self.len == other.len && (self.ptr_eq(other) || self.iter().eq(other.iter()))
+    }
+}
+
+impl<T: Eq> Eq for List<T> {}
+
+impl<T: Hash> Hash for List<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len);
+        for elem in self {
+            elem.hash(state);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for List<T> {
+    /// Keeps the order of the iterator, its first element becomes the head.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let elems: Vec<T> = iter.into_iter().collect();
+        elems
+            .into_iter()
+            .rev()
+            .fold(List::new(), |list, elem| list.cons(elem))
+    }
+}
+
+/// Iterator over the elements of a [`List`] by reference.
+pub struct Iter<'a, T> {
+    next: Option<&'a Node<T>>,
+    len: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.map(|node| {
+            self.next = node.next.as_deref();
+            self.len -= 1;
+            &node.elem
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<T> FusedIterator for Iter<'_, T> {}
+
+impl<'a, T> IntoIterator for &'a List<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the elements of a [`List`] by value.
+///
+/// Nodes only used by this list are taken apart, shared ones are cloned.
+pub struct IntoIter<T>(List<T>);
+
+impl<T: Clone> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let node = self.0.head.take()?;
+        self.0.len -= 1;
+        match Rc::try_unwrap(node) {
+            Ok(node) => {
+                self.0.head = node.next;
+                Some(node.elem)
+            }
+            Err(shared) => {
+                self.0.head = shared.next.clone();
+                Some(shared.elem.clone())
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
+}
+
+impl<T: Clone> ExactSizeIterator for IntoIter<T> {}
+
+impl<T: Clone> FusedIterator for IntoIter<T> {}
+
+impl<T: Clone> IntoIterator for List<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(elems: &[i32]) -> List<i32> {
+        elems.iter().copied().collect()
+    }
+
+    #[test]
+    fn cons_head_and_tail() {
+        let empty = List::new();
+        assert!(empty.is_empty());
+        assert_eq!(empty.head(), None);
+        assert_eq!(empty.tail(), None);
+
+        let one = empty.cons(1);
+        let two = one.cons(2);
+        assert_eq!(two.head(), Some(&2));
+        assert_eq!(two.len(), 2);
+        assert_eq!(two.tail(), Some(one.clone()));
+        assert!(two.tail().unwrap().ptr_eq(&one));
+        // the old lists are untouched
+        assert_eq!(one, list(&[1]));
+        assert!(empty.is_empty());
+    }
+    #[test]
+    fn collects_in_iterator_order() {
+        let l = list(&[1, 2, 3]);
+        assert_eq!(l.iter().copied().collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(l.iter().len(), 3);
+        assert_eq!(format!("{:?}", l), "[1, 2, 3]");
+    }
+    #[test]
+    fn rev_map_and_append() {
+        let l = list(&[1, 2, 3]);
+        assert_eq!(l.rev(), list(&[3, 2, 1]));
+        assert_eq!(l.map(|n| n * 10), list(&[10, 20, 30]));
+        assert_eq!(l.map(|n| n.to_string()).head().unwrap(), "1");
+
+        let back = list(&[4, 5]);
+        let joined = l.append(&back);
+        assert_eq!(joined, list(&[1, 2, 3, 4, 5]));
+        // the appended list is shared, not copied
+        let shared_tail = joined.tail().unwrap().tail().unwrap().tail().unwrap();
+        assert!(shared_tail.ptr_eq(&back));
+        assert_eq!(List::new().append(&back), back);
+    }
+    #[test]
+    fn equality_and_hashing() {
+        use std::collections::HashSet;
+        assert_eq!(list(&[1, 2]), list(&[1, 2]));
+        assert_ne!(list(&[1, 2]), list(&[1]));
+        assert_ne!(list(&[1, 2]), list(&[2, 1]));
+        let set: HashSet<_> = [list(&[1, 2]), list(&[1, 2]), list(&[2])]
+            .into_iter()
+            .collect();
+        assert_eq!(set.len(), 2);
+    }
+    #[test]
+    fn structural_sharing_counts() {
+        let base = list(&[2, 3]);
+        let node = Rc::clone(base.head.as_ref().unwrap());
+        assert_eq!(Rc::strong_count(&node), 2);
+        let a = base.cons(1);
+        let b = base.cons(1);
+        assert_eq!(Rc::strong_count(&node), 4);
+        drop((a, b, base));
+        // dropping stops at shared nodes, and everything is released in the end
+        assert_eq!(Rc::strong_count(&node), 1);
+    }
+    #[test]
+    fn into_iter_takes_unique_and_clones_shared_nodes() {
+        let shared = list(&[3, 4]);
+        let l = shared.cons(2).cons(1);
+        assert_eq!(l.into_iter().collect::<Vec<_>>(), [1, 2, 3, 4]);
+        assert_eq!(shared, list(&[3, 4]));
+        let strings: List<String> = ["a", "b"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(strings.into_iter().collect::<String>(), "ab");
+    }
+    #[test]
+    fn dropping_long_lists_does_not_overflow() {
+        let long: List<u32> = (0..1_000_000).collect();
+        let shared = long.cons(0);
+        drop(long);
+        assert_eq!(shared.len(), 1_000_001);
+        drop(shared);
+        // same when only part of it is shared
+        let long: List<u32> = (0..1_000_000).collect();
+        let tail = long.tail().unwrap().tail().unwrap();
+        drop(long);
+        assert_eq!(tail.iter().count(), 999_998);
+    }
+}
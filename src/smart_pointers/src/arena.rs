@@ -0,0 +1,371 @@
+//! A typed arena: values live in one `Vec` and refer to each other by
+//! [`Id`] instead of by pointer.
+//!
+//! `Rc<RefCell<_>>` graphs as in `combined()` pay for a heap allocation and
+//! a reference count per node, check borrows at runtime and leak as soon
+//! as two nodes point at each other. In an arena, links are plain indices:
+//! cycles are no problem since the arena owns every value, and dropping the
+//! arena drops them all.
+//!
+//! Every slot has a generation that is bumped when its value is removed.
+//! An `Id` remembers the generation it was created with, so an id of a
+//! removed value is detected as stale even after the slot has been reused.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut};
+
+/// Refers to a value in an [`Arena<T>`].
+pub struct Id<T> {
+    index: usize,
+    generation: u64,
+    // `fn() -> T` so an `Id` is `Send`, `Sync` and `Copy` whatever `T` is
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Id<T> {
+    /// Position of the slot in the arena, unique among the live values.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+// the impls below are written by hand because `#[derive]` would add a
+// `T: Trait` bound, and an id is copyable and comparable whatever it points at
+impl<T> Clone for Id<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Id<T> {}
+
+impl<T> PartialEq for Id<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Id<T> {}
+
+impl<T> Hash for Id<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Id<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Id({}v{})", self.index, self.generation)
+    }
+}
+
+enum Slot<T> {
+    Occupied {
+        generation: u64,
+        value: T,
+    },
+    // free slots form a linked list through their indices
+    Free {
+        generation: u64,
+        next_free: Option<usize>,
+    },
+}
+
+/// A collection of `T`s addressed by [`Id<T>`].
+///
+/// # Examples
+///
+/// ```
+/// use smart_pointers::arena::Arena;
+/// let mut arena = Arena::new();
+/// let a = arena.insert("a");
+/// assert_eq!(arena[a], "a");
+/// arena.remove(a);
+/// let b = arena.insert("b"); // reuses the slot of `a`
+/// assert_eq!(a.index(), b.index());
+/// assert_eq!(arena.get(a), None); // but `a` is stale
+/// ```
+pub struct Arena<T> {
+    slots: Vec<Slot<T>>,
+    free_head: Option<usize>,
+    len: usize,
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Arena<T> {
+        Arena {
+            slots: Vec::new(),
+            free_head: None,
+            len: 0,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Arena<T> {
+        Arena {
+            slots: Vec::with_capacity(capacity),
+            ..Arena::new()
+        }
+    }
+
+    /// Store `value`, reusing a free slot if there is one.
+    pub fn insert(&mut self, value: T) -> Id<T> {
+        self.len += 1;
+        if let Some(index) = self.free_head {
+            let Slot::Free {
+                generation,
+                next_free,
+            } = self.slots[index]
+            else {
+                unreachable!("free list points to an occupied slot");
+            };
+            self.free_head = next_free;
+            self.slots[index] = Slot::Occupied { generation, value };
+            return Id {
+                index,
+                generation,
+                marker: PhantomData,
+            };
+        }
+        self.slots.push(Slot::Occupied {
+            generation: 0,
+            value,
+        });
+        Id {
+            index: self.slots.len() - 1,
+            generation: 0,
+            marker: PhantomData,
+        }
+    }
+
+    /// Remove the value of `id`, `None` if `id` is stale.
+    pub fn remove(&mut self, id: Id<T>) -> Option<T> {
+        if !self.contains(id) {
+            return None;
+        }
+        let free = Slot::Free {
+            generation: id.generation + 1,
+            next_free: self.free_head,
+        };
+        self.free_head = Some(id.index);
+        self.len -= 1;
+        match std::mem::replace(&mut self.slots[id.index], free) {
+            Slot::Occupied { value, .. } => Some(value),
+            Slot::Free { .. } => unreachable!("checked by `contains`"),
+        }
+    }
+
+    /// Whether `id` refers to a value that is still in the arena.
+    pub fn contains(&self, id: Id<T>) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn get(&self, id: Id<T>) -> Option<&T> {
+        match self.slots.get(id.index) {
+            Some(Slot::Occupied { generation, value }) if *generation == id.generation => {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, id: Id<T>) -> Option<&mut T> {
+        match self.slots.get_mut(id.index) {
+            Some(Slot::Occupied { generation, value }) if *generation == id.generation => {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Remove all values at once. All existing ids become stale, the
+    /// memory is kept for new values.
+    pub fn clear(&mut self) {
+        self.retain(|_, _| false);
+    }
+
+    /// Remove all values for which `f` returns `false`.
+    pub fn retain(&mut self, mut f: impl FnMut(Id<T>, &mut T) -> bool) {
+        for index in 0..self.slots.len() {
+            let remove = match &mut self.slots[index] {
+                Slot::Occupied { generation, value } => !f(
+                    Id {
+                        index,
+                        generation: *generation,
+                        marker: PhantomData,
+                    },
+                    value,
+                ),
+                Slot::Free { .. } => false,
+            };
+            if remove {
+                let Slot::Occupied { generation, .. } = self.slots[index] else {
+                    unreachable!();
+                };
+                self.remove(Id {
+                    index,
+                    generation,
+                    marker: PhantomData,
+                });
+            }
+        }
+    }
+
+    /// All values with their ids, in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (Id<T>, &T)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| match slot {
+                Slot::Occupied { generation, value } => Some((
+                    Id {
+                        index,
+                        generation: *generation,
+                        marker: PhantomData,
+                    },
+                    value,
+                )),
+                Slot::Free { .. } => None,
+            })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Id<T>, &mut T)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| match slot {
+                Slot::Occupied { generation, value } => Some((
+                    Id {
+                        index,
+                        generation: *generation,
+                        marker: PhantomData,
+                    },
+                    value,
+                )),
+                Slot::Free { .. } => None,
+            })
+    }
+
+    /// The ids of all values, in slot order.
+    pub fn ids(&self) -> impl Iterator<Item = Id<T>> + '_ {
+        self.iter().map(|(id, _)| id)
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Arena::new()
+    }
+}
+
+impl<T> Index<Id<T>> for Arena<T> {
+    type Output = T;
+
+    /// # Panics
+    ///
+    /// Panics if `id` is stale.
+    fn index(&self, id: Id<T>) -> &T {
+        self.get(id)
+            .unwrap_or_else(|| panic!("stale arena id {:?}", id))
+    }
+}
+
+impl<T> IndexMut<Id<T>> for Arena<T> {
+    fn index_mut(&mut self, id: Id<T>) -> &mut T {
+        self.get_mut(id)
+            .unwrap_or_else(|| panic!("stale arena id {:?}", id))
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Arena<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn insert_get_and_remove() {
+        let mut arena = Arena::new();
+        let a = arena.insert(1);
+        let b = arena.insert(2);
+        assert_eq!(arena.len(), 2);
+        arena[b] += 10;
+        assert_eq!(arena.get(b), Some(&12));
+        assert_eq!(arena.remove(a), Some(1));
+        assert_eq!(arena.remove(a), None);
+        assert!(!arena.contains(a));
+        assert_eq!(arena.len(), 1);
+    }
+    #[test]
+    fn reused_slots_get_a_new_generation() {
+        let mut arena = Arena::new();
+        let old = arena.insert("old");
+        arena.remove(old);
+        let new = arena.insert("new");
+        assert_eq!(old.index(), new.index());
+        assert_ne!(old, new);
+        assert_eq!(arena.get(old), None);
+        assert_eq!(arena.get_mut(old), None);
+        assert_eq!(arena[new], "new");
+        assert_eq!(format!("{:?}", new), "Id(0v1)");
+    }
+    #[test]
+    #[should_panic(expected = "stale arena id")]
+    fn indexing_with_a_stale_id_panics() {
+        let mut arena = Arena::new();
+        let id = arena.insert(1);
+        arena.remove(id);
+        let _ = arena[id];
+    }
+    #[test]
+    fn clear_makes_all_ids_stale_and_keeps_the_slots() {
+        let mut arena = Arena::new();
+        let ids: Vec<_> = (0..10).map(|i| arena.insert(i)).collect();
+        arena.clear();
+        assert!(arena.is_empty());
+        assert!(ids.iter().all(|id| !arena.contains(*id)));
+        let reused: Vec<_> = (0..10).map(|i| arena.insert(i)).collect();
+        assert_eq!(arena.slots.len(), 10);
+        assert!(reused.iter().all(|id| !ids.contains(id)));
+    }
+    #[test]
+    fn retain_and_iteration() {
+        let mut arena = Arena::new();
+        for i in 0..6 {
+            arena.insert(i);
+        }
+        arena.retain(|_, value| *value % 2 == 0);
+        for (_, value) in arena.iter_mut() {
+            *value *= 10;
+        }
+        let values: Vec<_> = arena.iter().map(|(_, value)| *value).collect();
+        assert_eq!(values, [0, 20, 40]);
+        assert_eq!(arena.ids().count(), 3);
+    }
+    #[test]
+    fn dropping_the_arena_drops_the_values() {
+        let shared = Rc::new(());
+        let mut arena = Arena::new();
+        for _ in 0..3 {
+            arena.insert(Rc::clone(&shared));
+        }
+        assert_eq!(Rc::strong_count(&shared), 4);
+        drop(arena);
+        assert_eq!(Rc::strong_count(&shared), 1);
+    }
+}
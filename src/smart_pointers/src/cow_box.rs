@@ -0,0 +1,203 @@
+//! A smart pointer that shares its value until someone writes to it.
+//!
+//! Like `MyBox` in `main.rs`, [`CowBox`] derefs to its value. Cloning it
+//! only bumps a reference count; the value itself is cloned the first
+//! time a shared `CowBox` is mutated through `DerefMut`, so copies that
+//! are only read never pay for a clone.
+
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+/// A copy-on-write box, see the module documentation.
+///
+/// # Examples
+///
+/// ```
+/// use smart_pointers::cow_box::CowBox;
+/// let defaults = CowBox::new(vec![String::from("verbose=false")]);
+/// let mut custom = defaults.clone();
+/// assert!(CowBox::ptr_eq(&defaults, &custom)); // nothing copied yet
+/// custom.push(String::from("color=auto")); // the first write clones
+/// assert!(!CowBox::ptr_eq(&defaults, &custom));
+/// assert_eq!(defaults.len(), 1);
+/// assert_eq!(custom.len(), 2);
+/// ```
+pub struct CowBox<T: Clone> {
+    shared: Rc<T>,
+}
+
+impl<T: Clone> CowBox<T> {
+    pub fn new(value: T) -> CowBox<T> {
+        CowBox {
+            shared: Rc::new(value),
+        }
+    }
+
+    /// Whether both boxes share the same value. An associated function
+    /// like `Rc::ptr_eq`, so it doesn't hide a method of `T`.
+    pub fn ptr_eq(this: &CowBox<T>, other: &CowBox<T>) -> bool {
+        Rc::ptr_eq(&this.shared, &other.shared)
+    }
+
+    /// Number of boxes sharing the value.
+    pub fn strong_count(this: &CowBox<T>) -> usize {
+        Rc::strong_count(&this.shared)
+    }
+
+    /// Whether no other box shares the value, so writing won't clone it.
+    pub fn is_unique(this: &CowBox<T>) -> bool {
+        Rc::strong_count(&this.shared) == 1
+    }
+
+    /// A mutable reference to the value, cloning it first if it is shared.
+    /// This is what `DerefMut` does.
+    pub fn make_mut(this: &mut CowBox<T>) -> &mut T {
+        Rc::make_mut(&mut this.shared)
+    }
+
+    /// The value, cloned only if it is still shared.
+    pub fn into_inner(this: CowBox<T>) -> T {
+        Rc::unwrap_or_clone(this.shared)
+    }
+}
+
+impl<T: Clone> Clone for CowBox<T> {
+    /// Shares the value instead of cloning it.
+    fn clone(&self) -> Self {
+        CowBox {
+            shared: Rc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T: Clone> Deref for CowBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.shared
+    }
+}
+
+impl<T: Clone> DerefMut for CowBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        CowBox::make_mut(self)
+    }
+}
+
+impl<T: Clone> From<T> for CowBox<T> {
+    fn from(value: T) -> Self {
+        CowBox::new(value)
+    }
+}
+
+impl<T: Clone + Default> Default for CowBox<T> {
+    fn default() -> Self {
+        CowBox::new(T::default())
+    }
+}
+
+impl<T: Clone + PartialEq> PartialEq for CowBox<T> {
+    fn eq(&self, other: &Self) -> bool {
+        CowBox::ptr_eq(self, other) || **self == **other
+    }
+}
+
+impl<T: Clone + Eq> Eq for CowBox<T> {}
+
+impl<T: Clone + fmt::Debug> fmt::Debug for CowBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: Clone + fmt::Display> fmt::Display for CowBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::collections::HashMap;
+
+    thread_local! {
+        static CLONES: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Counts how often it is cloned.
+    #[derive(Debug, PartialEq, Default)]
+    struct Config {
+        settings: HashMap<String, String>,
+    }
+
+    impl Clone for Config {
+        fn clone(&self) -> Self {
+            CLONES.with(|clones| clones.set(clones.get() + 1));
+            Config {
+                settings: self.settings.clone(),
+            }
+        }
+    }
+
+    fn clones() -> usize {
+        CLONES.with(Cell::get)
+    }
+
+    #[test]
+    fn clones_share_until_a_write() {
+        let original = CowBox::new(Config::default());
+        let copies: Vec<_> = (0..10).map(|_| original.clone()).collect();
+        assert_eq!(CowBox::strong_count(&original), 11);
+        assert!(copies.iter().all(|copy| CowBox::ptr_eq(copy, &original)));
+        // reading through `Deref` never clones
+        assert!(copies.iter().all(|copy| copy.settings.is_empty()));
+        assert_eq!(clones(), 0);
+
+        let mut changed = original.clone();
+        changed
+            .settings
+            .insert(String::from("mode"), String::from("fast"));
+        assert_eq!(clones(), 1);
+        assert!(!CowBox::ptr_eq(&changed, &original));
+        assert!(original.settings.is_empty());
+        assert_eq!(CowBox::strong_count(&original), 11);
+        assert!(CowBox::is_unique(&changed));
+
+        // once unique, further writes don't clone again
+        changed.settings.clear();
+        CowBox::make_mut(&mut changed)
+            .settings
+            .insert(String::from("a"), String::from("b"));
+        assert_eq!(clones(), 1);
+    }
+    #[test]
+    fn unique_boxes_are_written_in_place() {
+        let mut unique = CowBox::new(Config::default());
+        let before: *const Config = &*unique;
+        unique.settings.insert(String::from("k"), String::from("v"));
+        assert_eq!(before, &*unique as *const Config);
+        assert_eq!(clones(), 0);
+    }
+    #[test]
+    fn into_inner_only_clones_shared_values() {
+        let a = CowBox::new(Config::default());
+        let b = a.clone();
+        let _ = CowBox::into_inner(a);
+        assert_eq!(clones(), 1);
+        let _ = CowBox::into_inner(b); // the last one can be moved out
+        assert_eq!(clones(), 1);
+    }
+    #[test]
+    fn comparison_and_formatting() {
+        let a = CowBox::from(String::from("x"));
+        let mut b = a.clone();
+        assert_eq!(a, b);
+        b.push('y');
+        assert_ne!(a, b);
+        assert_eq!(format!("{} {:?}", b, a), "xy \"x\"");
+        assert_eq!(*CowBox::<i32>::default(), 0);
+    }
+}
@@ -0,0 +1,357 @@
+//! A directed graph stored in an [`Arena`], cycles included.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use crate::arena::{Arena, Id};
+
+/// A node of a [`Graph`] with its value and edges.
+pub struct Node<N> {
+    value: N,
+    successors: Vec<NodeId<N>>,
+    predecessors: Vec<NodeId<N>>,
+}
+
+impl<N> Node<N> {
+    pub fn value(&self) -> &N {
+        &self.value
+    }
+
+    /// Targets of the edges leaving this node.
+    pub fn successors(&self) -> &[NodeId<N>] {
+        &self.successors
+    }
+
+    /// Sources of the edges entering this node.
+    pub fn predecessors(&self) -> &[NodeId<N>] {
+        &self.predecessors
+    }
+}
+
+pub type NodeId<N> = Id<Node<N>>;
+
+/// Error of [`Graph::topological_sort`] for a graph with a cycle.
+pub struct CycleError<N> {
+    /// A node that is part of a cycle or can only be reached through one.
+    pub node: NodeId<N>,
+}
+
+// by hand for the same reason as the impls of `Id` in the arena
+impl<N> PartialEq for CycleError<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.node == other.node
+    }
+}
+
+impl<N> Eq for CycleError<N> {}
+
+impl<N> fmt::Debug for CycleError<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CycleError")
+            .field("node", &self.node)
+            .finish()
+    }
+}
+
+impl<N> fmt::Display for CycleError<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "the graph has a cycle through or before node {:?}",
+            self.node
+        )
+    }
+}
+
+impl<N> std::error::Error for CycleError<N> {}
+
+/// A directed graph whose nodes live in an arena and link to each other by id.
+///
+/// # Examples
+///
+/// ```
+/// use smart_pointers::graph::Graph;
+/// let mut graph = Graph::new();
+/// let shirt = graph.add_node("shirt");
+/// let tie = graph.add_node("tie");
+/// let jacket = graph.add_node("jacket");
+/// graph.add_edge(shirt, tie);
+/// graph.add_edge(tie, jacket);
+/// graph.add_edge(shirt, jacket);
+/// let order: Vec<_> = graph.topological_sort().unwrap().into_iter().map(|id| graph[id]).collect();
+/// assert_eq!(order, ["shirt", "tie", "jacket"]);
+/// // unlike `Rc` links, a cycle is fine and still freed with the graph
+/// graph.add_edge(jacket, shirt);
+/// assert!(graph.topological_sort().is_err());
+/// ```
+pub struct Graph<N> {
+    nodes: Arena<Node<N>>,
+    edge_count: usize,
+}
+
+impl<N> Graph<N> {
+    pub fn new() -> Graph<N> {
+        Graph {
+            nodes: Arena::new(),
+            edge_count: 0,
+        }
+    }
+
+    pub fn add_node(&mut self, value: N) -> NodeId<N> {
+        self.nodes.insert(Node {
+            value,
+            successors: Vec::new(),
+            predecessors: Vec::new(),
+        })
+    }
+
+    /// Remove a node together with all edges from and to it.
+    pub fn remove_node(&mut self, id: NodeId<N>) -> Option<N> {
+        let node = self.nodes.remove(id)?;
+        for successor in &node.successors {
+            // a self loop is already gone with the node
+            if let Some(successor) = self.nodes.get_mut(*successor) {
+                successor.predecessors.retain(|p| *p != id);
+            }
+        }
+        for predecessor in &node.predecessors {
+            if let Some(predecessor) = self.nodes.get_mut(*predecessor) {
+                predecessor.successors.retain(|s| *s != id);
+            }
+        }
+        self.edge_count -= node.successors.len() + node.predecessors.len();
+        if node.successors.contains(&id) {
+            self.edge_count += 1; // a self loop was counted in both lists
+        }
+        Some(node.value)
+    }
+
+    /// Add an edge from `from` to `to`. Returns `false` if it already exists.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either id is stale.
+    pub fn add_edge(&mut self, from: NodeId<N>, to: NodeId<N>) -> bool {
+        assert!(self.nodes.contains(to), "stale arena id {:?}", to);
+        if self.nodes[from].successors.contains(&to) {
+            return false;
+        }
+        self.nodes[from].successors.push(to);
+        self.nodes[to].predecessors.push(from);
+        self.edge_count += 1;
+        true
+    }
+
+    /// Remove the edge from `from` to `to`, `false` if there was none.
+    pub fn remove_edge(&mut self, from: NodeId<N>, to: NodeId<N>) -> bool {
+        let Some(node) = self.nodes.get_mut(from) else {
+            return false;
+        };
+        let before = node.successors.len();
+        node.successors.retain(|s| *s != to);
+        if node.successors.len() == before {
+            return false;
+        }
+        self.nodes[to].predecessors.retain(|p| *p != from);
+        self.edge_count -= 1;
+        true
+    }
+
+    pub fn node(&self, id: NodeId<N>) -> Option<&Node<N>> {
+        self.nodes.get(id)
+    }
+
+    pub fn get(&self, id: NodeId<N>) -> Option<&N> {
+        self.nodes.get(id).map(|node| &node.value)
+    }
+
+    pub fn get_mut(&mut self, id: NodeId<N>) -> Option<&mut N> {
+        self.nodes.get_mut(id).map(|node| &mut node.value)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edge_count
+    }
+
+    /// All node ids, in the order the arena stores them.
+    pub fn node_ids(&self) -> impl Iterator<Item = NodeId<N>> + '_ {
+        self.nodes.ids()
+    }
+
+    /// All nodes reachable from `start` (itself included), each before the
+    /// nodes it leads to along the first path found. Nodes on cycles are
+    /// visited once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is stale.
+    pub fn dfs(&self, start: NodeId<N>) -> Vec<NodeId<N>> {
+        let mut visited = HashSet::new();
+        let mut order = Vec::new();
+        let mut stack = vec![start];
+        while let Some(id) = stack.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            order.push(id);
+            // reversed, so the first successor is visited first
+            stack.extend(
+                self.nodes[id]
+                    .successors
+                    .iter()
+                    .rev()
+                    .filter(|s| !visited.contains(*s)),
+            );
+        }
+        order
+    }
+
+    /// The nodes ordered so that every edge points forward.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CycleError` if there is no such order because of a cycle.
+    pub fn topological_sort(&self) -> Result<Vec<NodeId<N>>, CycleError<N>> {
+        // Kahn's algorithm: repeatedly take a node without remaining incoming edges
+        let mut incoming: HashMap<NodeId<N>, usize> = self
+            .nodes
+            .iter()
+            .map(|(id, node)| (id, node.predecessors.len()))
+            .collect();
+        let mut ready: VecDeque<NodeId<N>> =
+            self.node_ids().filter(|id| incoming[id] == 0).collect();
+        let mut order = Vec::with_capacity(self.node_count());
+        while let Some(id) = ready.pop_front() {
+            order.push(id);
+            for successor in &self.nodes[id].successors {
+                let count = incoming
+                    .get_mut(successor)
+                    .expect("edges only point to live nodes");
+                *count -= 1;
+                if *count == 0 {
+                    ready.push_back(*successor);
+                }
+            }
+        }
+        match self.node_ids().find(|id| incoming[id] > 0) {
+            Some(node) => Err(CycleError { node }),
+            None => Ok(order),
+        }
+    }
+
+    pub fn has_cycle(&self) -> bool {
+        self.topological_sort().is_err()
+    }
+}
+
+impl<N> Default for Graph<N> {
+    fn default() -> Self {
+        Graph::new()
+    }
+}
+
+impl<N> std::ops::Index<NodeId<N>> for Graph<N> {
+    type Output = N;
+
+    fn index(&self, id: NodeId<N>) -> &N {
+        &self.nodes[id].value
+    }
+}
+
+impl<N> std::ops::IndexMut<NodeId<N>> for Graph<N> {
+    fn index_mut(&mut self, id: NodeId<N>) -> &mut N {
+        &mut self.nodes[id].value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    fn values(graph: &Graph<char>, ids: &[NodeId<char>]) -> String {
+        ids.iter().map(|id| graph[*id]).collect()
+    }
+
+    // a -> b -> d
+    // a -> c -> d -> e
+    fn diamond() -> (Graph<char>, Vec<NodeId<char>>) {
+        let mut graph = Graph::new();
+        let ids: Vec<_> = "abcde".chars().map(|c| graph.add_node(c)).collect();
+        for (from, to) in [(0, 1), (0, 2), (1, 3), (2, 3), (3, 4)] {
+            graph.add_edge(ids[from], ids[to]);
+        }
+        (graph, ids)
+    }
+
+    #[test]
+    fn edges_are_kept_in_both_directions() {
+        let (mut graph, ids) = diamond();
+        assert_eq!(graph.node_count(), 5);
+        assert_eq!(graph.edge_count(), 5);
+        assert!(!graph.add_edge(ids[0], ids[1]));
+        let d = graph.node(ids[3]).unwrap();
+        assert_eq!(values(&graph, d.predecessors()), "bc");
+        assert_eq!(values(&graph, d.successors()), "e");
+
+        assert!(graph.remove_edge(ids[0], ids[1]));
+        assert!(!graph.remove_edge(ids[0], ids[1]));
+        assert_eq!(
+            values(&graph, graph.node(ids[1]).unwrap().predecessors()),
+            ""
+        );
+        assert_eq!(graph.edge_count(), 4);
+    }
+    #[test]
+    fn dfs_visits_each_node_once() {
+        let (mut graph, ids) = diamond();
+        assert_eq!(values(&graph, &graph.dfs(ids[0])), "abdec");
+        assert_eq!(values(&graph, &graph.dfs(ids[2])), "cde");
+        graph.add_edge(ids[4], ids[0]);
+        assert_eq!(values(&graph, &graph.dfs(ids[3])), "deabc");
+    }
+    #[test]
+    fn topological_sort_and_cycles() {
+        let (mut graph, ids) = diamond();
+        assert_eq!(values(&graph, &graph.topological_sort().unwrap()), "abcde");
+        assert!(!graph.has_cycle());
+        graph.add_edge(ids[4], ids[1]);
+        let error = graph.topological_sort().unwrap_err();
+        assert!([ids[1], ids[3], ids[4]].contains(&error.node));
+        graph.remove_edge(ids[4], ids[1]);
+        let self_loop = graph.add_node('x');
+        graph.add_edge(self_loop, self_loop);
+        assert_eq!(
+            graph.topological_sort(),
+            Err(CycleError { node: self_loop })
+        );
+    }
+    #[test]
+    fn removing_a_node_removes_its_edges() {
+        let (mut graph, ids) = diamond();
+        graph.add_edge(ids[3], ids[3]);
+        assert_eq!(graph.edge_count(), 6);
+        assert_eq!(graph.remove_node(ids[3]), Some('d'));
+        assert_eq!(graph.edge_count(), 2);
+        assert_eq!(graph.get(ids[3]), None);
+        assert!(graph.node(ids[1]).unwrap().successors().is_empty());
+        assert!(graph.node(ids[4]).unwrap().predecessors().is_empty());
+        assert_eq!(values(&graph, &graph.topological_sort().unwrap()), "aebc");
+    }
+    #[test]
+    fn cycles_are_freed_with_the_graph() {
+        let counter = Rc::new(());
+        let mut graph = Graph::new();
+        let a = graph.add_node(Rc::clone(&counter));
+        let b = graph.add_node(Rc::clone(&counter));
+        graph.add_edge(a, b);
+        graph.add_edge(b, a);
+        assert_eq!(Rc::strong_count(&counter), 3);
+        drop(graph);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+}
@@ -0,0 +1,12 @@
+//! # smart_pointers
+//!
+//! Reusable data structures that grew out of the examples in `main.rs`.
+
+pub mod arena;
+pub mod cow_box;
+pub mod graph;
+pub mod linked_list;
+pub mod lru;
+pub mod quota;
+pub mod trace;
+pub mod tree;
@@ -0,0 +1,458 @@
+//! A doubly linked list built from `Rc`, `RefCell` and `Weak`.
+//!
+//! Every node owns the next one with an `Rc` and refers to the previous one
+//! with a `Weak`. Strong links in both directions would form a cycle
+//! between every pair of neighbours, and the nodes would never be freed.
+
+use std::cell::{Ref, RefCell, RefMut};
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+type Link<T> = Option<Rc<RefCell<Node<T>>>>;
+
+struct Node<T> {
+    value: T,
+    next: Link<T>,
+    prev: Weak<RefCell<Node<T>>>,
+}
+
+/// A node that has been pushed with `push_front_node`, for users in this
+/// crate that need to find it again without walking the list.
+pub(crate) struct NodeHandle<T>(Rc<RefCell<Node<T>>>);
+
+impl<T> NodeHandle<T> {
+    pub(crate) fn value(&self) -> Ref<'_, T> {
+        Ref::map(self.0.borrow(), |node| &node.value)
+    }
+
+    pub(crate) fn value_mut(&self) -> RefMut<'_, T> {
+        RefMut::map(self.0.borrow_mut(), |node| &mut node.value)
+    }
+}
+
+/// A list that can grow and shrink at both ends.
+///
+/// # Examples
+///
+/// ```
+/// use smart_pointers::linked_list::LinkedList;
+/// let mut list: LinkedList<i32> = [2, 3].into_iter().collect();
+/// list.push_front(1);
+/// list.push_back(4);
+/// assert_eq!(list.pop_back(), Some(4));
+/// // a cursor edits the list in place
+/// let mut cursor = list.cursor_front_mut();
+/// cursor.move_next();
+/// *cursor.current().unwrap() *= 10;
+/// cursor.insert_after(25);
+/// assert_eq!(list.to_vec(), [1, 20, 25, 3]);
+/// ```
+pub struct LinkedList<T> {
+    head: Link<T>,
+    // `Weak`, the last node is already owned by the one before it
+    tail: Weak<RefCell<Node<T>>>,
+    len: usize,
+}
+
+impl<T> LinkedList<T> {
+    pub fn new() -> LinkedList<T> {
+        LinkedList {
+            head: None,
+            tail: Weak::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push_front(&mut self, value: T) {
+        self.push_front_node(value);
+    }
+
+    pub fn push_back(&mut self, value: T) {
+        let node = new_node(value);
+        match self.tail.upgrade() {
+            Some(tail) => self.link_after(&tail, Rc::clone(&node)),
+            None => self.link_front(node),
+        }
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        let head = self.head.clone()?;
+        Some(self.remove_node(NodeHandle(head)))
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        let tail = self.tail.upgrade()?;
+        Some(self.remove_node(NodeHandle(tail)))
+    }
+
+    pub fn front(&self) -> Option<Ref<'_, T>> {
+        self.head
+            .as_ref()
+            .map(|node| Ref::map(node.borrow(), |node| &node.value))
+    }
+
+    pub fn front_mut(&mut self) -> Option<RefMut<'_, T>> {
+        self.head
+            .as_ref()
+            .map(|node| RefMut::map(node.borrow_mut(), |node| &mut node.value))
+    }
+
+    /// The last value. It is returned as a copy since the list only has a
+    /// `Weak` to the last node, and a borrow can't outlive the upgraded `Rc`.
+    pub fn back(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.tail.upgrade().map(|node| node.borrow().value.clone())
+    }
+
+    /// Copies of all values from front to back.
+    pub fn to_vec(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut values = Vec::with_capacity(self.len);
+        self.for_each(|value| values.push(value.clone()));
+        values
+    }
+
+    /// Call `f` with every value from front to back.
+    pub fn for_each(&self, mut f: impl FnMut(&T)) {
+        let mut current = self.head.clone();
+        while let Some(node) = current {
+            let node = node.borrow();
+            f(&node.value);
+            current = node.next.clone();
+        }
+    }
+
+    /// A cursor at the first element, or at the "ghost" position if the
+    /// list is empty.
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            current: self.head.clone(),
+            list: self,
+        }
+    }
+
+    /// A cursor at the last element, or at the "ghost" position if the
+    /// list is empty.
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            current: self.tail.upgrade(),
+            list: self,
+        }
+    }
+
+    pub(crate) fn push_front_node(&mut self, value: T) -> NodeHandle<T> {
+        let node = new_node(value);
+        self.link_front(Rc::clone(&node));
+        NodeHandle(node)
+    }
+
+    pub(crate) fn back_node(&self) -> Option<NodeHandle<T>> {
+        self.tail.upgrade().map(NodeHandle)
+    }
+
+    /// Move a node of this list to the front.
+    pub(crate) fn move_to_front(&mut self, handle: &NodeHandle<T>) {
+        self.unlink(&handle.0);
+        self.link_front(Rc::clone(&handle.0));
+    }
+
+    /// Remove a node of this list and return its value.
+    pub(crate) fn remove_node(&mut self, handle: NodeHandle<T>) -> T {
+        self.unlink(&handle.0);
+        let node = Rc::try_unwrap(handle.0)
+            .unwrap_or_else(|_| panic!("node is still referenced elsewhere"));
+        node.into_inner().value
+    }
+
+    fn link_front(&mut self, node: Rc<RefCell<Node<T>>>) {
+        match self.head.take() {
+            Some(old_head) => {
+                old_head.borrow_mut().prev = Rc::downgrade(&node);
+                node.borrow_mut().next = Some(old_head);
+            }
+            None => self.tail = Rc::downgrade(&node),
+        }
+        self.head = Some(node);
+        self.len += 1;
+    }
+
+    fn link_after(&mut self, prev: &Rc<RefCell<Node<T>>>, node: Rc<RefCell<Node<T>>>) {
+        let next = prev.borrow_mut().next.take();
+        match &next {
+            Some(next) => next.borrow_mut().prev = Rc::downgrade(&node),
+            None => self.tail = Rc::downgrade(&node),
+        }
+        {
+            let mut new = node.borrow_mut();
+            new.prev = Rc::downgrade(prev);
+            new.next = next;
+        }
+        prev.borrow_mut().next = Some(node);
+        self.len += 1;
+    }
+
+    /// Take `node` out of the list, the caller keeps it alive.
+    fn unlink(&mut self, node: &Rc<RefCell<Node<T>>>) {
+        let (prev, next) = {
+            let mut node = node.borrow_mut();
+            let prev = std::mem::take(&mut node.prev).upgrade();
+            (prev, node.next.take())
+        };
+        match &next {
+            Some(next) => {
+                next.borrow_mut().prev = prev.as_ref().map_or_else(Weak::new, Rc::downgrade)
+            }
+            None => self.tail = prev.as_ref().map_or_else(Weak::new, Rc::downgrade),
+        }
+        match prev {
+            Some(prev) => prev.borrow_mut().next = next,
+            None => self.head = next,
+        }
+        self.len -= 1;
+    }
+}
+
+fn new_node<T>(value: T) -> Rc<RefCell<Node<T>>> {
+    Rc::new(RefCell::new(Node {
+        value,
+        next: None,
+        prev: Weak::new(),
+    }))
+}
+
+impl<T> Drop for LinkedList<T> {
+    fn drop(&mut self) {
+        // one node at a time, the default drop would recurse through all of them
+        let mut next = self.head.take();
+        while let Some(node) = next {
+            next = node.borrow_mut().next.take();
+        }
+    }
+}
+
+impl<T> Default for LinkedList<T> {
+    fn default() -> Self {
+        LinkedList::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for LinkedList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut list = f.debug_list();
+        self.for_each(|value| {
+            list.entry(value);
+        });
+        list.finish()
+    }
+}
+
+impl<T> FromIterator<T> for LinkedList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = LinkedList::new();
+        for value in iter {
+            list.push_back(value);
+        }
+        list
+    }
+}
+
+/// Iterator over the values of a [`LinkedList`], taken from the front.
+pub struct IntoIter<T>(LinkedList<T>);
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.0.pop_back()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> IntoIterator for LinkedList<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
+/// A position in a [`LinkedList`] for editing it in place.
+///
+/// Besides the elements there is a "ghost" position between the back and
+/// the front, where `current` is `None`. Moving past either end goes there.
+pub struct CursorMut<'a, T> {
+    list: &'a mut LinkedList<T>,
+    current: Link<T>,
+}
+
+impl<T> CursorMut<'_, T> {
+    /// The element under the cursor, `None` at the ghost position.
+    pub fn current(&mut self) -> Option<RefMut<'_, T>> {
+        self.current
+            .as_ref()
+            .map(|node| RefMut::map(node.borrow_mut(), |node| &mut node.value))
+    }
+
+    /// Move towards the back, from the ghost position to the front.
+    pub fn move_next(&mut self) {
+        self.current = match self.current.take() {
+            Some(node) => node.borrow().next.clone(),
+            None => self.list.head.clone(),
+        };
+    }
+
+    /// Move towards the front, from the ghost position to the back.
+    pub fn move_prev(&mut self) {
+        self.current = match self.current.take() {
+            Some(node) => node.borrow().prev.upgrade(),
+            None => self.list.tail.upgrade(),
+        };
+    }
+
+    /// Insert after the current element, at the ghost position that is the
+    /// front of the list. The cursor does not move.
+    pub fn insert_after(&mut self, value: T) {
+        match &self.current {
+            Some(current) => self.list.link_after(current, new_node(value)),
+            None => self.list.link_front(new_node(value)),
+        }
+    }
+
+    /// Insert before the current element, at the ghost position that is the
+    /// back of the list. The cursor does not move.
+    pub fn insert_before(&mut self, value: T) {
+        let prev = match &self.current {
+            Some(current) => current.borrow().prev.upgrade(),
+            None => self.list.tail.upgrade(),
+        };
+        match prev {
+            Some(prev) => self.list.link_after(&prev, new_node(value)),
+            None => self.list.link_front(new_node(value)),
+        }
+    }
+
+    /// Remove the current element and move to the next one. `None` at the
+    /// ghost position.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let node = self.current.take()?;
+        self.current = node.borrow().next.clone();
+        Some(self.list.remove_node(NodeHandle(node)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_and_pop_at_both_ends() {
+        let mut list = LinkedList::new();
+        list.push_back(2);
+        list.push_front(1);
+        list.push_back(3);
+        assert_eq!(list.len(), 3);
+        assert_eq!(*list.front().unwrap(), 1);
+        assert_eq!(list.back(), Some(3));
+        *list.front_mut().unwrap() = 0;
+        assert_eq!(list.pop_front(), Some(0));
+        assert_eq!(list.pop_back(), Some(3));
+        assert_eq!(list.pop_back(), Some(2));
+        assert_eq!(list.pop_back(), None);
+        assert_eq!(list.pop_front(), None);
+        assert!(list.is_empty());
+        assert!(list.front().is_none());
+    }
+    #[test]
+    fn iterates_in_both_directions() {
+        let list: LinkedList<_> = (1..=4).collect();
+        assert_eq!(format!("{:?}", list), "[1, 2, 3, 4]");
+        assert_eq!(list.into_iter().rev().collect::<Vec<_>>(), [4, 3, 2, 1]);
+    }
+    #[test]
+    fn cursor_moves_through_the_ghost_position() {
+        let mut list: LinkedList<_> = (1..=3).collect();
+        let mut cursor = list.cursor_back_mut();
+        assert_eq!(cursor.current().as_deref(), Some(&3));
+        cursor.move_next();
+        assert!(cursor.current().is_none());
+        cursor.move_next();
+        assert_eq!(cursor.current().as_deref(), Some(&1));
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.current().as_deref(), Some(&3));
+    }
+    #[test]
+    fn cursor_inserts_and_removes() {
+        let mut list: LinkedList<_> = [1, 3, 5].into_iter().collect();
+        let mut cursor = list.cursor_front_mut();
+        cursor.insert_before(0);
+        cursor.insert_after(2);
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(3));
+        assert_eq!(cursor.current().as_deref(), Some(&5));
+        cursor.insert_after(6);
+        cursor.move_next();
+        cursor.move_next(); // ghost
+        cursor.insert_before(7);
+        cursor.insert_after(-1);
+        assert_eq!(cursor.remove_current(), None);
+        assert_eq!(list.to_vec(), [-1, 0, 1, 2, 5, 6, 7]);
+        assert_eq!(list.len(), 7);
+        assert_eq!(list.back(), Some(7));
+
+        let mut cursor = list.cursor_back_mut();
+        while cursor.remove_current().is_some() {
+            cursor.move_prev();
+        }
+        assert!(list.is_empty());
+    }
+    #[test]
+    fn nodes_are_freed() {
+        let mut list: LinkedList<_> = (0..3).collect();
+        let weak: Vec<_> = {
+            let mut weak = vec![];
+            let mut current = list.head.clone();
+            while let Some(node) = current {
+                weak.push(Rc::downgrade(&node));
+                current = node.borrow().next.clone();
+            }
+            weak
+        };
+        // owned once by the previous node (or the head), never by the next one
+        assert!(weak.iter().all(|w| w.strong_count() == 1));
+        list.pop_back();
+        assert_eq!(weak[2].strong_count(), 0);
+        drop(list);
+        assert!(weak.iter().all(|w| w.strong_count() == 0));
+    }
+    #[test]
+    fn long_lists_drop_without_overflow() {
+        let list: LinkedList<u32> = (0..1_000_000).collect();
+        drop(list);
+    }
+}
@@ -0,0 +1,231 @@
+//! A least-recently-used cache on top of [`LinkedList`].
+//!
+//! The list keeps the entries from most to least recently used, and a
+//! `HashMap` points from every key to its node, so lookups, moving an entry
+//! to the front and evicting from the back are all O(1).
+
+use std::cell::Ref;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::linked_list::{LinkedList, NodeHandle};
+
+/// Hit and miss counts of a [`LruCache`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+impl CacheStats {
+    /// Share of lookups that were hits, `0.0` before the first lookup.
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+/// A map with a fixed capacity that forgets the least recently used entry
+/// when it is full.
+///
+/// # Examples
+///
+/// ```
+/// use smart_pointers::lru::LruCache;
+/// let mut cache = LruCache::new(2);
+/// cache.put("a", 1);
+/// cache.put("b", 2);
+/// assert_eq!(cache.get(&"a").as_deref(), Some(&1)); // "a" is now the most recent
+/// cache.put("c", 3); // so "b" gets evicted
+/// assert!(cache.get(&"b").is_none());
+/// assert_eq!(cache.stats().evictions, 1);
+/// ```
+pub struct LruCache<K, V> {
+    // the key is stored in the list too, to find the map entry when evicting
+    entries: LinkedList<(K, V)>,
+    index: HashMap<K, NodeHandle<(K, V)>>,
+    capacity: usize,
+    stats: CacheStats,
+}
+
+impl<K: Hash + Eq + Clone, V> LruCache<K, V> {
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> LruCache<K, V> {
+        assert!(capacity > 0, "cache capacity must be greater than zero");
+        LruCache {
+            entries: LinkedList::new(),
+            index: HashMap::with_capacity(capacity),
+            capacity,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// The value for `key`, which becomes the most recently used entry.
+    pub fn get(&mut self, key: &K) -> Option<Ref<'_, V>> {
+        match self.index.get(key) {
+            Some(handle) => {
+                self.stats.hits += 1;
+                self.entries.move_to_front(handle);
+                Some(Ref::map(handle.value(), |(_, value)| value))
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Look at the value for `key` without changing the order or the stats.
+    pub fn peek(&self, key: &K) -> Option<Ref<'_, V>> {
+        self.index
+            .get(key)
+            .map(|handle| Ref::map(handle.value(), |(_, value)| value))
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /// Insert or update the value for `key`, making it the most recently
+    /// used entry. Returns the old value; if the cache was full, the least
+    /// recently used entry is evicted first.
+    pub fn put(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(handle) = self.index.get(&key) {
+            self.entries.move_to_front(handle);
+            let old = std::mem::replace(&mut handle.value_mut().1, value);
+            return Some(old);
+        }
+        if self.index.len() == self.capacity {
+            self.evict();
+        }
+        let handle = self.entries.push_front_node((key.clone(), value));
+        self.index.insert(key, handle);
+        None
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let handle = self.index.remove(key)?;
+        Some(self.entries.remove_node(handle).1)
+    }
+
+    fn evict(&mut self) {
+        let Some(last) = self.entries.back_node() else {
+            return;
+        };
+        let key = last.value().0.clone();
+        drop(last);
+        // the node can only be taken apart once the map's handle is gone too
+        if let Some(handle) = self.index.remove(&key) {
+            self.entries.remove_node(handle);
+            self.stats.evictions += 1;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Keys from most to least recently used.
+    pub fn keys(&self) -> Vec<K> {
+        let mut keys = Vec::with_capacity(self.len());
+        self.entries.for_each(|(key, _)| keys.push(key.clone()));
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_the_least_recently_used_entry() {
+        let mut cache = LruCache::new(3);
+        for (key, value) in [("a", 1), ("b", 2), ("c", 3)] {
+            assert_eq!(cache.put(key, value), None);
+        }
+        assert_eq!(cache.keys(), ["c", "b", "a"]);
+        cache.get(&"a");
+        cache.put("d", 4);
+        assert_eq!(cache.keys(), ["d", "a", "c"]);
+        assert!(!cache.contains(&"b"));
+        assert_eq!(cache.len(), 3);
+    }
+    #[test]
+    fn updating_a_key_moves_it_to_the_front() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, "one");
+        cache.put(2, "two");
+        assert_eq!(cache.put(1, "uno"), Some("one"));
+        cache.put(3, "three");
+        assert_eq!(cache.keys(), [3, 1]);
+        assert_eq!(cache.peek(&1).as_deref(), Some(&"uno"));
+    }
+    #[test]
+    fn peek_does_not_change_the_order() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, 1);
+        cache.put(2, 2);
+        assert!(cache.peek(&1).is_some());
+        cache.put(3, 3);
+        assert!(!cache.contains(&1));
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 0,
+                misses: 0,
+                evictions: 1
+            }
+        );
+    }
+    #[test]
+    fn counts_hits_and_misses() {
+        let mut cache = LruCache::new(1);
+        assert_eq!(cache.stats().hit_ratio(), 0.0);
+        cache.put("k", 1);
+        assert!(cache.get(&"k").is_some());
+        assert!(cache.get(&"k").is_some());
+        assert!(cache.get(&"x").is_none());
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (2, 1));
+        assert!((stats.hit_ratio() - 2.0 / 3.0).abs() < 1e-9);
+    }
+    #[test]
+    fn remove_and_refill() {
+        let mut cache = LruCache::new(2);
+        cache.put("a", String::from("x"));
+        assert_eq!(cache.remove(&"a"), Some(String::from("x")));
+        assert_eq!(cache.remove(&"a"), None);
+        assert!(cache.is_empty());
+        for i in 0..100 {
+            cache.put("k", i.to_string());
+            cache.put("other", i.to_string());
+        }
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.capacity(), 2);
+        assert_eq!(cache.stats().evictions, 0);
+    }
+    #[test]
+    #[should_panic(expected = "greater than zero")]
+    fn zero_capacity_panics() {
+        LruCache::<u8, u8>::new(0);
+    }
+}
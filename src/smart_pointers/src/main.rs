@@ -1,3 +1,7 @@
+// count every heap allocation, see `drop_trait`
+#[global_allocator]
+static ALLOCATOR: smart_pointers::trace::TracingAllocator = smart_pointers::trace::TracingAllocator;
+
 fn box_sizing() {
     // the size of an enum is calculated from its biggest element
     // since `Cons` has a `List` again, the size calculation would be infinite
@@ -5,6 +9,7 @@ fn box_sizing() {
     // but a pointer to it with a fixed size (now the size for `List` can be calculated)
     // instead of storing elements inside of each other its more like next to each other
     #[derive(Debug)]
+    #[allow(dead_code)] // the fields are only shown with `{:?}`
     enum List {
         Cons(i32, Box<List>),
         Nil,
@@ -43,6 +48,16 @@ fn deref_trait() {
     }
     let m = MyBox::new(String::from("Rust"));
     hello(&m);
+
+    // `DerefMut` makes a box writable too. `CowBox` shares its value on `clone()`
+    // and only copies it on the first write through `DerefMut`
+    use smart_pointers::cow_box::CowBox;
+    let original = CowBox::new(String::from("Rust"));
+    let mut copy = original.clone();
+    println!("sharing before write: {}", CowBox::ptr_eq(&original, &copy));
+    copy.push_str("acean"); // deref coercion again, now to `&mut String`
+    hello(&copy);
+    println!("sharing after write: {}", CowBox::ptr_eq(&original, &copy));
 }
 
 fn drop_trait() {
@@ -70,9 +85,24 @@ fn drop_trait() {
         };
         println!("CustomSmartPointers created: '{:?}' and '{:?}'.", d, e);
     } // d and e go out of scope, so `Drop::drop` is called in reverse order of creation
+
+    // instead of writing a `Drop` impl with a `println!` for every type, wrap the values
+    use smart_pointers::trace::{Tracer, TracingAllocator};
+    let tracer = Tracer::new();
+    let ((), allocations) = TracingAllocator::measure(|| {
+        let d = tracer.wrap(String::from("other stuff"), "d");
+        let e = tracer.wrap(String::from("stuff stuff"), "e");
+        let shared = d.clone().into_rc(); // clones and moves are recorded too
+        drop(e);
+        drop(shared);
+    });
+    print!("{}", tracer.timeline());
+    tracer.assert_drop_order(&["e", "d", "d"]); // the clone is dropped with the `Rc`, before `d`
+    println!("allocations while tracing: {:?}", allocations);
 }
 
 fn reference_counting() {
+    #[allow(dead_code)] // only built to watch the reference counts
     enum List {
         Cons(i32, Rc<List>),
         Nil,
@@ -88,6 +118,18 @@ fn reference_counting() {
         println!("count after creating c = {}", Rc::strong_count(&a));
     }
     println!("count after c goes out of scope = {}", Rc::strong_count(&a));
+
+    // the same idea as a reusable library: `cons` shares the existing nodes
+    // instead of copying them, and the old list stays as it was
+    use persistent_list::List as SharedList;
+    let a: SharedList<i32> = [5, 10].into_iter().collect();
+    let b = a.cons(3);
+    let c = a.cons(4);
+    println!("a = {:?}, b = {:?}, c = {:?}", a, b, c);
+    println!(
+        "b and c share a: {}",
+        b.tail().unwrap().ptr_eq(&c.tail().unwrap())
+    );
 }
 
 fn interior_mutability() {
@@ -151,10 +193,19 @@ fn interior_mutability() {
     limit_tracker.set_value(80);
     // ... and borrow() to return a smart pointer type (Ref<T>) here
     assert_eq!(my_messenger.sent_messages.borrow().len(), 1);
+
+    // the library version tracks several tenants and resources, each warning is only sent once
+    use smart_pointers::quota::{QuotaPolicy, QuotaService, StderrMessenger};
+    let mut quotas = QuotaService::new(&StderrMessenger);
+    quotas.set_policy("storage", QuotaPolicy::new(100));
+    for value in [80, 85, 95] {
+        quotas.set_value("acme", "storage", value).unwrap(); // 85 doesn't repeat the 75% warning
+    }
 }
 
 fn combined() {
     #[derive(Debug)]
+    #[allow(dead_code)] // the fields are only shown with `{:?}`
     enum List {
         Cons(Rc<RefCell<i32>>, Rc<List>), // combine `Rc` with `RefCell`
         Nil,
@@ -173,6 +224,52 @@ fn combined() {
     println!("a after = {:?}", a);
     println!("b after = {:?}", b);
     println!("c after = {:?}", c);
+
+    // for graph-shaped data an arena is simpler: it owns all the nodes and the links are ids,
+    // so there is no borrow checking at runtime and cycles don't leak
+    use smart_pointers::graph::Graph;
+    let mut graph = Graph::new();
+    let a = graph.add_node(5);
+    let b = graph.add_node(3);
+    let c = graph.add_node(4);
+    graph.add_edge(b, a);
+    graph.add_edge(c, a);
+    graph[a] += 10; // the shared value can be changed through the graph
+    let order: Vec<_> = graph
+        .topological_sort()
+        .unwrap()
+        .iter()
+        .map(|id| graph[*id])
+        .collect();
+    println!("graph in topological order = {:?}", order);
+    graph.add_edge(a, b); // a cycle, which would leak with `Rc`
+    println!("graph has a cycle: {}", graph.has_cycle());
+}
+
+fn weak_references() {
+    use smart_pointers::tree::Tree;
+    // a parent owns its children (`Rc`), but a child only refers to its parent (`Weak`)
+    // otherwise both would keep each other alive and never be dropped
+    let leaf = Tree::new(3);
+    println!("leaf parent = {:?}", leaf.parent()); // `None`, a new node is a root
+    {
+        let branch = Tree::new(5);
+        branch.attach(&leaf);
+        // `parent()` upgrades the `Weak` to get a handle, which works while the branch lives
+        println!("leaf parent = {:?}", leaf.parent());
+        println!("leaf depth = {}", leaf.depth());
+    } // the branch is dropped here, since the leaf doesn't own it
+    println!("leaf parent = {:?}", leaf.parent()); // `None` again
+
+    let root = Tree::new("fruit");
+    let apples = root.add_child("apple");
+    apples.add_child("braeburn");
+    apples.add_child("gala");
+    root.add_child("pear");
+    let depth_first: Vec<_> = root.depth_first().map(|node| *node.value()).collect();
+    let breadth_first: Vec<_> = root.breadth_first().map(|node| *node.value()).collect();
+    println!("depth first: {:?}", depth_first);
+    println!("breadth first: {:?}", breadth_first);
 }
 
 fn main() {
@@ -182,4 +279,5 @@ fn main() {
     reference_counting(); // multiple owners
     interior_mutability(); // mutable data
     combined(); // multiple owners of mutable data
+    weak_references(); // owners and non-owners
 }
@@ -0,0 +1,495 @@
+//! Quota enforcement, the grown-up version of `LimitTracker` in `main.rs`.
+//!
+//! A [`QuotaService`] tracks the usage of several resources per tenant.
+//! Every resource has a [`QuotaPolicy`] with its own limit, warning levels
+//! and an optional reset period. Crossing a level sends its message once;
+//! it is only sent again after the usage dropped below that level.
+//!
+//! Like `LimitTracker`, the service only has a shared reference to its
+//! [`Messenger`], so messengers that record something need interior
+//! mutability.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Somewhere to send quota notifications to.
+pub trait Messenger {
+    fn send(&self, msg: &str);
+}
+
+/// Prints every message to stderr.
+pub struct StderrMessenger;
+
+impl Messenger for StderrMessenger {
+    fn send(&self, msg: &str) {
+        eprintln!("{}", msg);
+    }
+}
+
+/// Appends every message as a line to a log file.
+pub struct FileMessenger {
+    // `send` only gets `&self`, but writing needs `&mut File`
+    file: RefCell<File>,
+    failed_writes: Cell<usize>,
+}
+
+impl FileMessenger {
+    /// Open `path` for appending, creating it if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of opening the file.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<FileMessenger> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(FileMessenger {
+            file: RefCell::new(file),
+            failed_writes: Cell::new(0),
+        })
+    }
+
+    /// Number of messages that could not be written. `send` has no way to
+    /// report errors, so they are counted instead.
+    pub fn failed_writes(&self) -> usize {
+        self.failed_writes.get()
+    }
+}
+
+impl Messenger for FileMessenger {
+    fn send(&self, msg: &str) {
+        if writeln!(self.file.borrow_mut(), "{}", msg).is_err() {
+            self.failed_writes.set(self.failed_writes.get() + 1);
+        }
+    }
+}
+
+/// Keeps all messages, meant for tests.
+#[derive(Default)]
+pub struct MemoryMessenger {
+    messages: RefCell<Vec<String>>,
+}
+
+impl MemoryMessenger {
+    pub fn new() -> MemoryMessenger {
+        MemoryMessenger::default()
+    }
+
+    pub fn messages(&self) -> Vec<String> {
+        self.messages.borrow().clone()
+    }
+
+    /// Return the messages sent so far and forget them.
+    pub fn take(&self) -> Vec<String> {
+        self.messages.take()
+    }
+}
+
+impl Messenger for MemoryMessenger {
+    fn send(&self, msg: &str) {
+        self.messages.borrow_mut().push(String::from(msg));
+    }
+}
+
+/// A warning level: once usage reaches `fraction` of the limit, `message`
+/// is sent.
+///
+/// The message may contain the placeholders `{tenant}`, `{resource}`,
+/// `{value}`, `{max}` and `{percent}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Threshold {
+    pub fraction: f64,
+    pub message: String,
+}
+
+impl Threshold {
+    pub fn new(fraction: f64, message: &str) -> Threshold {
+        Threshold {
+            fraction,
+            message: String::from(message),
+        }
+    }
+}
+
+/// Limit, warning levels and reset period of one resource.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaPolicy {
+    max: usize,
+    thresholds: Vec<Threshold>,
+    reset_every: Option<Duration>,
+}
+
+impl QuotaPolicy {
+    /// A policy with the same levels and messages as `LimitTracker`.
+    pub fn new(max: usize) -> QuotaPolicy {
+        QuotaPolicy {
+            max,
+            thresholds: vec![
+                Threshold::new(0.75, "Warning: You've used up over 75% of your quota!"),
+                Threshold::new(
+                    0.9,
+                    "Urgent warning: You've used up over 90% of your quota!",
+                ),
+                Threshold::new(1.0, "Error: You are over your quota!"),
+            ],
+            reset_every: None,
+        }
+    }
+
+    /// Replace the warning levels, they are sorted by `fraction`.
+    pub fn with_thresholds(mut self, mut thresholds: Vec<Threshold>) -> QuotaPolicy {
+        thresholds.sort_by(|a, b| a.fraction.total_cmp(&b.fraction));
+        self.thresholds = thresholds;
+        self
+    }
+
+    /// Set usage back to zero every `period`, like a monthly allowance.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    pub fn with_reset_every(mut self, period: Duration) -> QuotaPolicy {
+        assert!(period > Duration::ZERO, "the reset period must not be zero");
+        self.reset_every = Some(period);
+        self
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Index of the highest threshold that `value` reaches.
+    fn level(&self, value: usize) -> Option<usize> {
+        let fraction = value as f64 / self.max as f64;
+        self.thresholds.iter().rposition(|t| fraction >= t.fraction)
+    }
+}
+
+/// Error of [`QuotaService`] methods.
+#[derive(Debug, PartialEq, Eq)]
+pub enum QuotaError {
+    /// No policy was set for the resource.
+    UnknownResource(String),
+}
+
+impl fmt::Display for QuotaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QuotaError::UnknownResource(resource) => {
+                write!(f, "no quota policy for resource `{}`", resource)
+            }
+        }
+    }
+}
+
+impl std::error::Error for QuotaError {}
+
+struct Usage {
+    value: usize,
+    // the highest level a message was sent for, so it isn't sent again
+    notified: Option<usize>,
+    period_start: Instant,
+}
+
+/// Tracks usage per tenant and resource and notifies a [`Messenger`].
+///
+/// # Examples
+///
+/// ```
+/// use smart_pointers::quota::{MemoryMessenger, QuotaPolicy, QuotaService};
+/// let messenger = MemoryMessenger::new();
+/// let mut quotas = QuotaService::new(&messenger);
+/// quotas.set_policy("api_calls", QuotaPolicy::new(100));
+/// quotas.set_value("acme", "api_calls", 80).unwrap();
+/// quotas.set_value("acme", "api_calls", 85).unwrap(); // still the same level
+/// assert_eq!(messenger.take(), ["Warning: You've used up over 75% of your quota!"]);
+/// ```
+pub struct QuotaService<'a, M: Messenger> {
+    messenger: &'a M,
+    policies: HashMap<String, QuotaPolicy>,
+    usage: HashMap<(String, String), Usage>,
+    clock: Box<dyn Fn() -> Instant + 'a>,
+}
+
+impl<'a, M: Messenger> QuotaService<'a, M> {
+    pub fn new(messenger: &'a M) -> QuotaService<'a, M> {
+        QuotaService::with_clock(messenger, Instant::now)
+    }
+
+    /// Use `clock` instead of the system time for reset periods, so tests
+    /// don't have to wait.
+    pub fn with_clock(messenger: &'a M, clock: impl Fn() -> Instant + 'a) -> QuotaService<'a, M> {
+        QuotaService {
+            messenger,
+            policies: HashMap::new(),
+            usage: HashMap::new(),
+            clock: Box::new(clock),
+        }
+    }
+
+    /// Set or replace the policy of `resource`. Existing usage is kept.
+    pub fn set_policy(&mut self, resource: &str, policy: QuotaPolicy) {
+        self.policies.insert(String::from(resource), policy);
+    }
+
+    /// Record the current usage of `resource` by `tenant`, sending the
+    /// message of a newly reached level.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UnknownResource` if `resource` has no policy.
+    pub fn set_value(
+        &mut self,
+        tenant: &str,
+        resource: &str,
+        value: usize,
+    ) -> Result<(), QuotaError> {
+        let policy = self
+            .policies
+            .get(resource)
+            .ok_or_else(|| QuotaError::UnknownResource(String::from(resource)))?;
+        let now = (self.clock)();
+        let usage = self
+            .usage
+            .entry((String::from(tenant), String::from(resource)))
+            .or_insert(Usage {
+                value: 0,
+                notified: None,
+                period_start: now,
+            });
+        let usage = reset_if_due(usage, policy, now);
+        usage.value = value;
+
+        let level = policy.level(value);
+        if level > usage.notified {
+            if let Some(index) = level {
+                let message = render(
+                    &policy.thresholds[index].message,
+                    tenant,
+                    resource,
+                    value,
+                    policy.max,
+                );
+                self.messenger.send(&message);
+            }
+        }
+        // dropping below a level allows its message to be sent again
+        usage.notified = level;
+        Ok(())
+    }
+
+    /// Add `amount` to the usage, see `set_value`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UnknownResource` if `resource` has no policy.
+    pub fn add(&mut self, tenant: &str, resource: &str, amount: usize) -> Result<(), QuotaError> {
+        let value = self.value(tenant, resource)?;
+        self.set_value(tenant, resource, value.saturating_add(amount))
+    }
+
+    /// The current usage, zero if nothing has been recorded yet or the
+    /// reset period is over.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UnknownResource` if `resource` has no policy.
+    pub fn value(&mut self, tenant: &str, resource: &str) -> Result<usize, QuotaError> {
+        let policy = self
+            .policies
+            .get(resource)
+            .ok_or_else(|| QuotaError::UnknownResource(String::from(resource)))?;
+        let now = (self.clock)();
+        Ok(self
+            .usage
+            .get_mut(&(String::from(tenant), String::from(resource)))
+            .map_or(0, |usage| reset_if_due(usage, policy, now).value))
+    }
+
+    /// Whether the usage is below the limit.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UnknownResource` if `resource` has no policy.
+    pub fn is_within_quota(&mut self, tenant: &str, resource: &str) -> Result<bool, QuotaError> {
+        let value = self.value(tenant, resource)?;
+        Ok(value < self.policies[resource].max)
+    }
+
+    /// Set the usage back to zero right away, without waiting for the period.
+    pub fn reset(&mut self, tenant: &str, resource: &str) {
+        self.usage
+            .remove(&(String::from(tenant), String::from(resource)));
+    }
+}
+
+/// Start a new period if the current one is over.
+fn reset_if_due<'u>(usage: &'u mut Usage, policy: &QuotaPolicy, now: Instant) -> &'u mut Usage {
+    if let Some(period) = policy.reset_every {
+        let elapsed = now.duration_since(usage.period_start);
+        if elapsed >= period {
+            usage.value = 0;
+            usage.notified = None;
+            // whole periods, so the boundaries don't drift with the calls
+            let into_period = elapsed.as_nanos() % period.as_nanos();
+            // less than `period`, so the seconds fit
+            let into_period = Duration::new(
+                (into_period / 1_000_000_000) as u64,
+                (into_period % 1_000_000_000) as u32,
+            );
+            usage.period_start = now - into_period;
+        }
+    }
+    usage
+}
+
+fn render(template: &str, tenant: &str, resource: &str, value: usize, max: usize) -> String {
+    let percent = value * 100 / max.max(1);
+    template
+        .replace("{tenant}", tenant)
+        .replace("{resource}", resource)
+        .replace("{value}", &value.to_string())
+        .replace("{max}", &max.to_string())
+        .replace("{percent}", &percent.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    const WARNING: &str = "Warning: You've used up over 75% of your quota!";
+    const URGENT: &str = "Urgent warning: You've used up over 90% of your quota!";
+    const ERROR: &str = "Error: You are over your quota!";
+
+    #[test]
+    fn sends_the_same_messages_as_limit_tracker() {
+        let messenger = MemoryMessenger::new();
+        let mut quotas = QuotaService::new(&messenger);
+        quotas.set_policy("storage", QuotaPolicy::new(100));
+        for value in [10, 80, 95, 100] {
+            quotas.set_value("acme", "storage", value).unwrap();
+        }
+        assert_eq!(messenger.take(), [WARNING, URGENT, ERROR]);
+    }
+    #[test]
+    fn messages_are_not_repeated_until_usage_drops() {
+        let messenger = MemoryMessenger::new();
+        let mut quotas = QuotaService::new(&messenger);
+        quotas.set_policy("storage", QuotaPolicy::new(100));
+        for value in [80, 85, 76] {
+            quotas.set_value("acme", "storage", value).unwrap();
+        }
+        assert_eq!(messenger.take(), [WARNING]);
+        // dropping from 90% to 80% is still at the warning level
+        quotas.set_value("acme", "storage", 91).unwrap();
+        quotas.set_value("acme", "storage", 80).unwrap();
+        assert_eq!(messenger.take(), [URGENT]);
+        quotas.set_value("acme", "storage", 50).unwrap();
+        quotas.set_value("acme", "storage", 80).unwrap();
+        assert_eq!(messenger.take(), [WARNING]);
+    }
+    #[test]
+    fn tenants_and_resources_are_tracked_separately() {
+        let messenger = MemoryMessenger::new();
+        let mut quotas = QuotaService::new(&messenger);
+        quotas.set_policy("storage", QuotaPolicy::new(100));
+        quotas.set_policy("seats", QuotaPolicy::new(10));
+        quotas.add("acme", "storage", 60).unwrap();
+        quotas.add("globex", "storage", 60).unwrap();
+        quotas.add("acme", "seats", 9).unwrap();
+        assert_eq!(messenger.take(), [URGENT]);
+        quotas.add("acme", "storage", 30).unwrap();
+        assert_eq!(messenger.take(), [URGENT]);
+        assert_eq!(quotas.value("globex", "storage"), Ok(60));
+        assert_eq!(quotas.value("initech", "seats"), Ok(0));
+        assert_eq!(quotas.is_within_quota("acme", "seats"), Ok(true));
+        quotas.add("acme", "seats", 1).unwrap();
+        assert_eq!(quotas.is_within_quota("acme", "seats"), Ok(false));
+        quotas.reset("acme", "seats");
+        assert_eq!(quotas.value("acme", "seats"), Ok(0));
+    }
+    #[test]
+    fn custom_thresholds_with_placeholders() {
+        let messenger = MemoryMessenger::new();
+        let mut quotas = QuotaService::new(&messenger);
+        let policy = QuotaPolicy::new(200).with_thresholds(vec![
+            Threshold::new(1.0, "{tenant} is out of {resource}"),
+            Threshold::new(0.5, "{tenant} used {value}/{max} {resource} ({percent}%)"),
+        ]);
+        quotas.set_policy("emails", policy);
+        quotas.set_value("acme", "emails", 120).unwrap();
+        quotas.set_value("acme", "emails", 250).unwrap();
+        assert_eq!(
+            messenger.take(),
+            ["acme used 120/200 emails (60%)", "acme is out of emails"]
+        );
+    }
+    #[test]
+    fn usage_resets_after_the_period() {
+        let now = Rc::new(Cell::new(Instant::now()));
+        let messenger = MemoryMessenger::new();
+        let clock = Rc::clone(&now);
+        let mut quotas = QuotaService::with_clock(&messenger, move || clock.get());
+        let day = Duration::from_secs(24 * 60 * 60);
+        quotas.set_policy("api_calls", QuotaPolicy::new(100).with_reset_every(day));
+
+        quotas.add("acme", "api_calls", 80).unwrap();
+        now.set(now.get() + day / 2);
+        quotas.add("acme", "api_calls", 10).unwrap();
+        assert_eq!(messenger.take(), [WARNING, URGENT]);
+
+        now.set(now.get() + day / 2);
+        assert_eq!(quotas.value("acme", "api_calls"), Ok(0));
+        // the warning is sent again in the new period
+        quotas.add("acme", "api_calls", 80).unwrap();
+        assert_eq!(messenger.take(), [WARNING]);
+    }
+    #[test]
+    fn many_periods_pass_at_once() {
+        let now = Rc::new(Cell::new(Instant::now()));
+        let messenger = MemoryMessenger::new();
+        let clock = Rc::clone(&now);
+        let mut quotas = QuotaService::with_clock(&messenger, move || clock.get());
+        let period = Duration::from_micros(1);
+        quotas.set_policy("api_calls", QuotaPolicy::new(100).with_reset_every(period));
+        // more periods than fit in a `u32`
+        now.set(now.get() + Duration::from_secs(5000));
+        quotas.add("acme", "api_calls", 10).unwrap();
+        assert_eq!(quotas.value("acme", "api_calls"), Ok(10));
+        now.set(now.get() + period);
+        assert_eq!(quotas.value("acme", "api_calls"), Ok(0));
+    }
+    #[test]
+    #[should_panic(expected = "must not be zero")]
+    fn zero_reset_period_panics() {
+        QuotaPolicy::new(100).with_reset_every(Duration::ZERO);
+    }
+    #[test]
+    fn unknown_resources_are_an_error() {
+        let messenger = MemoryMessenger::new();
+        let mut quotas = QuotaService::new(&messenger);
+        let error = quotas.set_value("acme", "gpus", 1).unwrap_err();
+        assert_eq!(error, QuotaError::UnknownResource(String::from("gpus")));
+        assert_eq!(error.to_string(), "no quota policy for resource `gpus`");
+    }
+    #[test]
+    fn file_messenger_appends_lines() {
+        let path = std::env::temp_dir().join(format!("quota-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let messenger = FileMessenger::open(&path).unwrap();
+            let mut quotas = QuotaService::new(&messenger);
+            quotas.set_policy("storage", QuotaPolicy::new(10));
+            quotas.set_value("acme", "storage", 10).unwrap();
+            assert_eq!(messenger.failed_writes(), 0);
+        }
+        FileMessenger::open(&path).unwrap().send("second");
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(log, format!("{}\nsecond\n", ERROR));
+    }
+}
@@ -0,0 +1,500 @@
+//! Instrumentation for watching values live and die, instead of sprinkling
+//! `println!` into `Drop` impls like `CustomSmartPointer` in `main.rs`.
+//!
+//! [`Traced<T>`] wraps a value and reports its creation, clones, moves into
+//! an `Rc` and its drop to a [`Tracer`], which can print the timeline or
+//! check the drop order in tests. [`TracingAllocator`] counts heap
+//! allocations when installed as the global allocator.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::{Cell, RefCell};
+use std::fmt::{self, Write};
+use std::ops::{Deref, DerefMut};
+use std::panic::Location;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// What happened to a traced value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Created,
+    /// Created by cloning the value with the given id.
+    Cloned {
+        from: u64,
+    },
+    MovedIntoRc,
+    Dropped,
+}
+
+/// One entry of a [`Tracer`]'s timeline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// Time since the tracer was created.
+    pub at: Duration,
+    /// Unique per traced value, clones get their own.
+    pub id: u64,
+    pub label: Rc<str>,
+    pub kind: EventKind,
+    /// Where the value was created, cloned or moved; `None` for drops,
+    /// which happen wherever the value goes out of scope.
+    pub location: Option<&'static Location<'static>>,
+}
+
+struct TracerInner {
+    start: Instant,
+    next_id: Cell<u64>,
+    events: RefCell<Vec<Event>>,
+}
+
+/// Collects the events of all values it wrapped; clones share the events.
+///
+/// # Examples
+///
+/// ```
+/// use smart_pointers::trace::Tracer;
+/// let tracer = Tracer::new();
+/// {
+///     let _d = tracer.wrap(String::from("other stuff"), "d");
+///     let _e = tracer.wrap(String::from("stuff stuff"), "e");
+/// } // dropped in reverse order of creation
+/// tracer.assert_drop_order(&["e", "d"]);
+/// ```
+#[derive(Clone)]
+pub struct Tracer {
+    inner: Rc<TracerInner>,
+}
+
+impl Tracer {
+    pub fn new() -> Tracer {
+        Tracer {
+            inner: Rc::new(TracerInner {
+                start: Instant::now(),
+                next_id: Cell::new(1),
+                events: RefCell::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Start tracing `value` under the name `label`.
+    #[track_caller]
+    pub fn wrap<T>(&self, value: T, label: &str) -> Traced<T> {
+        let label: Rc<str> = Rc::from(label);
+        let id = self.record(&label, EventKind::Created, Some(Location::caller()));
+        Traced {
+            value,
+            id,
+            label,
+            tracer: self.clone(),
+        }
+    }
+
+    /// Record the first event of a new value and return its id.
+    fn record(
+        &self,
+        label: &Rc<str>,
+        kind: EventKind,
+        location: Option<&'static Location<'static>>,
+    ) -> u64 {
+        let id = self.inner.next_id.get();
+        self.inner.next_id.set(id + 1);
+        self.push(id, label, kind, location);
+        id
+    }
+
+    fn push(
+        &self,
+        id: u64,
+        label: &Rc<str>,
+        kind: EventKind,
+        location: Option<&'static Location<'static>>,
+    ) {
+        self.inner.events.borrow_mut().push(Event {
+            at: self.inner.start.elapsed(),
+            id,
+            label: Rc::clone(label),
+            kind,
+            location,
+        });
+    }
+
+    pub fn events(&self) -> Vec<Event> {
+        self.inner.events.borrow().clone()
+    }
+
+    /// Forget all events recorded so far.
+    pub fn clear(&self) {
+        self.inner.events.borrow_mut().clear();
+    }
+
+    /// Labels of the dropped values, in the order they were dropped.
+    pub fn drop_order(&self) -> Vec<String> {
+        self.inner
+            .events
+            .borrow()
+            .iter()
+            .filter(|event| event.kind == EventKind::Dropped)
+            .map(|event| event.label.to_string())
+            .collect()
+    }
+
+    /// # Panics
+    ///
+    /// Panics with the whole timeline if the values were not dropped in
+    /// exactly this order.
+    #[track_caller]
+    pub fn assert_drop_order(&self, expected: &[&str]) {
+        let actual = self.drop_order();
+        if actual != expected {
+            panic!(
+                "unexpected drop order\n  expected: {:?}\n    actual: {:?}\ntimeline:\n{}",
+                expected,
+                actual,
+                self.timeline()
+            );
+        }
+    }
+
+    /// All events, one per line.
+    pub fn timeline(&self) -> String {
+        let mut out = String::new();
+        for event in self.inner.events.borrow().iter() {
+            let what = match event.kind {
+                EventKind::Created => String::from("created"),
+                EventKind::Cloned { from } => format!("cloned from #{}", from),
+                EventKind::MovedIntoRc => String::from("moved into Rc"),
+                EventKind::Dropped => String::from("dropped"),
+            };
+            // writing to a `String` can't fail
+            let _ = write!(
+                out,
+                "{:>10.3?}  #{} {}: {}",
+                event.at, event.id, event.label, what
+            );
+            if let Some(location) = event.location {
+                let _ = write!(out, " at {}", location);
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Tracer::new()
+    }
+}
+
+/// A value whose lifetime is recorded by a [`Tracer`], see [`Tracer::wrap`].
+///
+/// Derefs to the wrapped value like a `Box`.
+pub struct Traced<T> {
+    value: T,
+    id: u64,
+    label: Rc<str>,
+    tracer: Tracer,
+}
+
+impl<T> Traced<T> {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Move the value into an `Rc`, recording where it happened.
+    #[track_caller]
+    pub fn into_rc(self) -> Rc<Traced<T>> {
+        self.tracer.push(
+            self.id,
+            &self.label,
+            EventKind::MovedIntoRc,
+            Some(Location::caller()),
+        );
+        Rc::new(self)
+    }
+}
+
+impl<T: Clone> Clone for Traced<T> {
+    #[track_caller]
+    fn clone(&self) -> Self {
+        let id = self.tracer.record(
+            &self.label,
+            EventKind::Cloned { from: self.id },
+            Some(Location::caller()),
+        );
+        Traced {
+            value: self.value.clone(),
+            id,
+            label: Rc::clone(&self.label),
+            tracer: self.tracer.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Traced<T> {
+    fn drop(&mut self) {
+        self.tracer
+            .push(self.id, &self.label, EventKind::Dropped, None);
+    }
+}
+
+impl<T> Deref for Traced<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Traced<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Traced<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{} {} = {:?}", self.id, self.label, self.value)
+    }
+}
+
+/// Allocation counts, see [`TracingAllocator`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    pub allocations: u64,
+    pub deallocations: u64,
+    pub bytes_allocated: u64,
+    pub bytes_freed: u64,
+}
+
+impl AllocStats {
+    /// Bytes still allocated.
+    pub fn live_bytes(&self) -> i64 {
+        self.bytes_allocated as i64 - self.bytes_freed as i64
+    }
+
+    fn since(&self, earlier: &AllocStats) -> AllocStats {
+        AllocStats {
+            allocations: self.allocations - earlier.allocations,
+            deallocations: self.deallocations - earlier.deallocations,
+            bytes_allocated: self.bytes_allocated - earlier.bytes_allocated,
+            bytes_freed: self.bytes_freed - earlier.bytes_freed,
+        }
+    }
+}
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static BYTES_ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static BYTES_FREED: AtomicU64 = AtomicU64::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // `const` and without a destructor, so using it never allocates itself
+    static THREAD_STATS: Cell<AllocStats> = const {
+        Cell::new(AllocStats {
+            allocations: 0,
+            deallocations: 0,
+            bytes_allocated: 0,
+            bytes_freed: 0,
+        })
+    };
+}
+
+/// A global allocator that forwards to the system allocator and counts
+/// every allocation, in total and per thread.
+///
+/// Install it in a binary or test crate with
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: smart_pointers::trace::TracingAllocator = smart_pointers::trace::TracingAllocator;
+/// ```
+///
+/// Without that, all counts stay zero.
+pub struct TracingAllocator;
+
+impl TracingAllocator {
+    /// Counts of all threads since the program started.
+    pub fn stats() -> AllocStats {
+        AllocStats {
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            deallocations: DEALLOCATIONS.load(Ordering::Relaxed),
+            bytes_allocated: BYTES_ALLOCATED.load(Ordering::Relaxed),
+            bytes_freed: BYTES_FREED.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The most bytes that were allocated at the same time.
+    pub fn peak_bytes() -> usize {
+        PEAK_BYTES.load(Ordering::Relaxed)
+    }
+
+    /// Counts of the current thread, not disturbed by other threads.
+    pub fn thread_stats() -> AllocStats {
+        THREAD_STATS.with(Cell::get)
+    }
+
+    /// Run `f` and return what it allocated on the current thread.
+    pub fn measure<R>(f: impl FnOnce() -> R) -> (R, AllocStats) {
+        let before = TracingAllocator::thread_stats();
+        let result = f();
+        (result, TracingAllocator::thread_stats().since(&before))
+    }
+
+    fn count(allocated: usize, freed: usize) {
+        if allocated > 0 {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            let total =
+                BYTES_ALLOCATED.fetch_add(allocated as u64, Ordering::Relaxed) + allocated as u64;
+            let live = total.saturating_sub(BYTES_FREED.load(Ordering::Relaxed));
+            PEAK_BYTES.fetch_max(live as usize, Ordering::Relaxed);
+        }
+        if freed > 0 {
+            DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            BYTES_FREED.fetch_add(freed as u64, Ordering::Relaxed);
+        }
+        // fails while the thread is shutting down, only the totals count then
+        let _ = THREAD_STATS.try_with(|stats| {
+            let mut current = stats.get();
+            if allocated > 0 {
+                current.allocations += 1;
+                current.bytes_allocated += allocated as u64;
+            }
+            if freed > 0 {
+                current.deallocations += 1;
+                current.bytes_freed += freed as u64;
+            }
+            stats.set(current);
+        });
+    }
+}
+
+unsafe impl GlobalAlloc for TracingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // SAFETY: same contract as ours, forwarded unchanged
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            TracingAllocator::count(layout.size(), 0);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: same contract as ours, forwarded unchanged
+        unsafe { System.dealloc(ptr, layout) };
+        TracingAllocator::count(0, layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // SAFETY: same contract as ours, forwarded unchanged
+        let new = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new.is_null() {
+            // counted as freeing the old block and allocating a new one
+            TracingAllocator::count(new_size, layout.size());
+        }
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[global_allocator]
+    static ALLOCATOR: TracingAllocator = TracingAllocator;
+
+    #[test]
+    fn records_the_lifetime_of_values() {
+        let tracer = Tracer::new();
+        let a = tracer.wrap(vec![1, 2], "a");
+        let b = a.clone();
+        let shared = a.into_rc();
+        let also_shared = Rc::clone(&shared);
+        drop(shared);
+        drop(b);
+        drop(also_shared); // the last owner of the `Rc` drops the value
+
+        let kinds: Vec<_> = tracer.events().iter().map(|e| (e.id, e.kind)).collect();
+        assert_eq!(
+            kinds,
+            [
+                (1, EventKind::Created),
+                (2, EventKind::Cloned { from: 1 }),
+                (1, EventKind::MovedIntoRc),
+                (2, EventKind::Dropped),
+                (1, EventKind::Dropped),
+            ]
+        );
+        let events = tracer.events();
+        assert!(events.windows(2).all(|pair| pair[0].at <= pair[1].at));
+        assert_eq!(events[0].location.unwrap().file(), file!());
+        assert_eq!(
+            events[1].location.unwrap().line(),
+            events[0].location.unwrap().line() + 1
+        );
+    }
+    #[test]
+    fn drop_order_follows_scopes() {
+        let tracer = Tracer::new();
+        {
+            let c = tracer.wrap(String::from("my stuff"), "c");
+            drop(c);
+            let _d = tracer.wrap(String::from("other stuff"), "d");
+            let _e = tracer.wrap(String::from("stuff stuff"), "e");
+        }
+        tracer.assert_drop_order(&["c", "e", "d"]);
+        let moved = {
+            let f = tracer.wrap(1, "f");
+            let _g = tracer.wrap(2, "g");
+            f // moving out of the scope keeps `f` alive
+        };
+        tracer.assert_drop_order(&["c", "e", "d", "g"]);
+        drop(moved);
+        assert_eq!(tracer.drop_order().last().unwrap(), "f");
+    }
+    #[test]
+    #[should_panic(expected = "unexpected drop order")]
+    fn wrong_drop_order_panics() {
+        let tracer = Tracer::new();
+        drop(tracer.wrap((), "first"));
+        drop(tracer.wrap((), "second"));
+        tracer.assert_drop_order(&["second", "first"]);
+    }
+    #[test]
+    fn timeline_lists_every_event() {
+        let tracer = Tracer::new();
+        let mut value = tracer.wrap(5, "five");
+        *value += 1;
+        assert_eq!(format!("{:?}", value), "#1 five = 6");
+        drop(value);
+        let timeline = tracer.timeline();
+        let lines: Vec<_> = timeline.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("#1 five: created at ") && lines[0].contains(file!()));
+        assert!(lines[1].ends_with("#1 five: dropped"));
+        tracer.clear();
+        assert!(tracer.events().is_empty());
+    }
+    #[test]
+    fn allocator_counts_per_thread() {
+        let (boxed, stats) = TracingAllocator::measure(|| Box::new([0u8; 100]));
+        assert_eq!(stats.allocations, 1);
+        assert_eq!(stats.bytes_allocated, 100);
+        let (_, stats) = TracingAllocator::measure(|| drop(boxed));
+        assert_eq!(stats.deallocations, 1);
+        assert_eq!(stats.live_bytes(), -100);
+
+        let (_, stats) = TracingAllocator::measure(|| {
+            let mut v: Vec<u64> = Vec::with_capacity(1);
+            v.extend(0..10); // grows, so one realloc
+        });
+        assert_eq!(stats.live_bytes(), 0);
+        assert!(TracingAllocator::stats().allocations >= 2);
+        assert!(TracingAllocator::peak_bytes() >= 100);
+    }
+}
@@ -0,0 +1,354 @@
+//! A tree whose nodes own their children and only point back to their parent.
+//!
+//! Children are held with `Rc`, parents with `Weak`. If parents were held
+//! with `Rc` too, parent and child would keep each other alive forever and
+//! no node of the tree would ever be freed.
+
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::VecDeque;
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+struct Node<T> {
+    value: T,
+    parent: Weak<RefCell<Node<T>>>,
+    children: Vec<Rc<RefCell<Node<T>>>>,
+}
+
+/// A handle to one node of a tree; cloning it clones the handle, not the node.
+///
+/// A node stays alive as long as a handle to it or to one of its ancestors
+/// exists.
+///
+/// # Examples
+///
+/// ```
+/// use smart_pointers::tree::Tree;
+/// let root = Tree::new("root");
+/// let branch = root.add_child("branch");
+/// let leaf = branch.add_child("leaf");
+/// let path: Vec<_> = leaf.path_to_root().iter().map(|node| *node.value()).collect();
+/// assert_eq!(path, ["leaf", "branch", "root"]);
+/// drop(root);
+/// // only the root handle kept the root alive, the leaf just points back to it
+/// assert!(branch.parent().is_none());
+/// ```
+pub struct Tree<T> {
+    node: Rc<RefCell<Node<T>>>,
+}
+
+impl<T> Tree<T> {
+    /// A new tree with a single node.
+    pub fn new(value: T) -> Tree<T> {
+        Tree {
+            node: Rc::new(RefCell::new(Node {
+                value,
+                parent: Weak::new(),
+                children: Vec::new(),
+            })),
+        }
+    }
+
+    /// Add a new node with `value` as the last child of this one.
+    pub fn add_child(&self, value: T) -> Tree<T> {
+        let child = Tree::new(value);
+        self.attach(&child);
+        child
+    }
+
+    /// Move `subtree` (with all of its descendants) to the end of this
+    /// node's children, removing it from its old parent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `subtree` is this node or one of its ancestors, since a
+    /// node can't be its own descendant.
+    pub fn attach(&self, subtree: &Tree<T>) {
+        assert!(
+            !self.path_to_root().iter().any(|node| node.ptr_eq(subtree)),
+            "can't attach a node to itself or one of its descendants"
+        );
+        subtree.detach();
+        subtree.node.borrow_mut().parent = Rc::downgrade(&self.node);
+        self.node
+            .borrow_mut()
+            .children
+            .push(Rc::clone(&subtree.node));
+    }
+
+    /// Remove `child` from this node's children, it becomes the root of its
+    /// own tree. Returns `false` if it isn't a child of this node.
+    pub fn remove_child(&self, child: &Tree<T>) -> bool {
+        let mut node = self.node.borrow_mut();
+        let Some(index) = node
+            .children
+            .iter()
+            .position(|c| Rc::ptr_eq(c, &child.node))
+        else {
+            return false;
+        };
+        node.children.remove(index);
+        child.node.borrow_mut().parent = Weak::new();
+        true
+    }
+
+    /// Remove this node from its parent, if it has one.
+    pub fn detach(&self) {
+        if let Some(parent) = self.parent() {
+            parent.remove_child(self);
+        }
+    }
+
+    /// The parent, `None` for a root or if the parent has been dropped.
+    pub fn parent(&self) -> Option<Tree<T>> {
+        self.node
+            .borrow()
+            .parent
+            .upgrade()
+            .map(|node| Tree { node })
+    }
+
+    pub fn children(&self) -> Vec<Tree<T>> {
+        self.node
+            .borrow()
+            .children
+            .iter()
+            .map(|node| Tree {
+                node: Rc::clone(node),
+            })
+            .collect()
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.parent().is_none()
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.node.borrow().children.is_empty()
+    }
+
+    /// The topmost ancestor that is still alive.
+    pub fn root(&self) -> Tree<T> {
+        self.ancestors().last().unwrap_or_else(|| self.clone())
+    }
+
+    /// Number of edges between this node and the root.
+    pub fn depth(&self) -> usize {
+        self.ancestors().count()
+    }
+
+    /// The parent, grandparent and so on up to the root.
+    pub fn ancestors(&self) -> Ancestors<T> {
+        Ancestors {
+            next: self.parent(),
+        }
+    }
+
+    /// This node followed by all of its ancestors.
+    pub fn path_to_root(&self) -> Vec<Tree<T>> {
+        std::iter::once(self.clone())
+            .chain(self.ancestors())
+            .collect()
+    }
+
+    /// This node and its descendants, every node before its children.
+    pub fn depth_first(&self) -> DepthFirst<T> {
+        DepthFirst {
+            stack: vec![self.clone()],
+        }
+    }
+
+    /// This node and its descendants, level by level.
+    pub fn breadth_first(&self) -> BreadthFirst<T> {
+        BreadthFirst {
+            queue: VecDeque::from([self.clone()]),
+        }
+    }
+
+    /// Borrow the value of this node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed mutably.
+    pub fn value(&self) -> Ref<'_, T> {
+        Ref::map(self.node.borrow(), |node| &node.value)
+    }
+
+    /// Borrow the value of this node mutably.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed.
+    pub fn value_mut(&self) -> RefMut<'_, T> {
+        RefMut::map(self.node.borrow_mut(), |node| &mut node.value)
+    }
+
+    /// Whether both handles point to the same node.
+    pub fn ptr_eq(&self, other: &Tree<T>) -> bool {
+        Rc::ptr_eq(&self.node, &other.node)
+    }
+}
+
+impl<T> Clone for Tree<T> {
+    fn clone(&self) -> Self {
+        Tree {
+            node: Rc::clone(&self.node),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Tree<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let node = self.node.borrow();
+        if node.children.is_empty() {
+            return node.value.fmt(f);
+        }
+        // the parent is left out, printing it would go around in circles
+        f.debug_struct("Tree")
+            .field("value", &node.value)
+            .field("children", &self.children())
+            .finish()
+    }
+}
+
+/// Iterator returned by [`Tree::ancestors`].
+pub struct Ancestors<T> {
+    next: Option<Tree<T>>,
+}
+
+impl<T> Iterator for Ancestors<T> {
+    type Item = Tree<T>;
+
+    fn next(&mut self) -> Option<Tree<T>> {
+        let current = self.next.take()?;
+        self.next = current.parent();
+        Some(current)
+    }
+}
+
+/// Iterator returned by [`Tree::depth_first`].
+pub struct DepthFirst<T> {
+    stack: Vec<Tree<T>>,
+}
+
+impl<T> Iterator for DepthFirst<T> {
+    type Item = Tree<T>;
+
+    fn next(&mut self) -> Option<Tree<T>> {
+        let current = self.stack.pop()?;
+        // reversed, so the first child is popped first
+        self.stack.extend(current.children().into_iter().rev());
+        Some(current)
+    }
+}
+
+/// Iterator returned by [`Tree::breadth_first`].
+pub struct BreadthFirst<T> {
+    queue: VecDeque<Tree<T>>,
+}
+
+impl<T> Iterator for BreadthFirst<T> {
+    type Item = Tree<T>;
+
+    fn next(&mut self) -> Option<Tree<T>> {
+        let current = self.queue.pop_front()?;
+        self.queue.extend(current.children());
+        Some(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //        a
+    //      /   \
+    //     b     c
+    //    / \     \
+    //   d   e     f
+    fn sample() -> (Tree<char>, Vec<Tree<char>>) {
+        let a = Tree::new('a');
+        let b = a.add_child('b');
+        let c = a.add_child('c');
+        let d = b.add_child('d');
+        let e = b.add_child('e');
+        let f = c.add_child('f');
+        (a.clone(), vec![a, b, c, d, e, f])
+    }
+
+    fn values(nodes: impl IntoIterator<Item = Tree<char>>) -> String {
+        nodes.into_iter().map(|node| *node.value()).collect()
+    }
+
+    #[test]
+    fn traversals() {
+        let (root, nodes) = sample();
+        assert_eq!(values(root.depth_first()), "abdecf");
+        assert_eq!(values(root.breadth_first()), "abcdef");
+        assert_eq!(values(nodes[1].depth_first()), "bde");
+        assert_eq!(values(nodes[4].path_to_root()), "eba");
+        assert_eq!(nodes[4].depth(), 2);
+        assert!(nodes[4].root().ptr_eq(&root));
+        assert!(nodes[4].is_leaf() && !nodes[1].is_leaf());
+    }
+    #[test]
+    fn remove_and_attach() {
+        let (root, nodes) = sample();
+        let b = &nodes[1];
+        assert!(root.remove_child(b));
+        assert!(!root.remove_child(b));
+        assert!(b.is_root());
+        assert_eq!(values(root.depth_first()), "acf");
+
+        // moving a subtree takes it away from its old parent
+        nodes[5].attach(b);
+        assert_eq!(values(root.depth_first()), "acfbde");
+        nodes[2].attach(&nodes[4]);
+        assert_eq!(values(b.children()), "d");
+        assert_eq!(values(root.depth_first()), "acfbde");
+        assert_eq!(values(nodes[4].path_to_root()), "eca");
+    }
+    #[test]
+    #[should_panic(expected = "one of its descendants")]
+    fn attaching_an_ancestor_panics() {
+        let (root, nodes) = sample();
+        nodes[3].attach(&root);
+    }
+    #[test]
+    fn values_can_be_changed_through_any_handle() {
+        let (root, nodes) = sample();
+        *nodes[5].value_mut() = 'z';
+        assert_eq!(values(root.breadth_first()), "abcdez");
+        assert_eq!(
+            format!("{:?}", nodes[2]),
+            "Tree { value: 'c', children: ['z'] }"
+        );
+    }
+    #[test]
+    fn parents_do_not_keep_children_alive_in_a_cycle() {
+        let (root, nodes) = sample();
+        let weak: Vec<_> = nodes.iter().map(|node| Rc::downgrade(&node.node)).collect();
+        // every node is owned by its parent only, plus the handle in `nodes`
+        assert!(nodes[1..]
+            .iter()
+            .all(|node| Rc::strong_count(&node.node) == 2));
+        // children point back to the parent weakly
+        assert_eq!(Rc::weak_count(&nodes[1].node), 2 + 1);
+        drop(nodes);
+        drop(root);
+        assert!(weak.iter().all(|weak| weak.strong_count() == 0));
+        assert!(weak.iter().all(|weak| weak.upgrade().is_none()));
+    }
+    #[test]
+    fn children_outlive_a_dropped_parent() {
+        let leaf = {
+            let root = Tree::new(1);
+            root.add_child(2)
+        };
+        assert!(leaf.parent().is_none());
+        assert_eq!(leaf.depth(), 0);
+        assert!(leaf.root().ptr_eq(&leaf));
+        assert_eq!(Rc::strong_count(&leaf.node), 1);
+        assert_eq!(Rc::weak_count(&leaf.node), 0);
+    }
+}
@@ -0,0 +1,220 @@
+//! # thread_pool
+//!
+//! `thread_pool` generalizes the `thread::spawn` examples of the `concurrency`
+//! crate into a reusable pool with a fixed number of worker threads.
+//! Jobs are sent to the workers over an `mpsc` channel, a panicking job
+//! does not take its worker down and dropping the pool waits for all
+//! workers to finish.
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+/// A boxed closure that is executed once by one of the workers.
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Error returned by [`ThreadPool::build`] for an invalid pool size.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PoolCreationError;
+
+impl fmt::Display for PoolCreationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a thread pool needs at least one worker")
+    }
+}
+
+impl std::error::Error for PoolCreationError {}
+
+/// A fixed-size pool of worker threads.
+pub struct ThreadPool {
+    workers: Vec<Worker>,
+    // `Option` so that `drop` can take the sender and close the channel
+    sender: Option<mpsc::Sender<Job>>,
+    panicked_jobs: Arc<AtomicUsize>,
+}
+
+impl ThreadPool {
+    /// Create a new ThreadPool.
+    ///
+    /// The size is the number of threads in the pool.
+    ///
+    /// # Panics
+    ///
+    /// The `new` function will panic if the size is zero.
+    pub fn new(size: usize) -> ThreadPool {
+        ThreadPool::build(size).expect("thread pool size must be greater than zero")
+    }
+
+    /// Create a new ThreadPool without panicking.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `PoolCreationError` if the size is zero.
+    pub fn build(size: usize) -> Result<ThreadPool, PoolCreationError> {
+        if size == 0 {
+            return Err(PoolCreationError);
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        // all workers share the single receiving end of the channel
+        let receiver = Arc::new(Mutex::new(receiver));
+        let panicked_jobs = Arc::new(AtomicUsize::new(0));
+
+        let workers = (0..size)
+            .map(|id| Worker::new(id, Arc::clone(&receiver), Arc::clone(&panicked_jobs)))
+            .collect();
+
+        Ok(ThreadPool {
+            workers,
+            sender: Some(sender),
+            panicked_jobs,
+        })
+    }
+
+    /// Queue a closure to be run by the next idle worker.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use thread_pool::ThreadPool;
+    /// let pool = ThreadPool::new(2);
+    /// let (tx, rx) = mpsc::channel();
+    /// pool.execute(move || tx.send(21 * 2).unwrap());
+    /// assert_eq!(rx.recv().unwrap(), 42);
+    /// ```
+    pub fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let job = Box::new(f);
+        // the receivers only go away in `drop`, so sending cannot fail here
+        self.sender
+            .as_ref()
+            .expect("sender is only taken on drop")
+            .send(job)
+            .expect("workers hold the receiver until the pool is dropped");
+    }
+
+    /// Number of worker threads in the pool.
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Number of jobs that panicked so far.
+    ///
+    /// Panics are caught inside the worker, so the pool keeps its full size.
+    /// Note that this only works with unwinding: the workspace `release`
+    /// profile uses `panic = 'abort'`, which ends the whole process instead.
+    pub fn panicked_jobs(&self) -> usize {
+        self.panicked_jobs.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        // closing the channel makes `recv` return an error in every worker,
+        // which lets them leave their loop once the queue is empty
+        drop(self.sender.take());
+
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                // jobs never unwind through the worker loop, so joining cannot fail
+                thread.join().unwrap();
+            }
+        }
+    }
+}
+
+struct Worker {
+    _id: usize,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Worker {
+    fn new(
+        id: usize,
+        receiver: Arc<Mutex<mpsc::Receiver<Job>>>,
+        panicked_jobs: Arc<AtomicUsize>,
+    ) -> Worker {
+        let thread = thread::Builder::new()
+            .name(format!("thread-pool-worker-{id}"))
+            .spawn(move || loop {
+                // the lock guard is a temporary, so it is released before the job runs
+                let message = receiver.lock().unwrap().recv();
+                match message {
+                    Ok(job) => {
+                        // a panicking job must not kill the worker, so catch it here
+                        if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                            panicked_jobs.fetch_add(1, Ordering::SeqCst);
+                        }
+                    }
+                    // the sender was dropped, so the pool is shutting down
+                    Err(_) => break,
+                }
+            })
+            .expect("failed to spawn worker thread");
+
+        Worker {
+            _id: id,
+            thread: Some(thread),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn build_rejects_zero_workers() {
+        assert_eq!(ThreadPool::build(0).err(), Some(PoolCreationError));
+    }
+    #[test]
+    #[should_panic(expected = "greater than zero")]
+    fn new_panics_on_zero_workers() {
+        ThreadPool::new(0);
+    }
+    #[test]
+    fn runs_all_jobs() {
+        let pool = ThreadPool::new(4);
+        assert_eq!(pool.size(), 4);
+        let (tx, rx) = mpsc::channel();
+        for i in 0..20 {
+            let tx = tx.clone();
+            pool.execute(move || tx.send(i).unwrap());
+        }
+        drop(tx);
+        let mut results: Vec<i32> = rx.iter().collect();
+        results.sort();
+        assert_eq!(results, (0..20).collect::<Vec<_>>());
+    }
+    #[test]
+    fn drop_waits_for_queued_jobs() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        {
+            let pool = ThreadPool::new(2);
+            for _ in 0..8 {
+                let counter = Arc::clone(&counter);
+                pool.execute(move || {
+                    thread::sleep(Duration::from_millis(5));
+                    counter.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        } // pool goes out of scope and joins all workers
+        assert_eq!(counter.load(Ordering::SeqCst), 8);
+    }
+    #[test]
+    fn panicking_job_does_not_kill_worker() {
+        let pool = ThreadPool::new(1);
+        pool.execute(|| panic!("job failed on purpose"));
+        // the single worker must still be alive to run this job
+        let (tx, rx) = mpsc::channel();
+        pool.execute(move || tx.send("still alive").unwrap());
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok("still alive"));
+        assert_eq!(pool.panicked_jobs(), 1);
+    }
+}
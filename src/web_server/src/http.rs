@@ -0,0 +1,371 @@
+//! Just enough HTTP/1.1 to serve static files: parsing of the request line
+//! and headers, and writing of responses.
+
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+
+/// Upper bound for the request line and every header line.
+const MAX_LINE_LENGTH: usize = 8 * 1024;
+/// Upper bound for the number of headers in one request.
+const MAX_HEADERS: usize = 100;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Head,
+    // any other method is parsed but answered with `405 Method Not Allowed`
+    Other(String),
+}
+
+impl Method {
+    fn parse(method: &str) -> Method {
+        match method {
+            "GET" => Method::Get,
+            "HEAD" => Method::Head,
+            other => Method::Other(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Method::Get => write!(f, "GET"),
+            Method::Head => write!(f, "HEAD"),
+            Method::Other(method) => write!(f, "{method}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Request {
+    pub method: Method,
+    pub target: String,
+    pub version: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    /// Read the request line and all headers from `reader`.
+    ///
+    /// A request body is not read, since only `GET` and `HEAD` are served.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParseError::Io` if reading fails and `ParseError::Malformed`
+    /// if the request is not valid HTTP/1.x.
+    pub fn parse(reader: &mut impl BufRead) -> Result<Request, ParseError> {
+        let request_line = read_line(reader)?;
+        let mut parts = request_line.split(' ');
+        // `split(' ')` (instead of `split_whitespace`) rejects doubled spaces
+        let (method, target, version) =
+            match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some(m), Some(t), Some(v), None) if !m.is_empty() && !t.is_empty() => (m, t, v),
+                _ => return Err(ParseError::Malformed("invalid request line")),
+            };
+        if version != "HTTP/1.1" && version != "HTTP/1.0" {
+            return Err(ParseError::Malformed("unsupported HTTP version"));
+        }
+        if !target.starts_with('/') {
+            return Err(ParseError::Malformed(
+                "request target must be an absolute path",
+            ));
+        }
+
+        let mut headers = Vec::new();
+        loop {
+            let line = read_line(reader)?;
+            if line.is_empty() {
+                break; // an empty line ends the header section
+            }
+            if headers.len() == MAX_HEADERS {
+                return Err(ParseError::Malformed("too many headers"));
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or(ParseError::Malformed("header without colon"))?;
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(ParseError::Malformed("invalid header name"));
+            }
+            headers.push((name.to_string(), value.trim().to_string()));
+        }
+
+        if version == "HTTP/1.1" && !headers.iter().any(|(n, _)| n.eq_ignore_ascii_case("host")) {
+            return Err(ParseError::Malformed(
+                "HTTP/1.1 requests need a Host header",
+            ));
+        }
+
+        Ok(Request {
+            method: Method::parse(method),
+            target: target.to_string(),
+            version: version.to_string(),
+            headers,
+        })
+    }
+
+    /// Look up a header value, header names are case insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The path of the target without query string or fragment.
+    pub fn path(&self) -> &str {
+        let end = self.target.find(['?', '#']).unwrap_or(self.target.len());
+        &self.target[..end]
+    }
+}
+
+/// Read one CRLF (or LF) terminated line without the line ending.
+fn read_line(reader: &mut impl BufRead) -> Result<String, ParseError> {
+    let mut line = Vec::new();
+    // `take` keeps a client from sending us one endless line
+    let read = reader
+        .by_ref()
+        .take(MAX_LINE_LENGTH as u64 + 1)
+        .read_until(b'\n', &mut line)?;
+    if read == 0 {
+        return Err(ParseError::Malformed(
+            "connection closed before end of headers",
+        ));
+    }
+    if line.last() != Some(&b'\n') {
+        return Err(ParseError::Malformed("line too long"));
+    }
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    String::from_utf8(line).map_err(|_| ParseError::Malformed("request is not valid UTF-8"))
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    Io(io::Error),
+    Malformed(&'static str),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Io(e) => write!(f, "could not read request: {e}"),
+            ParseError::Malformed(reason) => write!(f, "malformed request: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<io::Error> for ParseError {
+    fn from(error: io::Error) -> Self {
+        ParseError::Io(error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    BadRequest,
+    NotFound,
+    MethodNotAllowed,
+    InternalServerError,
+}
+
+impl Status {
+    pub fn code(&self) -> u16 {
+        match self {
+            Status::Ok => 200,
+            Status::BadRequest => 400,
+            Status::NotFound => 404,
+            Status::MethodNotAllowed => 405,
+            Status::InternalServerError => 500,
+        }
+    }
+
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Status::Ok => "OK",
+            Status::BadRequest => "Bad Request",
+            Status::NotFound => "Not Found",
+            Status::MethodNotAllowed => "Method Not Allowed",
+            Status::InternalServerError => "Internal Server Error",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: Status,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    // `HEAD` responses carry the headers of the `GET` response but no body
+    omit_body: bool,
+}
+
+impl Response {
+    pub fn new(status: Status, content_type: &str, body: Vec<u8>) -> Response {
+        Response {
+            status,
+            headers: vec![(String::from("Content-Type"), content_type.to_string())],
+            body,
+            omit_body: false,
+        }
+    }
+
+    /// A small HTML page for error statuses.
+    pub fn error(status: Status) -> Response {
+        let body = format!(
+            "<!DOCTYPE html>\n<html><head><title>{code} {reason}</title></head>\
+             <body><h1>{code} {reason}</h1></body></html>\n",
+            code = status.code(),
+            reason = status.reason()
+        );
+        Response::new(status, "text/html; charset=utf-8", body.into_bytes())
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Response {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn without_body(mut self) -> Response {
+        self.omit_body = true;
+        self
+    }
+
+    /// Serialize the response, `Content-Length` and `Connection` are added here.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\n",
+            self.status.code(),
+            self.status.reason()
+        )?;
+        for (name, value) in &self.headers {
+            write!(writer, "{name}: {value}\r\n")?;
+        }
+        write!(writer, "Content-Length: {}\r\n", self.body.len())?;
+        // every connection serves exactly one request
+        write!(writer, "Connection: close\r\n\r\n")?;
+        if !self.omit_body {
+            writer.write_all(&self.body)?;
+        }
+        writer.flush()
+    }
+}
+
+/// Guess the content type from the file extension.
+pub fn content_type(path: &str) -> &'static str {
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(raw: &str) -> Result<Request, ParseError> {
+        Request::parse(&mut raw.as_bytes())
+    }
+
+    #[test]
+    fn parses_request_line_and_headers() {
+        let request =
+            parse("GET /index.html?x=1 HTTP/1.1\r\nHost: localhost\r\nAccept:  */*\r\n\r\n")
+                .unwrap();
+        assert_eq!(request.method, Method::Get);
+        assert_eq!(request.target, "/index.html?x=1");
+        assert_eq!(request.path(), "/index.html");
+        assert_eq!(request.version, "HTTP/1.1");
+        assert_eq!(request.header("host"), Some("localhost"));
+        assert_eq!(request.header("ACCEPT"), Some("*/*"));
+        assert_eq!(request.header("missing"), None);
+    }
+    #[test]
+    fn accepts_bare_line_feeds_and_http_1_0() {
+        let request = parse("HEAD / HTTP/1.0\n\n").unwrap();
+        assert_eq!(request.method, Method::Head);
+    }
+    #[test]
+    fn keeps_unknown_methods() {
+        let request = parse("DELETE / HTTP/1.1\r\nHost: a\r\n\r\n").unwrap();
+        assert_eq!(request.method, Method::Other(String::from("DELETE")));
+    }
+    #[test]
+    fn rejects_malformed_requests() {
+        let bad = [
+            "",
+            "GET /\r\n\r\n",
+            "GET  / HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET / HTTP/2.0\r\n\r\n",
+            "GET index.html HTTP/1.1\r\nHost: a\r\n\r\n",
+            "GET / HTTP/1.1\r\nno colon here\r\n\r\n",
+            "GET / HTTP/1.1\r\n\r\n",        // no Host header
+            "GET / HTTP/1.1\r\nHost: a\r\n", // headers never end
+        ];
+        for raw in bad {
+            assert!(
+                matches!(parse(raw), Err(ParseError::Malformed(_))),
+                "`{}` should be rejected",
+                raw.escape_debug()
+            );
+        }
+    }
+    #[test]
+    fn rejects_overlong_lines() {
+        let raw = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE_LENGTH));
+        assert!(matches!(
+            parse(&raw),
+            Err(ParseError::Malformed("line too long"))
+        ));
+    }
+    #[test]
+    fn writes_response() {
+        let mut out = Vec::new();
+        Response::new(Status::Ok, "text/plain", b"hi".to_vec())
+            .write_to(&mut out)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 2\r\nConnection: close\r\n\r\nhi"
+        );
+    }
+    #[test]
+    fn head_response_keeps_content_length() {
+        let mut out = Vec::new();
+        Response::error(Status::NotFound)
+            .without_body()
+            .write_to(&mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(!out.contains("Content-Length: 0"));
+        assert!(out.ends_with("\r\n\r\n"));
+    }
+    #[test]
+    fn guesses_content_types() {
+        assert_eq!(content_type("/index.HTML"), "text/html; charset=utf-8");
+        assert_eq!(content_type("/style.css"), "text/css; charset=utf-8");
+        assert_eq!(content_type("/logo.png"), "image/png");
+        assert_eq!(content_type("/README"), "application/octet-stream");
+    }
+}
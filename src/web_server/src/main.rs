@@ -0,0 +1,56 @@
+mod http;
+mod server;
+
+use std::env;
+use std::io::{self, BufRead};
+use std::process;
+use std::thread;
+
+use server::Server;
+
+// Execute `cargo run --bin web_server` from the `web_server` directory and
+// try e.g. `curl -i http://127.0.0.1:7878/` or `curl -I http://127.0.0.1:7878/style.css`.
+// Optional arguments: `cargo run --bin web_server -- <root_dir> <address> <workers>`
+// Type `quit` and press enter to shut the server down gracefully.
+fn main() {
+    let mut args = env::args().skip(1);
+    let root = args.next().unwrap_or_else(|| String::from("public"));
+    let addr = args
+        .next()
+        .unwrap_or_else(|| String::from("127.0.0.1:7878"));
+    let workers = match args.next().map(|w| w.parse::<usize>()) {
+        None => 4,
+        Some(Ok(workers)) => workers,
+        Some(Err(e)) => {
+            eprintln!("Problem parsing the number of workers: {e}");
+            process::exit(1);
+        }
+    };
+
+    let server = Server::bind(&addr, &root, workers).unwrap_or_else(|err| {
+        eprintln!("Problem starting the server: {err}");
+        process::exit(1);
+    });
+    let shutdown = server.shutdown_handle().unwrap_or_else(|err| {
+        eprintln!("Problem starting the server: {err}");
+        process::exit(1);
+    });
+
+    // wait for `quit` on stdin in the background, `run` blocks the main thread
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            match line {
+                Ok(line) if line.trim() == "quit" => {
+                    shutdown.shutdown();
+                    return;
+                }
+                Ok(_) => continue,
+                Err(_) => return,
+            }
+        }
+        // stdin is closed (e.g. running in the background), keep serving
+    });
+
+    println!("Serving `{root}` on http://{addr} with {workers} workers, type `quit` to stop.");
+    server.run();
+}
@@ -0,0 +1,268 @@
+//! The multithreaded server: accepts connections on the main thread and
+//! hands each one to a `ThreadPool` worker that serves files from `root`.
+
+use std::fs;
+use std::io::{self, BufReader};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use thread_pool::ThreadPool;
+
+use crate::http::{self, Method, Request, Response, Status};
+
+/// Slow clients must not block a worker forever.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Server {
+    listener: TcpListener,
+    root: Arc<PathBuf>,
+    pool: ThreadPool,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl Server {
+    /// Bind to `addr` and serve the files below `root` with `workers` threads.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address cannot be bound or `root` is not
+    /// an existing directory.
+    pub fn bind(
+        addr: impl ToSocketAddrs,
+        root: impl AsRef<Path>,
+        workers: usize,
+    ) -> io::Result<Server> {
+        // canonicalize once, so that every served path can be checked against it
+        let root = fs::canonicalize(root)?;
+        if !root.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "root must be a directory",
+            ));
+        }
+        let pool = ThreadPool::build(workers)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        Ok(Server {
+            listener: TcpListener::bind(addr)?,
+            root: Arc::new(root),
+            pool,
+            shutdown: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// A handle that can stop `run` from another thread.
+    pub fn shutdown_handle(&self) -> io::Result<ShutdownHandle> {
+        Ok(ShutdownHandle {
+            flag: Arc::clone(&self.shutdown),
+            addr: self.local_addr()?,
+        })
+    }
+
+    /// Accept connections until shutdown is requested.
+    ///
+    /// Dropping the server at the end joins the pool, so requests that are
+    /// already accepted are answered before this returns.
+    pub fn run(self) {
+        for stream in self.listener.incoming() {
+            if self.shutdown.load(Ordering::SeqCst) {
+                break;
+            }
+            match stream {
+                Ok(stream) => {
+                    let root = Arc::clone(&self.root);
+                    self.pool.execute(move || {
+                        if let Err(e) = handle_connection(stream, &root) {
+                            eprintln!("Connection error: {e}");
+                        }
+                    });
+                }
+                Err(e) => eprintln!("Failed to accept connection: {e}"),
+            }
+        }
+        println!("Shutting down, waiting for {} workers.", self.pool.size());
+    }
+}
+
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    flag: Arc<AtomicBool>,
+    addr: SocketAddr,
+}
+
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+        // `accept` blocks, so wake it up with a dummy connection
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, root: &Path) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let response = match Request::parse(&mut reader) {
+        Ok(request) => {
+            let response = respond(&request, root);
+            println!(
+                "{} {} {} -> {} ({})",
+                request.method,
+                request.target,
+                request.version,
+                response.status.code(),
+                request.header("User-Agent").unwrap_or("-")
+            );
+            response
+        }
+        // the wake-up connection of `ShutdownHandle` ends up here as well
+        Err(http::ParseError::Io(e)) => return Err(e),
+        Err(http::ParseError::Malformed(_)) => Response::error(Status::BadRequest),
+    };
+    response.write_to(&mut stream)
+}
+
+fn respond(request: &Request, root: &Path) -> Response {
+    match &request.method {
+        Method::Get => serve_file(request.path(), root),
+        Method::Head => serve_file(request.path(), root).without_body(),
+        Method::Other(_) => {
+            Response::error(Status::MethodNotAllowed).with_header("Allow", "GET, HEAD")
+        }
+    }
+}
+
+fn serve_file(path: &str, root: &Path) -> Response {
+    let Some(relative) = decode_path(path) else {
+        return Response::error(Status::BadRequest);
+    };
+    let mut file = root.join(relative);
+    if file.is_dir() {
+        file.push("index.html");
+    }
+    // symlinks could still point outside of `root`, so check the real path
+    let file = match fs::canonicalize(&file) {
+        Ok(file) if file.starts_with(root) => file,
+        _ => return Response::error(Status::NotFound),
+    };
+    match fs::read(&file) {
+        Ok(body) => Response::new(
+            Status::Ok,
+            http::content_type(&file.to_string_lossy()),
+            body,
+        ),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Response::error(Status::NotFound),
+        Err(_) => Response::error(Status::InternalServerError),
+    }
+}
+
+/// Percent-decode the path and turn it into a relative path below the root.
+///
+/// Returns `None` for invalid escapes and for paths containing `..`.
+fn decode_path(path: &str) -> Option<PathBuf> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    let decoded = String::from_utf8(decoded).ok()?;
+
+    let mut relative = PathBuf::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => continue,
+            ".." => return None,
+            // a decoded `\` or NUL byte has no business in a file name
+            s if s.contains(['\\', '\0']) => return None,
+            s => relative.push(s),
+        }
+    }
+    Some(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::thread;
+
+    #[test]
+    fn decodes_paths() {
+        assert_eq!(decode_path("/"), Some(PathBuf::new()));
+        assert_eq!(
+            decode_path("/a/./b%20c.txt"),
+            Some(PathBuf::from("a/b c.txt"))
+        );
+        assert_eq!(decode_path("/a/../../etc/passwd"), None);
+        assert_eq!(decode_path("/%2e%2e/secret"), None);
+        assert_eq!(decode_path("/bad%zz"), None);
+        assert_eq!(decode_path("/cut%2"), None);
+    }
+
+    fn request(addr: SocketAddr, raw: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(raw.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn serves_files_over_tcp() {
+        let root = std::env::temp_dir().join(format!("web_server_test_{}", std::process::id()));
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("index.html"), "<h1>home</h1>").unwrap();
+        fs::write(root.join("sub/style.css"), "body {}").unwrap();
+
+        let server = Server::bind("127.0.0.1:0", &root, 2).unwrap();
+        let addr = server.local_addr().unwrap();
+        let shutdown = server.shutdown_handle().unwrap();
+        let handle = thread::spawn(move || server.run());
+
+        let home = request(addr, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(home.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(home.contains("Content-Type: text/html; charset=utf-8\r\n"));
+        assert!(home.ends_with("<h1>home</h1>"));
+
+        let css = request(
+            addr,
+            "HEAD /sub/style.css HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+        assert!(css.contains("Content-Type: text/css; charset=utf-8\r\n"));
+        assert!(css.contains("Content-Length: 7\r\n"));
+        assert!(css.ends_with("\r\n\r\n"));
+
+        let missing = request(addr, "GET /nope.html HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(missing.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        let post = request(addr, "POST / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(post.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        assert!(post.contains("Allow: GET, HEAD\r\n"));
+
+        let garbage = request(addr, "hello\r\n\r\n");
+        assert!(garbage.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+
+        let escape = request(
+            addr,
+            "GET /../Cargo.toml HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+        assert!(escape.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+
+        shutdown.shutdown();
+        handle.join().unwrap();
+        fs::remove_dir_all(root).unwrap();
+    }
+}